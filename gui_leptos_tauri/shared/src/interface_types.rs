@@ -39,6 +39,11 @@ pub struct UpdatePosteriorArgs{
     pub opportunityCostWeight: f64,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProbaInitArgs{
+    pub seed: Option<u64>, // None keeps the seed of the proba grid problem
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ClickCellArgs{
     pub x: usize,
@@ -46,4 +51,82 @@ pub struct ClickCellArgs{
     pub r: u8,
     pub g: u8,
     pub b: u8,
-}
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[allow(non_snake_case)]
+pub struct SetNetClassArgs{
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub netClass: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProbaSolveResult{
+    pub grid: ColorGrid,
+    pub report: String, // how the solver converged, or why it stopped
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LoadRouterConfigArgs{
+    pub path: String, // a .toml or .json file
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OpenBoardArgs{
+    pub path: String, // a .kicad_pcb or .dsn file
+    pub pitch: f64, // the size of a grid cell in mm
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExportBoardArgs{
+    pub path: String, // where to write the routed copy of the opened .kicad_pcb file or the .ses session
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProblemFileArgs{
+    pub path: String, // a .json or .toml problem file
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NetHeatmap{
+    pub net_id: usize,
+    pub route_color: Color,
+    pub occupancy: Vec<Vec<f64>>, // occupancy[y][x] is the probability that the net covers the cell
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TraceInspection{
+    pub trace_id: usize,
+    pub net_id: usize,
+    pub pad_pair_id: usize,
+    pub iteration: usize,
+    pub score: f64,
+    pub prior: f64, // shared with the other traces the pad pair found in the same iteration
+    pub posterior: Option<f64>, // None until the posterior is updated
+    pub colliding_traces: Vec<usize>,
+    pub cells: Vec<(usize, usize)>, // (x, y) of the cells the trace covers
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProbaInspection{
+    pub heatmaps: Vec<NetHeatmap>, // sorted by net
+    pub traces: Vec<TraceInspection>, // sorted by trace
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProbaSnapshotInfo{
+    pub id: usize,
+    pub parent: Option<usize>, // the snapshot this one was reached from
+    pub iteration: usize, // the number of completed sampling passes
+    pub num_traces: usize,
+    pub posteriors_updated: bool,
+    pub label: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[allow(non_snake_case)]
+pub struct RestoreSnapshotArgs{
+    pub snapshotId: usize,
+}
@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use crate::{grid::Grid, kicad::KicadDocument, proba_grid::ProbaGridProblem, specctra::DsnDesign};
+
+/// A board opened from a file, which keeps what is needed to write the routes back in its format
+#[derive(Debug, Clone)]
+pub enum BoardFile {
+    Kicad(KicadDocument),
+    Specctra { design: DsnDesign, pitch: f64 },
+}
+
+impl BoardFile {
+    /// Opens a .kicad_pcb board or a Specctra .dsn design, depending on the extension of path
+    pub fn open(path: &Path, pitch: f64) -> Result<Self, String> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("kicad_pcb") => Ok(BoardFile::Kicad(KicadDocument::open(path, pitch)?)),
+            Some("dsn") => {
+                let design = DsnDesign::load(path)?;
+                // fails early if the pitch does not fit the design
+                design.board.grid_mapping(pitch)?;
+                Ok(BoardFile::Specctra { design, pitch })
+            }
+            _ => Err(format!(
+                "Cannot open {}, expected a .kicad_pcb or .dsn file",
+                path.display()
+            )),
+        }
+    }
+    pub fn to_grid(&self) -> Result<Grid, String> {
+        match self {
+            BoardFile::Kicad(document) => document.board.to_grid(document.mapping.pitch),
+            BoardFile::Specctra { design, pitch } => design.to_grid(*pitch),
+        }
+    }
+    pub fn to_proba_grid_problem(&self) -> Result<ProbaGridProblem, String> {
+        match self {
+            BoardFile::Kicad(document) => {
+                document.board.to_proba_grid_problem(document.mapping.pitch)
+            }
+            BoardFile::Specctra { design, pitch } => design.to_proba_grid_problem(*pitch),
+        }
+    }
+    /// Writes the routes of the grid as a routed copy of a KiCad board or as a Specctra session
+    pub fn save_routed(&self, grid: &Grid, path: &Path) -> Result<(), String> {
+        match self {
+            BoardFile::Kicad(document) => document.save_routed(grid, path),
+            BoardFile::Specctra { design, pitch } => design.save_session(grid, *pitch, path),
+        }
+    }
+}
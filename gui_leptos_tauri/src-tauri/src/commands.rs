@@ -3,14 +3,29 @@ use std::{
     vec,
 };
 
-use shared::interface_types::{ClickCellArgs, Color, ColorGrid, MyResult, NewGridArgs};
+use shared::interface_types::{
+    ClickCellArgs, Color, ColorGrid, ExportBoardArgs, LoadRouterConfigArgs, MyResult, NewGridArgs,
+    OpenBoardArgs, ProbaInitArgs, ProbaInspection, ProbaSnapshotInfo, ProbaSolveResult,
+    ProblemFileArgs, RestoreSnapshotArgs, SetNetClassArgs, UpdatePosteriorArgs,
+};
 
 use crate::{
+    board_file::BoardFile,
+    extract_routing::extract_routing,
     grid::{Grid, Net, Point},
-    hyperparameters::{OPPORTUNITY_COST_WEIGHT, SCORE_WEIGHT},
-    naive_route::naive_route,
+    hyperparameters::{DEFAULT_PROBA_SEED, NAIVE_GRID_LAYERS},
+    naive_route::naive_route_with_decomposition,
+    pathfinder_route::pathfinder_route,
     proba_grid::{NetID, ProbaGridProblem, ProbaGridState},
-    solve_proba_grid::{initialize_proba_grid, sample_new_traces, update_posterior},
+    proba_history::ProbaHistory,
+    problem_file::{ProblemFile, ProblemFileKind},
+    router_config::RouterConfig,
+    solve_proba_grid::{
+        finish_sampling, initialize_proba_grid_with_decomposition, sample_next_net,
+        sample_next_pair, solve_proba_grid_observed, start_sampling, update_posterior,
+        ConvergenceCriterion,
+    },
+    steiner_tree::NetDecomposition,
     tauri_state::TAURI_STATE,
     TCP_STREAM,
 };
@@ -50,7 +65,8 @@ where
 fn naive_new_grid_local(rows: usize, cols: usize) -> Result<ColorGrid, String> {
     println!("Creating new grid locally ");
     let mut tauri_state = TAURI_STATE.lock().unwrap();
-    tauri_state.naive_grid = Grid::new(rows, cols);
+    tauri_state.naive_grid = Grid::new_with_layers(rows, cols, NAIVE_GRID_LAYERS);
+    tauri_state.naive_board = None;
     let grid = tauri_state.naive_grid.to_color_grid();
     Ok(grid)
 }
@@ -70,6 +86,147 @@ pub fn naive_new_grid(rows: usize, cols: usize) -> MyResult<ColorGrid, String> {
     }
 }
 
+fn naive_open_board_local(path: String, pitch: f64) -> Result<ColorGrid, String> {
+    let board = BoardFile::open(std::path::Path::new(&path), pitch)?;
+    let grid = board.to_grid()?;
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    tauri_state.naive_grid = grid;
+    tauri_state.naive_board = Some(board);
+    Ok(tauri_state.naive_grid.to_color_grid())
+}
+
+#[tauri::command]
+pub fn naive_open_board(path: String, pitch: f64) -> MyResult<ColorGrid, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<OpenBoardArgs, ColorGrid>(
+            "naive_open_board",
+            OpenBoardArgs { path, pitch },
+        ) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match naive_open_board_local(path, pitch) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn naive_export_board_local(path: String) -> Result<String, String> {
+    let tauri_state = TAURI_STATE.lock().unwrap();
+    let board = tauri_state
+        .naive_board
+        .as_ref()
+        .ok_or("The naive grid was not opened from a board file")?;
+    board.save_routed(&tauri_state.naive_grid, std::path::Path::new(&path))?;
+    Ok(format!("Wrote the routed board to {}", path))
+}
+
+#[tauri::command]
+pub fn naive_export_board(path: String) -> MyResult<String, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<ExportBoardArgs, String>(
+            "naive_export_board",
+            ExportBoardArgs { path },
+        ) {
+            Ok(message) => MyResult::Ok(message),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match naive_export_board_local(path) {
+            Ok(message) => MyResult::Ok(message),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+// names the saved file after its path, e.g. "two_nets" for two_nets.json
+fn save_problem_file(mut file: ProblemFile, path: &str) -> Result<String, String> {
+    let path = std::path::Path::new(path);
+    file.metadata.name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(str::to_string);
+    file.save(path)?;
+    Ok(format!("Wrote {}", path.display()))
+}
+
+fn naive_save_problem_local(path: String) -> Result<String, String> {
+    let tauri_state = TAURI_STATE.lock().unwrap();
+    let file = ProblemFile::from_grid(&tauri_state.naive_grid, ProblemFileKind::Problem);
+    save_problem_file(file, &path)
+}
+
+#[tauri::command]
+pub fn naive_save_problem(path: String) -> MyResult<String, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<ProblemFileArgs, String>(
+            "naive_save_problem",
+            ProblemFileArgs { path },
+        ) {
+            Ok(message) => MyResult::Ok(message),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match naive_save_problem_local(path) {
+            Ok(message) => MyResult::Ok(message),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn naive_save_solution_local(path: String) -> Result<String, String> {
+    let tauri_state = TAURI_STATE.lock().unwrap();
+    let file = ProblemFile::from_grid(&tauri_state.naive_grid, ProblemFileKind::Solution);
+    save_problem_file(file, &path)
+}
+
+#[tauri::command]
+pub fn naive_save_solution(path: String) -> MyResult<String, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<ProblemFileArgs, String>(
+            "naive_save_solution",
+            ProblemFileArgs { path },
+        ) {
+            Ok(message) => MyResult::Ok(message),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match naive_save_solution_local(path) {
+            Ok(message) => MyResult::Ok(message),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+// loads a problem, or a solution with its routes
+fn naive_load_problem_local(path: String) -> Result<ColorGrid, String> {
+    let grid = ProblemFile::load(std::path::Path::new(&path))?.to_grid()?;
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    tauri_state.naive_grid = grid;
+    tauri_state.naive_board = None;
+    Ok(tauri_state.naive_grid.to_color_grid())
+}
+
+#[tauri::command]
+pub fn naive_load_problem(path: String) -> MyResult<ColorGrid, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<ProblemFileArgs, ColorGrid>(
+            "naive_load_problem",
+            ProblemFileArgs { path },
+        ) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match naive_load_problem_local(path) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
 fn naive_click_cell_local(x: usize, y: usize, r: u8, g: u8, b: u8) -> Result<ColorGrid, String> {
     let mut tauri_state = TAURI_STATE.lock().unwrap();
     let grid = &mut tauri_state.naive_grid;
@@ -184,7 +341,11 @@ pub fn proba_click_cell(x: usize, y: usize, r: u8, g: u8, b: u8) -> MyResult<Col
 fn naive_do_route_local() -> Result<ColorGrid, String> {
     let mut tauri_state = TAURI_STATE.lock().unwrap();
     let old_grid = tauri_state.naive_grid.clone();
-    tauri_state.naive_grid = naive_route(old_grid)?;
+    let decomposition = tauri_state
+        .router_config
+        .decomposition
+        .unwrap_or(NetDecomposition::MinimumSpanningTree);
+    tauri_state.naive_grid = naive_route_with_decomposition(old_grid, decomposition)?;
     let color_grid = tauri_state.naive_grid.to_color_grid();
     Ok(color_grid)
 }
@@ -204,17 +365,74 @@ pub fn naive_do_route() -> MyResult<ColorGrid, String> {
     }
 }
 
+fn naive_do_pathfinder_route_local() -> Result<ColorGrid, String> {
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    let old_grid = tauri_state.naive_grid.clone();
+    tauri_state.naive_grid = pathfinder_route(old_grid)?;
+    let color_grid = tauri_state.naive_grid.to_color_grid();
+    Ok(color_grid)
+}
+
+#[tauri::command]
+pub fn naive_do_pathfinder_route() -> MyResult<ColorGrid, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<(), ColorGrid>("pathfinder_route", ()) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match naive_do_pathfinder_route_local() {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn naive_set_net_class_local(r: u8, g: u8, b: u8, net_class: String) -> Result<ColorGrid, String> {
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    let grid = &mut tauri_state.naive_grid;
+    let pad_color = Color { r, g, b };
+    let net = grid
+        .pads
+        .keys()
+        .find(|net| matches!(net, Net::Color { pad_color: c, .. } if *c == pad_color))
+        .cloned()
+        .ok_or_else(|| format!("No net with color ({}, {}, {})", r, g, b))?;
+    grid.design_rules.set_net_class(net, &net_class)?;
+    let grid = grid.to_color_grid();
+    Ok(grid)
+}
+
+#[tauri::command]
+pub fn naive_set_net_class(r: u8, g: u8, b: u8, net_class: String) -> MyResult<ColorGrid, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<SetNetClassArgs, ColorGrid>(
+            "set_net_class",
+            SetNetClassArgs {
+                r,
+                g,
+                b,
+                netClass: net_class,
+            },
+        ) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match naive_set_net_class_local(r, g, b, net_class) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
 fn proba_clear_local(rows: usize, cols: usize) -> Result<ColorGrid, String> {
     println!("Creating new grid locally ");
     let mut tauri_state = TAURI_STATE.lock().unwrap();
     tauri_state.proba_grid = ProbaGridState::Uninitialized {
-        input: ProbaGridProblem {
-            width: rows,
-            height: cols,
-            nets: std::collections::HashMap::new(),
-            net_to_pads: std::collections::HashMap::new(),
-        },
+        input: ProbaGridProblem::new(rows, cols, DEFAULT_PROBA_SEED),
     };
+    tauri_state.proba_board = None;
     let grid = tauri_state.proba_grid.to_color_grid();
     Ok(grid)
 }
@@ -234,29 +452,112 @@ pub fn proba_clear(rows: usize, cols: usize) -> MyResult<ColorGrid, String> {
     }
 }
 
-fn proba_init_local() -> Result<ColorGrid, String> {
+fn proba_open_board_local(path: String, pitch: f64) -> Result<ColorGrid, String> {
+    let board = BoardFile::open(std::path::Path::new(&path), pitch)?;
+    let problem = board.to_proba_grid_problem()?;
     let mut tauri_state = TAURI_STATE.lock().unwrap();
-    let grid = match &tauri_state.proba_grid {
+    tauri_state.proba_grid = ProbaGridState::Uninitialized { input: problem };
+    tauri_state.proba_board = Some(board);
+    Ok(tauri_state.proba_grid.to_color_grid())
+}
+
+#[tauri::command]
+pub fn proba_open_board(path: String, pitch: f64) -> MyResult<ColorGrid, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<OpenBoardArgs, ColorGrid>(
+            "proba_open_board",
+            OpenBoardArgs { path, pitch },
+        ) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match proba_open_board_local(path, pitch) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn proba_init_local(seed: Option<u64>) -> Result<ColorGrid, String> {
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    let decomposition = tauri_state
+        .router_config
+        .decomposition
+        .unwrap_or(NetDecomposition::AllPairs);
+    let grid = match &mut tauri_state.proba_grid {
         ProbaGridState::Uninitialized { input } => input,
         _ => return Err("Proba grid is already initialized".to_string()),
     };
-    let grid_output = initialize_proba_grid(grid.clone())?;
+    if let Some(seed) = seed {
+        grid.seed = seed;
+    }
+    let grid_output = initialize_proba_grid_with_decomposition(grid.clone(), decomposition)?;
+    let mut history = ProbaHistory::default();
+    history.record(
+        &grid_output,
+        "init",
+        tauri_state.router_config.max_snapshots,
+    );
     tauri_state.proba_grid = ProbaGridState::Initialized {
         output: grid_output,
+        cursor: None,
+        history,
     };
     let color_grid = tauri_state.proba_grid.to_color_grid();
     Ok(color_grid)
 }
 
 #[tauri::command]
-pub fn proba_init() -> MyResult<ColorGrid, String> {
+pub fn proba_init(seed: Option<u64>) -> MyResult<ColorGrid, String> {
     if USE_PYTHON_SERVER {
-        match call_python_server::<(), ColorGrid>("naive_route", ()) {
+        match call_python_server::<ProbaInitArgs, ColorGrid>("proba_init", ProbaInitArgs { seed }) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match proba_init_local(seed) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn proba_set_net_class_local(r: u8, g: u8, b: u8, net_class: String) -> Result<ColorGrid, String> {
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    let grid = match &mut tauri_state.proba_grid {
+        ProbaGridState::Uninitialized { input } => input,
+        _ => return Err("Proba grid is already initialized".to_string()),
+    };
+    let pad_color = Color { r, g, b };
+    let net_id = grid
+        .nets
+        .iter()
+        .find(|(_, net_info)| net_info.pad_color.as_ref() == Some(&pad_color))
+        .map(|(net_id, _)| *net_id)
+        .ok_or_else(|| format!("No net with color ({}, {}, {})", r, g, b))?;
+    grid.design_rules.set_net_class(net_id, &net_class)?;
+    let color_grid = tauri_state.proba_grid.to_color_grid();
+    Ok(color_grid)
+}
+
+#[tauri::command]
+pub fn proba_set_net_class(r: u8, g: u8, b: u8, net_class: String) -> MyResult<ColorGrid, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<SetNetClassArgs, ColorGrid>(
+            "set_net_class",
+            SetNetClassArgs {
+                r,
+                g,
+                b,
+                netClass: net_class,
+            },
+        ) {
             Ok(grid) => MyResult::Ok(grid),
             Err(e) => MyResult::Err(e),
         }
     } else {
-        match proba_init_local() {
+        match proba_set_net_class_local(r, g, b, net_class) {
             Ok(grid) => MyResult::Ok(grid),
             Err(e) => MyResult::Err(e),
         }
@@ -267,14 +568,18 @@ fn proba_update_posterior_local(
     scoreWeight: f64,
     opportunityCostWeight: f64,
 ) -> Result<ColorGrid, String> {
-    *SCORE_WEIGHT.lock().unwrap() = scoreWeight;
-    *OPPORTUNITY_COST_WEIGHT.lock().unwrap() = opportunityCostWeight;
     let mut tauri_state = TAURI_STATE.lock().unwrap();
-    let grid = match &mut tauri_state.proba_grid {
-        ProbaGridState::Initialized { output } => output,
+    tauri_state.router_config.score_weight = scoreWeight;
+    tauri_state.router_config.opportunity_cost_weight = opportunityCostWeight;
+    let config = tauri_state.router_config;
+    let (grid, history) = match &mut tauri_state.proba_grid {
+        ProbaGridState::Initialized {
+            output, history, ..
+        } => (output, history),
         _ => return Err("Proba grid is not initialized".to_string()),
     };
-    update_posterior(grid)?;
+    update_posterior(grid, &config)?;
+    history.record(grid, "update posterior", config.max_snapshots);
     let color_grid = grid.to_color_grid();
     Ok(color_grid)
 }
@@ -298,12 +603,23 @@ pub fn proba_update_posterior(
 }
 
 fn proba_next_net_local() -> Result<ColorGrid, String> {
-    // let mut tauri_state = TAURI_STATE.lock().unwrap();
-    // let old_grid = tauri_state.grid.clone();
-    // tauri_state.grid = naive_route(old_grid)?;
-    // let color_grid = tauri_state.grid.to_color_grid();
-    // Ok(color_grid)
-    todo!()
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    let config = tauri_state.router_config;
+    let (grid, cursor, history) = match &mut tauri_state.proba_grid {
+        ProbaGridState::Initialized {
+            output,
+            cursor,
+            history,
+        } => (output, cursor, history),
+        _ => return Err("Proba grid is not initialized".to_string()),
+    };
+    let pass = cursor.get_or_insert_with(|| start_sampling(grid));
+    if sample_next_net(grid, pass, &config)? {
+        *cursor = None;
+        history.record(grid, "sample", config.max_snapshots);
+    }
+    let color_grid = tauri_state.proba_grid.to_color_grid();
+    Ok(color_grid)
 }
 
 #[tauri::command]
@@ -322,12 +638,23 @@ pub fn proba_next_net() -> MyResult<ColorGrid, String> {
 }
 
 fn proba_next_pair_local() -> Result<ColorGrid, String> {
-    // let mut tauri_state = TAURI_STATE.lock().unwrap();
-    // let old_grid = tauri_state.grid.clone();
-    // tauri_state.grid = naive_route(old_grid)?;
-    // let color_grid = tauri_state.grid.to_color_grid();
-    // Ok(color_grid)
-    todo!()
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    let config = tauri_state.router_config;
+    let (grid, cursor, history) = match &mut tauri_state.proba_grid {
+        ProbaGridState::Initialized {
+            output,
+            cursor,
+            history,
+        } => (output, cursor, history),
+        _ => return Err("Proba grid is not initialized".to_string()),
+    };
+    let pass = cursor.get_or_insert_with(|| start_sampling(grid));
+    if sample_next_pair(grid, pass, &config)? {
+        *cursor = None;
+        history.record(grid, "sample", config.max_snapshots);
+    }
+    let color_grid = tauri_state.proba_grid.to_color_grid();
+    Ok(color_grid)
 }
 
 #[tauri::command]
@@ -347,11 +674,20 @@ pub fn proba_next_pair() -> MyResult<ColorGrid, String> {
 
 fn proba_sample_local() -> Result<ColorGrid, String> {
     let mut tauri_state = TAURI_STATE.lock().unwrap();
-    let grid = match &mut tauri_state.proba_grid {
-        ProbaGridState::Initialized { output } => output,
+    let config = tauri_state.router_config;
+    let (grid, cursor, history) = match &mut tauri_state.proba_grid {
+        ProbaGridState::Initialized {
+            output,
+            cursor,
+            history,
+        } => (output, cursor, history),
         _ => return Err("Proba grid is not initialized".to_string()),
     };
-    sample_new_traces(grid)?;
+    // a pass started with next net or next pair is completed instead of starting a new one
+    let pass = cursor.get_or_insert_with(|| start_sampling(grid));
+    finish_sampling(grid, pass, &config)?;
+    *cursor = None;
+    history.record(grid, "sample", config.max_snapshots);
     let color_grid = grid.to_color_grid();
     Ok(color_grid)
 }
@@ -370,3 +706,352 @@ pub fn proba_sample() -> MyResult<ColorGrid, String> {
         }
     }
 }
+
+fn proba_solve_local(
+    scoreWeight: f64,
+    opportunityCostWeight: f64,
+) -> Result<ProbaSolveResult, String> {
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    tauri_state.router_config.score_weight = scoreWeight;
+    tauri_state.router_config.opportunity_cost_weight = opportunityCostWeight;
+    let config = tauri_state.router_config;
+    let (grid, cursor, history) = match &mut tauri_state.proba_grid {
+        ProbaGridState::Initialized {
+            output,
+            cursor,
+            history,
+        } => (output, cursor, history),
+        _ => return Err("Proba grid is not initialized".to_string()),
+    };
+    if let Some(pass) = cursor {
+        finish_sampling(grid, pass, &config)?;
+        *cursor = None;
+        history.record(grid, "sample", config.max_snapshots);
+    }
+    let report =
+        solve_proba_grid_observed(grid, &config, &ConvergenceCriterion::default(), |grid| {
+            history.record(grid, "solve", config.max_snapshots);
+        })?;
+    println!("{}", report.summary());
+    Ok(ProbaSolveResult {
+        grid: grid.to_color_grid(),
+        report: report.summary(),
+    })
+}
+
+#[tauri::command]
+pub fn proba_solve(
+    scoreWeight: f64,
+    opportunityCostWeight: f64,
+) -> MyResult<ProbaSolveResult, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<UpdatePosteriorArgs, ProbaSolveResult>(
+            "proba_solve",
+            UpdatePosteriorArgs {
+                scoreWeight,
+                opportunityCostWeight,
+            },
+        ) {
+            Ok(result) => MyResult::Ok(result),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match proba_solve_local(scoreWeight, opportunityCostWeight) {
+            Ok(result) => MyResult::Ok(result),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn proba_extract_local() -> Result<ColorGrid, String> {
+    let tauri_state = TAURI_STATE.lock().unwrap();
+    let grid = match &tauri_state.proba_grid {
+        ProbaGridState::Initialized { output, .. } => output,
+        _ => return Err("Proba grid is not initialized".to_string()),
+    };
+    let routed_grid = extract_routing(grid)?;
+    let color_grid = routed_grid.to_color_grid();
+    Ok(color_grid)
+}
+
+#[tauri::command]
+pub fn proba_extract() -> MyResult<ColorGrid, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<(), ColorGrid>("proba_extract", ()) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match proba_extract_local() {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn proba_export_board_local(path: String) -> Result<String, String> {
+    let tauri_state = TAURI_STATE.lock().unwrap();
+    let board = tauri_state
+        .proba_board
+        .as_ref()
+        .ok_or("The proba grid was not opened from a board file")?;
+    let grid = match &tauri_state.proba_grid {
+        ProbaGridState::Initialized { output, .. } => output,
+        _ => return Err("Proba grid is not initialized".to_string()),
+    };
+    let routed_grid = extract_routing(grid)?;
+    board.save_routed(&routed_grid, std::path::Path::new(&path))?;
+    Ok(format!("Wrote the routed board to {}", path))
+}
+
+#[tauri::command]
+pub fn proba_export_board(path: String) -> MyResult<String, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<ExportBoardArgs, String>(
+            "proba_export_board",
+            ExportBoardArgs { path },
+        ) {
+            Ok(message) => MyResult::Ok(message),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match proba_export_board_local(path) {
+            Ok(message) => MyResult::Ok(message),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn proba_save_problem_local(path: String) -> Result<String, String> {
+    let tauri_state = TAURI_STATE.lock().unwrap();
+    let problem = match &tauri_state.proba_grid {
+        ProbaGridState::Uninitialized { input } => input,
+        // the seed is consumed by proba_init, so the problem is saved before it
+        _ => return Err("Proba grid is already initialized".to_string()),
+    };
+    save_problem_file(ProblemFile::from_proba_grid_problem(problem), &path)
+}
+
+#[tauri::command]
+pub fn proba_save_problem(path: String) -> MyResult<String, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<ProblemFileArgs, String>(
+            "proba_save_problem",
+            ProblemFileArgs { path },
+        ) {
+            Ok(message) => MyResult::Ok(message),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match proba_save_problem_local(path) {
+            Ok(message) => MyResult::Ok(message),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn proba_save_solution_local(path: String) -> Result<String, String> {
+    let tauri_state = TAURI_STATE.lock().unwrap();
+    let grid = match &tauri_state.proba_grid {
+        ProbaGridState::Initialized { output, .. } => output,
+        _ => return Err("Proba grid is not initialized".to_string()),
+    };
+    let routed_grid = extract_routing(grid)?;
+    save_problem_file(
+        ProblemFile::from_grid(&routed_grid, ProblemFileKind::Solution),
+        &path,
+    )
+}
+
+#[tauri::command]
+pub fn proba_save_solution(path: String) -> MyResult<String, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<ProblemFileArgs, String>(
+            "proba_save_solution",
+            ProblemFileArgs { path },
+        ) {
+            Ok(message) => MyResult::Ok(message),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match proba_save_solution_local(path) {
+            Ok(message) => MyResult::Ok(message),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn proba_load_problem_local(path: String) -> Result<ColorGrid, String> {
+    let problem = ProblemFile::load(std::path::Path::new(&path))?.to_proba_grid_problem()?;
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    tauri_state.proba_grid = ProbaGridState::Uninitialized { input: problem };
+    tauri_state.proba_board = None;
+    Ok(tauri_state.proba_grid.to_color_grid())
+}
+
+#[tauri::command]
+pub fn proba_load_problem(path: String) -> MyResult<ColorGrid, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<ProblemFileArgs, ColorGrid>(
+            "proba_load_problem",
+            ProblemFileArgs { path },
+        ) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match proba_load_problem_local(path) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn proba_inspect_local() -> Result<ProbaInspection, String> {
+    let tauri_state = TAURI_STATE.lock().unwrap();
+    let grid = match &tauri_state.proba_grid {
+        ProbaGridState::Initialized { output, .. } => output,
+        _ => return Err("Proba grid is not initialized".to_string()),
+    };
+    grid.inspect(&tauri_state.router_config)
+}
+
+#[tauri::command]
+pub fn proba_inspect() -> MyResult<ProbaInspection, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<(), ProbaInspection>("proba_inspect", ()) {
+            Ok(inspection) => MyResult::Ok(inspection),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match proba_inspect_local() {
+            Ok(inspection) => MyResult::Ok(inspection),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn proba_list_snapshots_local() -> Result<Vec<ProbaSnapshotInfo>, String> {
+    let tauri_state = TAURI_STATE.lock().unwrap();
+    match &tauri_state.proba_grid {
+        ProbaGridState::Initialized { history, .. } => Ok(history.list()),
+        _ => Err("Proba grid is not initialized".to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn proba_list_snapshots() -> MyResult<Vec<ProbaSnapshotInfo>, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<(), Vec<ProbaSnapshotInfo>>("proba_list_snapshots", ()) {
+            Ok(snapshots) => MyResult::Ok(snapshots),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match proba_list_snapshots_local() {
+            Ok(snapshots) => MyResult::Ok(snapshots),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn proba_restore_snapshot_local(snapshotId: usize) -> Result<ColorGrid, String> {
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    let (grid, cursor, history) = match &mut tauri_state.proba_grid {
+        ProbaGridState::Initialized {
+            output,
+            cursor,
+            history,
+        } => (output, cursor, history),
+        _ => return Err("Proba grid is not initialized".to_string()),
+    };
+    // an unfinished sampling pass belongs to the grid being replaced
+    *grid = history.restore(snapshotId)?;
+    *cursor = None;
+    let color_grid = grid.to_color_grid();
+    Ok(color_grid)
+}
+
+#[tauri::command]
+pub fn proba_restore_snapshot(snapshotId: usize) -> MyResult<ColorGrid, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<RestoreSnapshotArgs, ColorGrid>(
+            "proba_restore_snapshot",
+            RestoreSnapshotArgs { snapshotId },
+        ) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match proba_restore_snapshot_local(snapshotId) {
+            Ok(grid) => MyResult::Ok(grid),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn get_router_config_local() -> Result<RouterConfig, String> {
+    let tauri_state = TAURI_STATE.lock().unwrap();
+    Ok(tauri_state.router_config)
+}
+
+#[tauri::command]
+pub fn get_router_config() -> MyResult<RouterConfig, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<(), RouterConfig>("get_router_config", ()) {
+            Ok(config) => MyResult::Ok(config),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match get_router_config_local() {
+            Ok(config) => MyResult::Ok(config),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn set_router_config_local(config: RouterConfig) -> Result<RouterConfig, String> {
+    config.validate()?;
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    tauri_state.router_config = config;
+    Ok(config)
+}
+
+#[tauri::command]
+pub fn set_router_config(config: RouterConfig) -> MyResult<RouterConfig, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<RouterConfig, RouterConfig>("set_router_config", config) {
+            Ok(config) => MyResult::Ok(config),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match set_router_config_local(config) {
+            Ok(config) => MyResult::Ok(config),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
+
+fn load_router_config_local(path: String) -> Result<RouterConfig, String> {
+    let config = RouterConfig::load(std::path::Path::new(&path))?;
+    let mut tauri_state = TAURI_STATE.lock().unwrap();
+    tauri_state.router_config = config;
+    Ok(config)
+}
+
+#[tauri::command]
+pub fn load_router_config(path: String) -> MyResult<RouterConfig, String> {
+    if USE_PYTHON_SERVER {
+        match call_python_server::<LoadRouterConfigArgs, RouterConfig>(
+            "load_router_config",
+            LoadRouterConfigArgs { path },
+        ) {
+            Ok(config) => MyResult::Ok(config),
+            Err(e) => MyResult::Err(e),
+        }
+    } else {
+        match load_router_config_local(path) {
+            Ok(config) => MyResult::Ok(config),
+            Err(e) => MyResult::Err(e),
+        }
+    }
+}
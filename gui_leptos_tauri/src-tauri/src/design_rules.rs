@@ -0,0 +1,218 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    hash::Hash,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::grid::{LayerPoint, Point};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ViaPolicy {
+    Allowed,
+    Forbidden, // the net stays on the layer it starts on
+}
+
+/// Routing rules shared by a group of nets, e.g. power nets that need wider copper than signals
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetClass {
+    pub name: String,
+    pub trace_width: usize,
+    pub clearance: usize,
+    pub allowed_layers: Option<BTreeSet<usize>>, // None means every layer of the board
+    pub max_length: Option<f64>, // the longest allowed trace between two pads, in cells
+    pub via_policy: ViaPolicy,
+}
+
+impl NetClass {
+    pub fn power() -> Self {
+        NetClass {
+            name: "power".to_string(),
+            trace_width: 3,
+            clearance: 1,
+            allowed_layers: None,
+            max_length: None,
+            via_policy: ViaPolicy::Allowed,
+        }
+    }
+    pub fn signal() -> Self {
+        NetClass {
+            name: "signal".to_string(),
+            trace_width: 1,
+            clearance: 1,
+            allowed_layers: None,
+            max_length: None,
+            via_policy: ViaPolicy::Allowed,
+        }
+    }
+    pub fn high_speed() -> Self {
+        // short traces without vias keep the impedance under control
+        NetClass {
+            name: "high_speed".to_string(),
+            trace_width: 1,
+            clearance: 2,
+            allowed_layers: None,
+            max_length: Some(50.0),
+            via_policy: ViaPolicy::Forbidden,
+        }
+    }
+}
+
+/// Trace widths and clearances in grid cells, keyed by net (grid::Net or proba_grid::NetID)
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesignRules<N: Clone + Eq + Hash + Ord> {
+    pub default_trace_width: usize, // the copper of a trace spans this many cells across
+    pub default_clearance: usize, // the number of free cells required between copper of different nets
+    pub trace_widths: HashMap<N, usize>, // overrides the width of the net class
+    pub clearances: HashMap<(N, N), usize>, // keyed by the ordered net pair, see set_clearance
+    pub net_classes: HashMap<String, NetClass>, // class name to class
+    pub net_to_class: HashMap<N, String>,
+}
+
+impl<N: Clone + Eq + Hash + Ord> Default for DesignRules<N> {
+    fn default() -> Self {
+        // a trace is one cell wide and may touch traces of other nets, as before design rules existed
+        DesignRules {
+            default_trace_width: 1,
+            default_clearance: 0,
+            trace_widths: HashMap::new(),
+            clearances: HashMap::new(),
+            net_classes: [
+                NetClass::power(),
+                NetClass::signal(),
+                NetClass::high_speed(),
+            ]
+            .into_iter()
+            .map(|net_class| (net_class.name.clone(), net_class))
+            .collect(),
+            net_to_class: HashMap::new(),
+        }
+    }
+}
+
+impl<N: Clone + Eq + Hash + Ord> DesignRules<N> {
+    fn ordered(a: &N, b: &N) -> (N, N) {
+        if a <= b {
+            (a.clone(), b.clone())
+        } else {
+            (b.clone(), a.clone())
+        }
+    }
+    pub fn net_class(&self, net: &N) -> Option<&NetClass> {
+        self.net_to_class
+            .get(net)
+            .and_then(|name| self.net_classes.get(name))
+    }
+    pub fn set_net_class(&mut self, net: N, name: &str) -> Result<(), String> {
+        if !self.net_classes.contains_key(name) {
+            return Err(format!("Unknown net class: {}", name));
+        }
+        self.net_to_class.insert(net, name.to_string());
+        Ok(())
+    }
+    pub fn trace_width(&self, net: &N) -> usize {
+        self.trace_widths
+            .get(net)
+            .cloned()
+            .or_else(|| self.net_class(net).map(|net_class| net_class.trace_width))
+            .unwrap_or(self.default_trace_width)
+    }
+    /// The number of cells the copper extends on each side of the center line,
+    /// even widths are rounded up to the next odd width
+    pub fn trace_radius(&self, net: &N) -> usize {
+        self.trace_width(net) / 2
+    }
+    /// The clearance set for the pair, otherwise the larger clearance of the two net classes
+    pub fn clearance(&self, a: &N, b: &N) -> usize {
+        let class_clearance = |net: &N| {
+            self.net_class(net)
+                .map(|net_class| net_class.clearance)
+                .unwrap_or(self.default_clearance)
+        };
+        self.clearances
+            .get(&Self::ordered(a, b))
+            .cloned()
+            .unwrap_or_else(|| class_clearance(a).max(class_clearance(b)))
+    }
+    pub fn allowed_layers(&self, net: &N, num_layers: usize) -> BTreeSet<usize> {
+        match self
+            .net_class(net)
+            .and_then(|net_class| net_class.allowed_layers.as_ref())
+        {
+            Some(layers) => layers
+                .iter()
+                .filter(|layer| **layer < num_layers)
+                .cloned()
+                .collect(),
+            None => (0..num_layers).collect(),
+        }
+    }
+    pub fn vias_allowed(&self, net: &N) -> bool {
+        self.net_class(net)
+            .map(|net_class| net_class.via_policy == ViaPolicy::Allowed)
+            .unwrap_or(true)
+    }
+    pub fn max_length(&self, net: &N) -> Option<f64> {
+        self.net_class(net)
+            .and_then(|net_class| net_class.max_length)
+    }
+    pub fn set_trace_width(&mut self, net: N, width: usize) {
+        assert!(width >= 1, "Trace width must be at least one cell");
+        self.trace_widths.insert(net, width);
+    }
+    pub fn set_clearance(&mut self, a: &N, b: &N, clearance: usize) {
+        self.clearances.insert(Self::ordered(a, b), clearance);
+    }
+    /// The same rules keyed by another kind of net, e.g. from proba_grid::NetID to grid::Net
+    pub fn map_nets<M: Clone + Eq + Hash + Ord>(&self, net: impl Fn(&N) -> M) -> DesignRules<M> {
+        DesignRules {
+            default_trace_width: self.default_trace_width,
+            default_clearance: self.default_clearance,
+            trace_widths: self
+                .trace_widths
+                .iter()
+                .map(|(n, width)| (net(n), *width))
+                .collect(),
+            clearances: self
+                .clearances
+                .iter()
+                .map(|((a, b), clearance)| (DesignRules::ordered(&net(a), &net(b)), *clearance))
+                .collect(),
+            net_classes: self.net_classes.clone(),
+            net_to_class: self
+                .net_to_class
+                .iter()
+                .map(|(n, name)| (net(n), name.clone()))
+                .collect(),
+        }
+    }
+    /// How far the center line of `net` must stay from the center line of `other`
+    pub fn trace_inflation(&self, net: &N, other: &N) -> usize {
+        self.trace_radius(net) + self.trace_radius(other) + self.clearance(net, other)
+    }
+    /// How far the center line of `net` must stay from a pad of `other`, pads being one cell
+    pub fn pad_inflation(&self, net: &N, other: &N) -> usize {
+        self.trace_radius(net) + self.clearance(net, other)
+    }
+}
+
+/// All points within the Chebyshev distance of the given points on the same layer.
+/// The result is not clipped to the grid, points outside of it are never visited by the router.
+pub fn inflate(
+    points: impl IntoIterator<Item = LayerPoint>,
+    distance: usize,
+) -> HashSet<LayerPoint> {
+    let mut inflated = HashSet::new();
+    for LayerPoint { point, layer } in points {
+        for x in point.x.saturating_sub(distance)..=point.x + distance {
+            for y in point.y.saturating_sub(distance)..=point.y + distance {
+                inflated.insert(LayerPoint {
+                    point: Point { x, y },
+                    layer,
+                });
+            }
+        }
+    }
+    inflated
+}
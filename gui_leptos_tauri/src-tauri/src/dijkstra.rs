@@ -4,80 +4,212 @@ use std::{
 };
 
 use crate::{
-    grid::Point,
+    grid::{LayerPoint, Point},
+    hyperparameters::{LENGTH_PENALTY_RATE, TURN_PENALTY_RATE, VIA_COST},
     proba_grid::{Direction, TracePath},
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    Dijkstra,
+    AStar, // guided by the octile distance to the nearest goal
+}
+
+/// The objective minimised by the router, shared with the trace scorer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathCosts {
+    pub length: f64,   // cost of a cardinal move
+    pub diagonal: f64, // cost of a diagonal move
+    pub turn: f64,     // cost of changing direction between two moves
+    pub via: f64,      // cost of moving from one layer to another
+}
+
+impl Default for PathCosts {
+    fn default() -> Self {
+        PathCosts {
+            length: 1.0,
+            diagonal: (2.0f64).sqrt(),
+            turn: 0.0,
+            via: VIA_COST,
+        }
+    }
+}
+
+impl PathCosts {
+    /// The costs used by the Bayesian scorer, so that the router finds the traces the scorer prefers
+    pub fn scoring() -> Self {
+        PathCosts {
+            length: LENGTH_PENALTY_RATE,
+            diagonal: LENGTH_PENALTY_RATE * (2.0f64).sqrt(),
+            turn: TURN_PENALTY_RATE,
+            via: VIA_COST,
+        }
+    }
+    fn move_cost(&self, direction: &Direction) -> f64 {
+        if direction.x != 0 && direction.y != 0 {
+            self.diagonal
+        } else {
+            self.length
+        }
+    }
+    pub fn count_turns(directions: &[Direction]) -> usize {
+        directions
+            .windows(2)
+            .filter(|window| window[0] != window[1])
+            .count()
+    }
+    /// The cost of a path, which is exactly what the router minimises
+    pub fn path_cost(&self, directions: &[Direction], num_vias: usize) -> f64 {
+        let move_cost: f64 = directions
+            .iter()
+            .map(|direction| self.move_cost(direction))
+            .sum();
+        move_cost + Self::count_turns(directions) as f64 * self.turn + num_vias as f64 * self.via
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DijkstraModel {
     pub width: usize,
     pub height: usize,
-    pub obstacles: HashSet<Point>,
-    pub diagonal_obstacles: HashSet<Point>, // obstacles that are diagonal traces
+    pub num_layers: usize,
+    pub obstacles: HashSet<LayerPoint>,
+    pub diagonal_obstacles: HashSet<LayerPoint>, // obstacles that are diagonal traces
     pub start: Point,
-    pub end: Point,
+    pub start_layers: BTreeSet<usize>, // the layers the start pad exists on
+    pub goals: HashSet<LayerPoint>, // the search ends on any of these, e.g. the end pad or copper already connected to it
+    pub allowed_layers: BTreeSet<usize>, // the layers the trace may use, e.g. restricted by its net class
+    pub vias_allowed: bool,
+    pub costs: PathCosts,
+    pub search_mode: SearchMode,
+    pub cell_costs: HashMap<LayerPoint, f64>, // extra cost of entering a cell, e.g. for congestion
+    pub diagonal_cell_costs: HashMap<LayerPoint, f64>, // extra cost of a diagonal through a square, keyed by its top left corner
+}
+
+/// The goals of a single end pad on the given layers
+pub fn pad_goals(point: Point, layers: &BTreeSet<usize>) -> HashSet<LayerPoint> {
+    layers
+        .iter()
+        .map(|layer| LayerPoint {
+            point,
+            layer: *layer,
+        })
+        .collect()
 }
 
 impl DijkstraModel {
     pub fn run(&self) -> Result<DijkstraResult, String> {
-        let mut heap = BinaryHeap::new();
-        let mut dist: HashMap<Point, f64> = HashMap::new();
-        let mut prev: HashMap<Point, Point> = HashMap::new();
+        // the incoming direction is part of the search state so that turns can be charged
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        struct Node {
+            position: LayerPoint,
+            incoming: Option<Direction>,
+        }
 
         #[derive(Debug, PartialEq)]
         struct State {
+            priority: f64, // cost plus the heuristic estimate, equal to cost for Dijkstra
             cost: f64,
-            position: Point,
+            node: Node,
         }
 
         impl Eq for State {}
 
         impl PartialOrd for State {
             fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-                other.cost.partial_cmp(&self.cost)
+                Some(self.cmp(other))
             }
         }
 
         impl Ord for State {
             fn cmp(&self, other: &Self) -> Ordering {
-                self.partial_cmp(other).unwrap()
+                // on equal priority, prefer the lower layer so that the top layer is used when possible
+                other
+                    .priority
+                    .partial_cmp(&self.priority)
+                    .unwrap()
+                    .then_with(|| other.node.position.layer.cmp(&self.node.position.layer))
             }
         }
 
-        heap.push(State {
-            cost: 0.0,
-            position: self.start,
-        });
-        dist.insert(self.start, 0.0);
+        let mut heap = BinaryHeap::new();
+        let mut dist: HashMap<Node, f64> = HashMap::new();
+        let mut prev: HashMap<Node, Node> = HashMap::new();
+        // without a turn cost the incoming direction does not matter, so it is not tracked
+        let track_direction = self.costs.turn != 0.0;
+        let goal_points: Vec<Point> = self
+            .goals
+            .iter()
+            .map(|goal| goal.point)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        for &layer in self.start_layers.intersection(&self.allowed_layers) {
+            let node = Node {
+                position: LayerPoint {
+                    point: self.start,
+                    layer,
+                },
+                incoming: None,
+            };
+            heap.push(State {
+                priority: self.heuristic(&goal_points, self.start),
+                cost: 0.0,
+                node: node.clone(),
+            });
+            dist.insert(node, 0.0);
+        }
 
         let cardinal_dirs: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
         let diagonal_dirs: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
 
-        while let Some(State { cost, position }) = heap.pop() {
-            if position == self.end {
+        let mut reached_end: Option<Node> = None;
+        let mut expanded = 0;
+        while let Some(State { cost, node, .. }) = heap.pop() {
+            let position = node.position;
+            if self.goals.contains(&position) {
+                reached_end = Some(node);
                 break;
             }
             // skip if we already found a shorter path
-            if let Some(&d) = dist.get(&position) {
+            if let Some(&d) = dist.get(&node) {
                 if cost > d {
                     continue;
                 }
             }
+            expanded += 1;
+            let mut relax = |next: Node, next_cost: f64| {
+                if next_cost < *dist.get(&next).unwrap_or(&f64::INFINITY) {
+                    dist.insert(next.clone(), next_cost);
+                    prev.insert(next.clone(), node.clone());
+                    heap.push(State {
+                        priority: next_cost + self.heuristic(&goal_points, next.position.point),
+                        cost: next_cost,
+                        node: next,
+                    });
+                }
+            };
+            let planar_move = |next: LayerPoint, dx: i32, dy: i32| {
+                let direction = Direction { x: dx, y: dy };
+                let mut move_cost = self.costs.move_cost(&direction) + self.cell_cost(next);
+                if matches!(&node.incoming, Some(incoming) if *incoming != direction) {
+                    move_cost += self.costs.turn;
+                }
+                let next = Node {
+                    position: next,
+                    incoming: track_direction.then_some(direction),
+                };
+                (next, cost + move_cost)
+            };
             // Cardinal moves
             for &(dx, dy) in &cardinal_dirs {
                 if let Some(next) = self.offset_point(position, dx, dy) {
                     if self.obstacles.contains(&next) {
                         continue;
                     }
-                    let next_cost = cost + 1.0;
-                    if next_cost < *dist.get(&next).unwrap_or(&f64::INFINITY) {
-                        dist.insert(next, next_cost);
-                        prev.insert(next, position);
-                        heap.push(State {
-                            cost: next_cost,
-                            position: next,
-                        });
-                    }
+                    let (next, next_cost) = planar_move(next, dx, dy);
+                    relax(next, next_cost);
                 }
             }
             // Diagonal moves
@@ -89,81 +221,139 @@ impl DijkstraModel {
                     {
                         continue;
                     }
-                    let next_cost = cost + (2.0f64).sqrt();
-                    if next_cost < *dist.get(&next).unwrap_or(&f64::INFINITY) {
-                        dist.insert(next, next_cost);
-                        prev.insert(next, position);
-                        heap.push(State {
-                            cost: next_cost,
-                            position: next,
-                        });
-                    }
+                    let (next, next_cost) = planar_move(next, dx, dy);
+                    let diagonal_cell_cost = self
+                        .diagonal_cell_costs
+                        .get(&top_left)
+                        .cloned()
+                        .unwrap_or(0.0);
+                    relax(next, next_cost + diagonal_cell_cost);
+                }
+            }
+            // Via moves: a via occupies its point on every layer, and keeps the incoming direction
+            if self.vias_allowed && self.num_layers > 1 && self.via_allowed(position.point) {
+                for layer in self
+                    .allowed_layers
+                    .iter()
+                    .cloned()
+                    .filter(|layer| *layer != position.layer)
+                {
+                    let next = Node {
+                        position: LayerPoint {
+                            point: position.point,
+                            layer,
+                        },
+                        incoming: node.incoming.clone(),
+                    };
+                    let next_cost = cost + self.costs.via + self.cell_cost(next.position);
+                    relax(next, next_cost);
                 }
             }
         }
 
+        let reached_end = match reached_end {
+            Some(reached_end) => reached_end,
+            None => return Err("Dijkstra Algorithm Failed: No Path Found".to_string()),
+        };
+        let cost = *dist.get(&reached_end).unwrap_or(&f64::INFINITY);
+
         // Reconstruct path
+        let mut nodes = vec![reached_end.position];
+        let mut current = reached_end;
+        while !(current.position.point == self.start
+            && self.start_layers.contains(&current.position.layer)
+            && current.incoming.is_none())
+        {
+            match prev.get(&current) {
+                Some(prev_node) => {
+                    nodes.push(prev_node.position);
+                    current = prev_node.clone();
+                }
+                None => return Err("Dijkstra Algorithm Failed: No Path Found".to_string()),
+            }
+        }
+        nodes.reverse();
         let mut trace_directions = Vec::new();
         let mut covered = BTreeSet::new();
         let mut diagonal_covered = BTreeSet::new();
-        let mut current = self.end;
-        covered.insert(current);
-        while current != self.start {
-            if let Some(&prev_point) = prev.get(&current) {
-                let direction = Direction {
-                    x: current.x as i32 - prev_point.x as i32,
-                    y: current.y as i32 - prev_point.y as i32,
-                };
-                if direction.x != 0 && direction.y != 0 {
-                    // if the direction is diagonal, we also add the diagonal trace
-                    let diagonal_trace_point = Point {
-                        x: prev_point.x.min(current.x),
-                        y: prev_point.y.min(current.y),
-                    };
-                    diagonal_covered.insert(diagonal_trace_point);
-                }
-                trace_directions.push(direction);
-                current = prev_point;
-                covered.insert(current);
+        let mut vias = BTreeSet::new();
+        let mut distance = 0.0;
+        covered.insert(nodes[0]);
+        for window in nodes.windows(2) {
+            let (prev_point, current) = (window[0], window[1]);
+            covered.insert(current);
+            if prev_point.layer != current.layer {
+                vias.insert(current.point);
+                continue;
+            }
+            let direction = Direction {
+                x: current.point.x as i32 - prev_point.point.x as i32,
+                y: current.point.y as i32 - prev_point.point.y as i32,
+            };
+            if direction.x != 0 && direction.y != 0 {
+                // if the direction is diagonal, we also add the diagonal trace
+                diagonal_covered.insert(LayerPoint {
+                    point: Point {
+                        x: prev_point.point.x.min(current.point.x),
+                        y: prev_point.point.y.min(current.point.y),
+                    },
+                    layer: current.layer,
+                });
+                distance += (2.0f64).sqrt();
             } else {
-                // No path found
-                return Err("Dijkstra Algorithm Failed: No Path Found".to_string());
+                distance += 1.0;
             }
+            trace_directions.push(direction);
         }
-        trace_directions.reverse();
-        let mut current = self.start;
-        let trace_path: BTreeSet<Point> =
-            trace_directions
-                .iter()
-                .fold(BTreeSet::new(), |mut acc, dir| {
-                    current = Point {
-                        x: (current.x as i32 + dir.x) as usize,
-                        y: (current.y as i32 + dir.y) as usize,
-                    };
-                    acc.insert(current);
-                    acc
-                });
         let trace_path = TracePath {
             covered,
             diagonal_covered,
+            vias,
         };
+        let end = nodes.last().unwrap().point;
         Ok(DijkstraResult {
             start: self.start,
-            end: self.end,
+            end,
             trace_path,
             trace_directions,
-            distance: *dist.get(&self.end).unwrap_or(&f64::INFINITY),
+            distance,
+            cost,
+            expanded,
         })
     }
 
-    fn offset_point(&self, point: Point, dx: i32, dy: i32) -> Option<Point> {
-        let nx = point.x as i32 + dx;
-        let ny = point.y as i32 + dy;
+    /// Octile distance to the nearest goal weighted by the move costs, which never overestimates
+    fn heuristic(&self, goal_points: &[Point], point: Point) -> f64 {
+        match self.search_mode {
+            SearchMode::Dijkstra => 0.0,
+            SearchMode::AStar => {
+                let diagonal = self.costs.diagonal.min(2.0 * self.costs.length);
+                goal_points
+                    .iter()
+                    .map(|goal| {
+                        let dx = (point.x as f64 - goal.x as f64).abs();
+                        let dy = (point.y as f64 - goal.y as f64).abs();
+                        let (long, short) = (dx.max(dy), dx.min(dy));
+                        (long - short) * self.costs.length + short * diagonal
+                    })
+                    .fold(f64::INFINITY, f64::min)
+            }
+        }
+    }
+
+    fn cell_cost(&self, point: LayerPoint) -> f64 {
+        self.cell_costs.get(&point).cloned().unwrap_or(0.0)
+    }
+
+    fn via_allowed(&self, point: Point) -> bool {
+        (0..self.num_layers).all(|layer| !self.obstacles.contains(&LayerPoint { point, layer }))
+    }
+
+    fn offset_point(&self, point: LayerPoint, dx: i32, dy: i32) -> Option<LayerPoint> {
+        let nx = point.point.x as i32 + dx;
+        let ny = point.point.y as i32 + dy;
         if nx >= 0 && ny >= 0 && (nx as usize) < self.width && (ny as usize) < self.height {
-            Some(Point {
-                x: nx as usize,
-                y: ny as usize,
-            })
+            Some(LayerPoint::new(nx as usize, ny as usize, point.layer))
         } else {
             None
         }
@@ -172,8 +362,10 @@ impl DijkstraModel {
 
 pub struct DijkstraResult {
     pub start: Point,
-    pub end: Point,
+    pub end: Point, // the goal that was reached
     pub trace_path: TracePath,
     pub trace_directions: Vec<Direction>,
-    pub distance: f64,
+    pub distance: f64,   // the geometric length of the trace
+    pub cost: f64,       // the value of the objective, including turns and vias
+    pub expanded: usize, // the number of nodes taken off the heap, to compare search modes
 }
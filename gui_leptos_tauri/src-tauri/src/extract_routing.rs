@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::{
+    grid::{Grid, Net, Point},
+    hyperparameters::{
+        EXACT_EXTRACTION_MAX_PAD_PAIRS, MAX_EXACT_EXTRACTION_NODES, MAX_EXTRACTION_REPAIRS,
+    },
+    proba_grid::{NetID, NetInfo, PadPairID, ProbaGrid, TraceID},
+};
+
+/// The trace chosen for each pad pair that is routed, pad pairs missing from the map are left unrouted
+pub type Selection = BTreeMap<PadPairID, TraceID>;
+
+fn posterior(grid: &ProbaGrid, trace_id: TraceID) -> f64 {
+    let num_traces_in_the_same_iteration = grid.get_num_traces_in_the_same_iteration(trace_id);
+    grid.traces[&trace_id].get_posterior_normalized_with_fallback(
+        &grid.prior_schedule,
+        num_traces_in_the_same_iteration,
+    )
+}
+
+/// The candidate traces of every pad pair, most probable first
+fn candidates(grid: &ProbaGrid) -> BTreeMap<PadPairID, Vec<(TraceID, f64)>> {
+    grid.pad_pair_to_traces
+        .iter()
+        .map(|(pad_pair_id, iterations)| {
+            let mut traces: Vec<(TraceID, f64)> = iterations
+                .values()
+                .flat_map(|trace_ids| trace_ids.iter())
+                .map(|trace_id| (*trace_id, posterior(grid, *trace_id)))
+                .collect();
+            traces.sort_by(|(id_a, p_a), (id_b, p_b)| p_b.total_cmp(p_a).then(id_a.cmp(id_b)));
+            (*pad_pair_id, traces)
+        })
+        .collect()
+}
+
+/// The selected pad pairs whose trace collides with the given trace
+fn conflicts(grid: &ProbaGrid, selection: &Selection, trace_id: TraceID) -> Vec<PadPairID> {
+    let Some(colliding) = grid.trace_collision_adjacency.get(&trace_id) else {
+        return vec![];
+    };
+    selection
+        .iter()
+        .filter(|(_, selected)| colliding.contains(selected))
+        .map(|(pad_pair_id, _)| *pad_pair_id)
+        .collect()
+}
+
+/// Assigns every point connected through the selected pad pairs of the net the same representative
+fn components(grid: &ProbaGrid, net_id: NetID, selection: &Selection) -> HashMap<Point, Point> {
+    fn find(parents: &mut HashMap<Point, Point>, point: Point) -> Point {
+        let parent = *parents.entry(point).or_insert(point);
+        if parent == point {
+            return point;
+        }
+        let root = find(parents, parent);
+        parents.insert(point, root);
+        root
+    }
+    let mut parents: HashMap<Point, Point> = HashMap::new();
+    for pad in grid.net_to_pads.get(&net_id).into_iter().flatten() {
+        parents.insert(*pad, *pad);
+    }
+    for pad_pair_id in grid.net_to_pad_pairs.get(&net_id).into_iter().flatten() {
+        let pad_pair = &grid.pad_pairs[pad_pair_id];
+        let start = find(&mut parents, pad_pair.start);
+        let end = find(&mut parents, pad_pair.end);
+        if selection.contains_key(pad_pair_id) && start != end {
+            parents.insert(start, end);
+        }
+    }
+    let points: Vec<Point> = parents.keys().cloned().collect();
+    points
+        .into_iter()
+        .map(|point| (point, find(&mut parents, point)))
+        .collect()
+}
+
+pub fn net_is_connected(grid: &ProbaGrid, net_id: NetID, selection: &Selection) -> bool {
+    let components = components(grid, net_id, selection);
+    let roots: HashSet<Point> = grid
+        .net_to_pads
+        .get(&net_id)
+        .into_iter()
+        .flatten()
+        .map(|pad| components[pad])
+        .collect();
+    roots.len() <= 1
+}
+
+fn unconnected_nets(grid: &ProbaGrid, selection: &Selection) -> BTreeSet<NetID> {
+    grid.net_to_pads
+        .keys()
+        .filter(|net_id| !net_is_connected(grid, **net_id, selection))
+        .cloned()
+        .collect()
+}
+
+fn score(selection: &Selection, candidates: &BTreeMap<PadPairID, Vec<(TraceID, f64)>>) -> f64 {
+    selection
+        .iter()
+        .map(|(pad_pair_id, trace_id)| posterior_of(candidates, *pad_pair_id, *trace_id))
+        .sum()
+}
+
+/// Adds the most probable traces first, skipping the ones that collide with traces already selected
+fn fill_greedily(
+    grid: &ProbaGrid,
+    candidates: &BTreeMap<PadPairID, Vec<(TraceID, f64)>>,
+    selection: &mut Selection,
+) {
+    let mut ordered: Vec<(PadPairID, TraceID, f64)> = candidates
+        .iter()
+        .filter(|(pad_pair_id, _)| !selection.contains_key(pad_pair_id))
+        .flat_map(|(pad_pair_id, traces)| {
+            traces
+                .iter()
+                .map(|(trace_id, posterior)| (*pad_pair_id, *trace_id, *posterior))
+        })
+        .collect();
+    ordered.sort_by(|(_, id_a, p_a), (_, id_b, p_b)| p_b.total_cmp(p_a).then(id_a.cmp(id_b)));
+    for (pad_pair_id, trace_id, _) in ordered {
+        if !selection.contains_key(&pad_pair_id) && conflicts(grid, selection, trace_id).is_empty()
+        {
+            selection.insert(pad_pair_id, trace_id);
+        }
+    }
+}
+
+/// Connects the nets the greedy selection left open, giving up the least probable colliding traces
+fn repair(
+    grid: &ProbaGrid,
+    candidates: &BTreeMap<PadPairID, Vec<(TraceID, f64)>>,
+    selection: &mut Selection,
+) {
+    for _ in 0..MAX_EXTRACTION_REPAIRS {
+        let Some(net_id) = unconnected_nets(grid, selection).into_iter().next() else {
+            return;
+        };
+        let components = components(grid, net_id, selection);
+        // the trace joining two parts of the net that costs the least posterior
+        let best = grid.net_to_pad_pairs[&net_id]
+            .iter()
+            .filter(|pad_pair_id| !selection.contains_key(pad_pair_id))
+            .filter(|pad_pair_id| {
+                let pad_pair = &grid.pad_pairs[pad_pair_id];
+                components[&pad_pair.start] != components[&pad_pair.end]
+            })
+            .flat_map(|pad_pair_id| {
+                candidates[pad_pair_id]
+                    .iter()
+                    .map(move |(trace_id, posterior)| (*pad_pair_id, *trace_id, *posterior))
+            })
+            .map(|(pad_pair_id, trace_id, posterior)| {
+                let victims = conflicts(grid, selection, trace_id);
+                let lost: f64 = victims
+                    .iter()
+                    .map(|victim| posterior_of(candidates, *victim, selection[victim]))
+                    .sum();
+                (lost - posterior, pad_pair_id, trace_id, victims)
+            })
+            .min_by(|(cost_a, _, id_a, _), (cost_b, _, id_b, _)| {
+                cost_a.total_cmp(cost_b).then(id_a.cmp(id_b))
+            });
+        let Some((_, pad_pair_id, trace_id, victims)) = best else {
+            return; // no candidate trace can connect the net
+        };
+        for victim in victims {
+            selection.remove(&victim);
+        }
+        selection.insert(pad_pair_id, trace_id);
+        fill_greedily(grid, candidates, selection);
+    }
+}
+
+fn posterior_of(
+    candidates: &BTreeMap<PadPairID, Vec<(TraceID, f64)>>,
+    pad_pair_id: PadPairID,
+    trace_id: TraceID,
+) -> f64 {
+    candidates[&pad_pair_id]
+        .iter()
+        .find(|(candidate, _)| *candidate == trace_id)
+        .map(|(_, posterior)| *posterior)
+        .unwrap()
+}
+
+struct BranchAndBound<'a> {
+    grid: &'a ProbaGrid,
+    pad_pairs: Vec<PadPairID>,
+    options: Vec<&'a Vec<(TraceID, f64)>>,
+    remaining_bound: Vec<f64>, // the best posterior the pad pairs from this index on could add
+    best: Option<(f64, Selection)>,
+    nodes: usize,
+}
+
+impl BranchAndBound<'_> {
+    fn search(&mut self, index: usize, selection: &mut Selection, score: f64) {
+        self.nodes += 1;
+        if self.nodes > MAX_EXACT_EXTRACTION_NODES {
+            return;
+        }
+        if let Some((best_score, _)) = &self.best {
+            if score + self.remaining_bound[index] <= *best_score {
+                return;
+            }
+        }
+        if index == self.pad_pairs.len() {
+            if unconnected_nets(self.grid, selection).is_empty() {
+                self.best = Some((score, selection.clone()));
+            }
+            return;
+        }
+        let pad_pair_id = self.pad_pairs[index];
+        for (trace_id, posterior) in self.options[index].iter() {
+            if conflicts(self.grid, selection, *trace_id).is_empty() {
+                selection.insert(pad_pair_id, *trace_id);
+                self.search(index + 1, selection, score + posterior);
+                selection.remove(&pad_pair_id);
+            }
+        }
+        // leave the pad pair unrouted
+        self.search(index + 1, selection, score);
+    }
+}
+
+/// Picks at most one trace per pad pair so that no traces of different nets collide and every net is connected,
+/// maximising the total posterior. Small boards are searched exhaustively, larger ones greedily with repairs
+pub fn select_traces(grid: &ProbaGrid) -> Result<Selection, String> {
+    let candidates = candidates(grid);
+    let mut selection = Selection::new();
+    fill_greedily(grid, &candidates, &mut selection);
+    repair(grid, &candidates, &mut selection);
+    let routed_pad_pairs: Vec<PadPairID> = candidates
+        .iter()
+        .filter(|(_, traces)| !traces.is_empty())
+        .map(|(pad_pair_id, _)| *pad_pair_id)
+        .collect();
+    if routed_pad_pairs.len() <= EXACT_EXTRACTION_MAX_PAD_PAIRS {
+        let options: Vec<&Vec<(TraceID, f64)>> = routed_pad_pairs
+            .iter()
+            .map(|pad_pair_id| &candidates[pad_pair_id])
+            .collect();
+        let mut remaining_bound = vec![0.0; routed_pad_pairs.len() + 1];
+        for i in (0..routed_pad_pairs.len()).rev() {
+            remaining_bound[i] = remaining_bound[i + 1] + options[i][0].1;
+        }
+        let mut search = BranchAndBound {
+            grid,
+            pad_pairs: routed_pad_pairs,
+            options,
+            remaining_bound,
+            // the greedy selection is the one to beat
+            best: unconnected_nets(grid, &selection)
+                .is_empty()
+                .then(|| (score(&selection, &candidates), selection.clone())),
+            nodes: 0,
+        };
+        search.search(0, &mut Selection::new(), 0.0);
+        if let Some((_, best)) = search.best {
+            selection = best;
+        }
+    }
+    let unconnected = unconnected_nets(grid, &selection);
+    if !unconnected.is_empty() {
+        return Err(format!(
+            "No conflict-free routing connects the nets {:?}, sample more traces",
+            unconnected
+        ));
+    }
+    Ok(selection)
+}
+
+fn net_of(net_info: &NetInfo) -> Result<Net, String> {
+    match net_info {
+        NetInfo {
+            pad_color: Some(pad_color),
+            route_color,
+            ..
+        } => Ok(Net::Color {
+            pad_color: pad_color.clone(),
+            route_color: route_color.clone().unwrap_or(pad_color.clone()),
+        }),
+        NetInfo {
+            pad_character: Some(pad_c),
+            route_character,
+            ..
+        } => Ok(Net::Character {
+            pad_c: *pad_c,
+            route_c: route_character.unwrap_or(pad_c.to_ascii_lowercase()),
+        }),
+        _ => Err(format!(
+            "Net {} has neither a pad color nor a pad character",
+            net_info.net_id
+        )),
+    }
+}
+
+/// Turns the most probable conflict-free selection of traces into a routed board
+pub fn extract_routing(grid: &ProbaGrid) -> Result<Grid, String> {
+    let selection = select_traces(grid)?;
+    let nets: HashMap<NetID, Net> = grid
+        .nets
+        .iter()
+        .map(|(net_id, net_info)| Ok((*net_id, net_of(net_info)?)))
+        .collect::<Result<_, String>>()?;
+    let mut routed_grid = Grid::new(grid.width, grid.height);
+    routed_grid.keepouts = grid.keepouts.clone();
+    routed_grid.design_rules = grid.design_rules.map_nets(|net_id| nets[net_id].clone());
+    for (net_id, pads) in &grid.net_to_pads {
+        for pad in pads {
+            routed_grid.insert_pad(nets[net_id].clone(), *pad);
+        }
+    }
+    for trace_id in selection.values() {
+        let trace = &grid.traces[trace_id];
+        let net = &nets[&trace.net_id];
+        routed_grid
+            .traces
+            .entry(net.clone())
+            .or_default()
+            .extend(trace.trace_path.covered.iter().cloned());
+        if !trace.trace_path.diagonal_covered.is_empty() {
+            routed_grid
+                .diagonal_traces
+                .entry(net.clone())
+                .or_default()
+                .extend(trace.trace_path.diagonal_covered.iter().cloned());
+        }
+    }
+    Ok(routed_grid)
+}
@@ -1,7 +1,11 @@
 use std::collections::{BTreeSet, HashMap, HashSet};
 
+use serde::{Deserialize, Serialize};
 use shared::interface_types::{Color, ColorGrid};
 
+use crate::design_rules::{inflate, DesignRules};
+use crate::keepout::{Keepout, KeepoutShape, KEEPOUT_COLOR};
+
 #[derive(Debug, Clone, PartialEq, Hash, Eq, PartialOrd, Ord)]
 pub enum Net {
     Character {
@@ -14,11 +18,45 @@ pub enum Net {
     },
 }
 
-#[derive(Debug, Clone, PartialEq, Hash, Eq, Copy, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Hash, Eq, Copy, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Point {
     pub x: usize,
     pub y: usize,
 }
+/// A grid cell on a specific copper layer, layer 0 being the top layer
+#[derive(Debug, Clone, PartialEq, Hash, Eq, Copy, PartialOrd, Ord)]
+pub struct LayerPoint {
+    pub point: Point,
+    pub layer: usize,
+}
+
+impl LayerPoint {
+    pub fn new(x: usize, y: usize, layer: usize) -> Self {
+        LayerPoint {
+            point: Point { x, y },
+            layer,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Hash, Eq, Copy, PartialOrd, Ord)]
+pub enum PadKind {
+    ThroughHole,          // the pad exists on every copper layer
+    Smd { layer: usize }, // the pad only exists on one copper layer
+}
+
+impl PadKind {
+    pub fn layers(&self, num_layers: usize) -> BTreeSet<usize> {
+        match self {
+            PadKind::ThroughHole => (0..num_layers).collect(),
+            PadKind::Smd { layer } => {
+                assert!(*layer < num_layers, "SMD pad layer out of bounds");
+                BTreeSet::from([*layer])
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Hash, Eq, Copy, PartialOrd, Ord)]
 pub struct PointPair {
     start: Point,
@@ -53,42 +91,175 @@ impl PointPair {
 //     pub point: Point,
 // }
 
-#[derive(Debug, Clone)]
+// the first and last cells of every run of cells, each offset from the previous one by step
+fn runs(cells: &BTreeSet<Point>, step: (isize, isize)) -> Vec<(Point, Point)> {
+    let offset = |point: Point, k: isize| -> Option<Point> {
+        Some(Point {
+            x: point.x.checked_add_signed(step.0 * k)?,
+            y: point.y.checked_add_signed(step.1 * k)?,
+        })
+    };
+    let mut runs = Vec::new();
+    for &start in cells {
+        if offset(start, -1).is_some_and(|previous| cells.contains(&previous)) {
+            continue; // not the first cell of its run
+        }
+        let mut end = start;
+        while let Some(next) = offset(end, 1).filter(|next| cells.contains(next)) {
+            end = next;
+        }
+        runs.push((start, end));
+    }
+    runs
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Grid {
     pub pads: HashMap<Net, BTreeSet<Point>>,
-    pub traces: HashMap<Net, HashSet<Point>>,
-    pub diagonal_traces: HashMap<Net, HashSet<Point>>, // the point is at the top left corner of the diagonal trace
+    pub pad_kinds: HashMap<Point, PadKind>, // pads missing from this map are through-hole
+    pub traces: HashMap<Net, HashSet<LayerPoint>>,
+    pub diagonal_traces: HashMap<Net, HashSet<LayerPoint>>, // the point is at the top left corner of the diagonal trace
+    pub vias: HashMap<Net, HashSet<Point>>, // a via connects every copper layer at its point
     pub width: usize,
     pub height: usize,
+    pub layers: usize,
+    pub design_rules: DesignRules<Net>,
+    pub keepouts: Vec<Keepout>,
 }
 
 impl Grid {
     pub fn new(width: usize, height: usize) -> Self {
+        Self::new_with_layers(width, height, 1)
+    }
+    pub fn new_with_layers(width: usize, height: usize, layers: usize) -> Self {
+        assert!(layers >= 1, "Grid must have at least one layer");
         Grid {
             pads: HashMap::new(),
+            pad_kinds: HashMap::new(),
             traces: HashMap::new(),
             diagonal_traces: HashMap::new(),
+            vias: HashMap::new(),
             width,
             height,
+            layers,
+            design_rules: DesignRules::default(),
+            keepouts: Vec::new(),
         }
     }
-    pub fn pads_except(&self, net: &Net) -> HashSet<Point> {
+    pub fn pad_kind(&self, point: Point) -> PadKind {
+        self.pad_kinds
+            .get(&point)
+            .cloned()
+            .unwrap_or(PadKind::ThroughHole)
+    }
+    pub fn pad_layers(&self, point: Point) -> BTreeSet<usize> {
+        self.pad_kind(point).layers(self.layers)
+    }
+    /// The layers a route may start or end on at this point: all layers of a pad,
+    /// or a single layer the net may use for a junction that is not a pad (e.g. a Steiner point),
+    /// so that every route meeting at the junction ends on the same layer
+    pub fn terminal_layers(&self, net: &Net, point: Point) -> BTreeSet<usize> {
+        if self.pads.values().any(|points| points.contains(&point)) {
+            self.pad_layers(point)
+        } else {
+            self.design_rules
+                .allowed_layers(net, self.layers)
+                .into_iter()
+                .take(1)
+                .collect()
+        }
+    }
+    pub fn pads_except(&self, net: &Net) -> HashSet<LayerPoint> {
         self.pads
             .iter()
             .filter(|(n, _)| **n != *net)
             .flat_map(|(_, points)| points.iter())
-            .cloned()
+            .flat_map(|point| {
+                self.pad_layers(*point).into_iter().map(|layer| LayerPoint {
+                    point: *point,
+                    layer,
+                })
+            })
             .collect()
     }
-    pub fn routes_except(&self, net: &Net) -> HashSet<Point> {
+    pub fn routes_except(&self, net: &Net) -> HashSet<LayerPoint> {
+        let via_points = self
+            .vias
+            .iter()
+            .filter(|(n, _)| **n != *net)
+            .flat_map(|(_, points)| points.iter())
+            .flat_map(|point| {
+                (0..self.layers).map(|layer| LayerPoint {
+                    point: *point,
+                    layer,
+                })
+            });
         self.traces
             .iter()
             .filter(|(n, _)| **n != *net)
             .flat_map(|(_, points)| points.iter())
             .cloned()
+            .chain(via_points)
             .collect()
     }
-    pub fn diagonal_routes_except(&self, net: &Net) -> HashSet<Point> {
+    /// Pads of other nets, inflated so that the copper of `net` keeps its clearance from them
+    pub fn pad_obstacles(&self, net: &Net) -> HashSet<LayerPoint> {
+        let mut obstacles = HashSet::new();
+        for (other, points) in self.pads.iter().filter(|(n, _)| **n != *net) {
+            let pad_points: Vec<LayerPoint> = points
+                .iter()
+                .flat_map(|point| {
+                    self.pad_layers(*point).into_iter().map(|layer| LayerPoint {
+                        point: *point,
+                        layer,
+                    })
+                })
+                .collect();
+            obstacles.extend(inflate(
+                pad_points,
+                self.design_rules.pad_inflation(net, other),
+            ));
+        }
+        obstacles
+    }
+    /// Cells inside keepouts, inflated so that the copper of `net` stays out of them
+    pub fn keepout_obstacles(&self, net: &Net) -> HashSet<LayerPoint> {
+        let cells = self
+            .keepouts
+            .iter()
+            .flat_map(|keepout| keepout.cells(self.width, self.height, self.layers));
+        inflate(cells, self.design_rules.trace_radius(net))
+    }
+    /// Traces and vias of other nets, inflated by both trace widths and the clearance between the nets
+    pub fn route_obstacles(&self, net: &Net) -> HashSet<LayerPoint> {
+        let mut obstacles = HashSet::new();
+        let other_nets: BTreeSet<&Net> = self
+            .traces
+            .keys()
+            .chain(self.vias.keys())
+            .filter(|n| **n != *net)
+            .collect();
+        for other in other_nets {
+            let trace_points = self.traces.get(other).into_iter().flatten().cloned();
+            let via_points = self
+                .vias
+                .get(other)
+                .into_iter()
+                .flatten()
+                .flat_map(|point| {
+                    (0..self.layers).map(|layer| LayerPoint {
+                        point: *point,
+                        layer,
+                    })
+                });
+            obstacles.extend(inflate(
+                trace_points.chain(via_points),
+                self.design_rules.trace_inflation(net, other),
+            ));
+        }
+        obstacles
+    }
+    pub fn diagonal_routes_except(&self, net: &Net) -> HashSet<LayerPoint> {
         self.diagonal_traces
             .iter()
             .filter(|(n, _)| **n != *net)
@@ -96,36 +267,200 @@ impl Grid {
             .cloned()
             .collect()
     }
-    fn to_char_matrix(&self) -> Vec<Vec<char>> {
+    /// The traces of a net on a layer as straight segments between cell centers,
+    /// a run of cells in the same direction becoming a single segment
+    pub fn trace_segments(&self, net: &Net, layer: usize) -> Vec<(Point, Point)> {
+        let on_layer = |points: Option<&HashSet<LayerPoint>>| -> BTreeSet<Point> {
+            points
+                .into_iter()
+                .flatten()
+                .filter(|point| point.layer == layer)
+                .map(|point| point.point)
+                .collect()
+        };
+        let cells = on_layer(self.traces.get(net));
+        let diagonals = on_layer(self.diagonal_traces.get(net));
+        // only the top left corner of a diagonal is kept, the covered cells tell its direction
+        let (falling, rising): (BTreeSet<Point>, BTreeSet<Point>) =
+            diagonals.iter().partition(|point| {
+                cells.contains(point)
+                    && cells.contains(&Point {
+                        x: point.x + 1,
+                        y: point.y + 1,
+                    })
+            });
+        let mut segments: Vec<(Point, Point)> = Vec::new();
+        for step in [(1, 0), (0, 1)] {
+            segments.extend(runs(&cells, step).into_iter().filter(|(a, b)| a != b));
+        }
+        for (start, end) in runs(&falling, (1, 1)) {
+            segments.push((
+                start,
+                Point {
+                    x: end.x + 1,
+                    y: end.y + 1,
+                },
+            ));
+        }
+        for (start, end) in runs(&rising, (-1, 1)) {
+            segments.push((
+                Point {
+                    x: start.x + 1,
+                    y: start.y,
+                },
+                Point {
+                    x: end.x,
+                    y: end.y + 1,
+                },
+            ));
+        }
+        segments
+    }
+    // the character a pad of the net is written with
+    fn text_pad_char(net: &Net) -> char {
+        match net {
+            Net::Character { pad_c, .. } => *pad_c,
+            _ => panic!("Unsupported Net type for the text format: {:?}", net),
+        }
+    }
+    // the character traces on the top layer are drawn with, when from_string reads it back as the same net.
+    // Traces of other nets are written as trace directives
+    fn text_route_char(net: &Net) -> Option<char> {
+        match net {
+            Net::Character { pad_c, route_c }
+                if *route_c == pad_c.to_ascii_lowercase()
+                    && *route_c != *pad_c
+                    && !matches!(pad_c, ' ' | '#') =>
+            {
+                Some(*route_c)
+            }
+            _ => None,
+        }
+    }
+    // whether the cell is a pad of the net on that layer, which from_string adds to the trace of a routed net
+    fn is_pad_trace(&self, net: &Net, cell: &LayerPoint) -> bool {
+        self.pads
+            .get(net)
+            .is_some_and(|pads| pads.contains(&cell.point))
+            && self.pad_layers(cell.point).contains(&cell.layer)
+    }
+    /// The top layer as a matrix of characters, and the directives for what the matrix cannot show
+    fn to_char_matrix(&self) -> (Vec<Vec<char>>, Vec<String>) {
         let width = self.width;
         let height = self.height;
-        let mut grid_string: Vec<Vec<char>> = vec![vec![' '; width as usize]; height as usize];
-        for (net, points) in &self.pads {
-            if let Net::Character { pad_c, route_c } = net {
-                let net_char = pad_c;
-                for point in points {
-                    assert!(point.x < width && point.y < height, "Point out of bounds");
-                    grid_string[point.y as usize][point.x as usize] = *net_char;
+        let mut grid_string: Vec<Vec<char>> = vec![vec![' '; width]; height];
+        let mut directives = Vec::new();
+        if self.layers > 1 {
+            directives.push(format!("layers {}", self.layers));
+        }
+        let mut keepout_directives = Vec::new();
+        for keepout in &self.keepouts {
+            match keepout {
+                Keepout {
+                    shape: KeepoutShape::Rect { min, max },
+                    layers: None,
+                } if min == max && min.x < width && min.y < height => {
+                    grid_string[min.y][min.x] = '#';
                 }
-            } else {
-                panic!("Unsupported Net type for pads: {:?}", net);
+                _ => keepout_directives.push(keepout.to_directive()),
             }
         }
-        for (net, points) in &self.traces {
-            if let Net::Character { pad_c, route_c } = net {
-                let route_char = route_c;
-                for point in points {
+        let nets: Vec<&Net> = self
+            .pads
+            .keys()
+            .chain(self.traces.keys())
+            .chain(self.diagonal_traces.keys())
+            .chain(self.vias.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        for net in &nets {
+            let pad_c = Self::text_pad_char(net);
+            let mut points: Vec<&Point> = self.pads.get(*net).into_iter().flatten().collect();
+            points.sort();
+            for point in points {
+                assert!(point.x < width && point.y < height, "Point out of bounds");
+                grid_string[point.y][point.x] = pad_c;
+                if let PadKind::Smd { layer } = self.pad_kind(*point) {
+                    directives.push(format!("smd {} {} {} {}", pad_c, point.x, point.y, layer));
+                }
+            }
+        }
+        for net in &nets {
+            let pad_c = Self::text_pad_char(net);
+            let route_c = Self::text_route_char(net);
+            let mut cells: Vec<&LayerPoint> = self.traces.get(*net).into_iter().flatten().collect();
+            cells.sort_by_key(|cell| (cell.layer, cell.point.y, cell.point.x));
+            let layers: BTreeSet<usize> = cells.iter().map(|cell| cell.layer).collect();
+            for layer in layers {
+                let on_layer = || cells.iter().filter(move |cell| cell.layer == layer);
+                // pad cells are implied once the net has another trace cell on the layer
+                let mut explicit: Vec<&LayerPoint> = on_layer()
+                    .filter(|cell| !self.is_pad_trace(net, cell))
+                    .copied()
+                    .collect();
+                if explicit.is_empty() {
+                    explicit.extend(on_layer().take(1));
+                }
+                for LayerPoint { point, layer } in explicit {
                     assert!(point.x < width && point.y < height, "Point out of bounds");
-                    grid_string[point.y as usize][point.x as usize] = *route_char;
+                    let cell = &mut grid_string[point.y][point.x];
+                    match route_c {
+                        Some(route_c) if *layer == 0 && *cell == ' ' => *cell = route_c,
+                        _ => directives
+                            .push(format!("trace {} {} {} {}", pad_c, point.x, point.y, layer)),
+                    }
+                }
+                // pads the trace does not cover on this layer
+                let mut pads: Vec<&Point> = self.pads.get(*net).into_iter().flatten().collect();
+                pads.sort();
+                for point in pads {
+                    let cell = LayerPoint {
+                        point: *point,
+                        layer,
+                    };
+                    if self.is_pad_trace(net, &cell) && !self.traces[*net].contains(&cell) {
+                        directives
+                            .push(format!("bare {} {} {} {}", pad_c, point.x, point.y, layer));
+                    }
                 }
-            } else {
-                panic!("Unsupported Net type for traces: {:?}", net);
             }
         }
-        grid_string
+        for net in &nets {
+            let pad_c = Self::text_pad_char(net);
+            let mut cells: Vec<&LayerPoint> = self
+                .diagonal_traces
+                .get(*net)
+                .into_iter()
+                .flatten()
+                .collect();
+            cells.sort_by_key(|cell| (cell.layer, cell.point.y, cell.point.x));
+            for LayerPoint { point, layer } in cells {
+                directives.push(format!(
+                    "diagonal {} {} {} {}",
+                    pad_c, point.x, point.y, layer
+                ));
+            }
+            let mut vias: Vec<&Point> = self.vias.get(*net).into_iter().flatten().collect();
+            vias.sort_by_key(|point| (point.y, point.x));
+            for point in vias {
+                directives.push(format!("via {} {} {}", pad_c, point.x, point.y));
+            }
+        }
+        let mut classes: Vec<String> = self
+            .design_rules
+            .net_to_class
+            .iter()
+            .filter(|(net, _)| self.pads.contains_key(*net))
+            .map(|(net, class)| format!("class {} {}", Self::text_pad_char(net), class))
+            .collect();
+        classes.sort();
+        directives.extend(classes);
+        directives.extend(keepout_directives);
+        (grid_string, directives)
     }
 
-    fn build_grid_string(char_matrix: &Vec<Vec<char>>) -> String {
+    fn build_grid_string(char_matrix: &[Vec<char>], directives: &[String]) -> String {
         let width = char_matrix[0].len();
         let horizontal_wall = "#".repeat(width + 2);
         let mut result = String::new();
@@ -143,50 +478,258 @@ impl Grid {
         result.push_str(&horizontal_wall);
         result.push('\n');
 
+        for directive in directives {
+            result.push_str(directive);
+            result.push('\n');
+        }
+
         result
     }
     pub fn print(&self) {
-        let char_matrix = self.to_char_matrix();
-        let result = Self::build_grid_string(&char_matrix);
-        println!("{}", result);
+        println!("{}", self.to_string());
     }
+    /// The text format read by from_string. Pads are written with their character and the traces on
+    /// the top layer with the lowercase pad character. The pads of a net count as part of its trace on
+    /// every layer the trace reaches, and the lines after the bottom wall hold what the matrix cannot show:
+    /// layers, SMD pads, traces on other layers, bare pads, diagonal traces, vias, net classes and keepouts.
+    /// Nets are read back by their pad character, so a net routed with another character than the lowercase
+    /// one keeps its traces but not its route character. Design rules other than net classes are not part of the text format
     pub fn to_string(&self) -> String {
-        let char_matrix = self.to_char_matrix();
-        Self::build_grid_string(&char_matrix)
+        let (char_matrix, directives) = self.to_char_matrix();
+        Self::build_grid_string(&char_matrix, &directives)
     }
     pub fn from_string(s: &str) -> Self {
+        Self::try_from_string(s).unwrap()
+    }
+    /// Parses the text format written by to_string, e.g. from a file a user picked
+    pub fn try_from_string(s: &str) -> Result<Self, String> {
         let mut lines = s.lines().collect::<Vec<&str>>();
-        let first_line = lines[0];
-        let width = first_line.len() as usize - 2; // subtract 2 for the walls
-        assert!(
-            lines.len() >= 3,
-            "Grid must have at least 3 lines (top wall, bottom wall, and one row of data)"
-        );
+        if lines.len() < 3 {
+            return Err(
+                "Grid must have at least 3 lines (top wall, bottom wall, and one row of data)"
+                    .to_string(),
+            );
+        }
+        let is_wall = |line: &str| !line.is_empty() && line.chars().all(|c| c == '#');
+        if !is_wall(lines[0]) {
+            return Err("Grid must start with a wall".to_string());
+        }
+        let width = lines[0].chars().count().saturating_sub(2); // subtract 2 for the walls
+        if width == 0 {
+            return Err("Grid must be at least one cell wide".to_string());
+        }
+        // the lines after the bottom wall are directives, e.g. "class A power"
+        // a row of keepout cells looks like a wall, so the bottom wall is the last one
+        let bottom_wall = lines
+            .iter()
+            .skip(1)
+            .rposition(|line| is_wall(line))
+            .map(|i| i + 1)
+            .ok_or_else(|| "Grid must have a bottom wall".to_string())?;
+        let directives = lines.split_off(bottom_wall + 1);
         lines.pop(); // remove the last line (bottom wall)
         lines.remove(0); // remove the first line (top wall)
-        let height = lines.len() as usize;
+        if lines.is_empty() {
+            return Err("Grid must have at least one row".to_string());
+        }
+        for (y, line) in lines.iter().enumerate() {
+            if line.chars().count() != width + 2 || !line.starts_with('#') || !line.ends_with('#') {
+                return Err(format!(
+                    "Row {} must have {} cells between two walls",
+                    y, width
+                ));
+            }
+        }
+        let height = lines.len();
         let mut pads: HashMap<Net, BTreeSet<Point>> = HashMap::new();
+        let mut traces: HashMap<Net, HashSet<LayerPoint>> = HashMap::new();
+        let mut keepouts: Vec<Keepout> = Vec::new();
         for (y, line) in lines.iter().enumerate() {
-            for (x, c) in line.chars().enumerate() {
-                if c != ' ' && c != '#' {
-                    let point = Point {
-                        x: x as usize - 1,
-                        y: y as usize,
+            // skip the walls on both sides
+            for (x, c) in line.chars().enumerate().skip(1).take(width) {
+                if c == '#' {
+                    // a wall character inside the grid is a keepout cell on every layer
+                    let point = Point { x: x - 1, y };
+                    keepouts.push(Keepout {
+                        shape: KeepoutShape::Rect {
+                            min: point,
+                            max: point,
+                        },
+                        layers: None,
+                    });
+                } else if c.is_ascii_lowercase() {
+                    // a trace cell on the top layer of the net with the uppercase pad character
+                    let net = Net::Character {
+                        pad_c: c.to_ascii_uppercase(),
+                        route_c: c,
                     };
+                    traces
+                        .entry(net)
+                        .or_default()
+                        .insert(LayerPoint::new(x - 1, y, 0));
+                } else if c != ' ' {
+                    let point = Point { x: x - 1, y };
                     let net = Net::Character {
                         pad_c: c,
                         route_c: c.to_ascii_lowercase(),
-                    }; // assuming pad and route characters are the same
+                    }; // the route character is the lowercase pad character
                     pads.entry(net).or_default().insert(point);
                 }
             }
         }
-        Grid {
+        let mut grid = Grid {
             pads,
-            traces: HashMap::new(),
+            pad_kinds: HashMap::new(),
+            traces,
             diagonal_traces: HashMap::new(),
+            vias: HashMap::new(),
             width,
             height,
+            layers: 1,
+            design_rules: DesignRules::default(),
+            keepouts,
+        };
+        let mut bare: HashSet<(Net, LayerPoint)> = HashSet::new();
+        for directive in directives.iter().map(|line| line.trim()) {
+            if directive.is_empty() {
+                continue;
+            }
+            let result = match directive.split_whitespace().collect::<Vec<_>>().as_slice() {
+                // bare pads are only known once every trace has been read
+                ["bare", pad, coordinates @ ..] => {
+                    grid.text_cell(pad, coordinates).map(|(net, cell)| {
+                        bare.insert((net, cell));
+                    })
+                }
+                _ => grid.apply_directive(directive),
+            };
+            result.map_err(|e| format!("Invalid directive {:?}: {}", directive, e))?;
+        }
+        let pad_traces: Vec<(Net, LayerPoint)> = grid
+            .traces
+            .iter()
+            .flat_map(|(net, cells)| {
+                let layers: BTreeSet<usize> = cells.iter().map(|cell| cell.layer).collect();
+                let grid = &grid;
+                grid.pads
+                    .get(net)
+                    .into_iter()
+                    .flatten()
+                    .flat_map(move |point| {
+                        grid.pad_layers(*point)
+                            .intersection(&layers)
+                            .map(|layer| LayerPoint {
+                                point: *point,
+                                layer: *layer,
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .map(move |cell| (net.clone(), cell))
+            })
+            .filter(|pad_trace| !bare.contains(pad_trace))
+            .collect();
+        for (net, cell) in pad_traces {
+            grid.traces.entry(net).or_default().insert(cell);
+        }
+        Ok(grid)
+    }
+    fn pad_net(&self, pad: &str) -> Result<Net, String> {
+        let net = Self::text_net(pad)?;
+        if !self.pads.contains_key(&net) {
+            return Err(format!("No pads for net {:?}", pad));
+        }
+        Ok(net)
+    }
+    // the net of a pad character, whether or not it has pads yet
+    fn text_net(pad: &str) -> Result<Net, String> {
+        let mut chars = pad.chars();
+        let pad_c = match (chars.next(), chars.next()) {
+            (Some(pad_c), None) => pad_c,
+            _ => return Err(format!("Expected a single pad character, got {:?}", pad)),
+        };
+        Ok(Net::Character {
+            pad_c,
+            route_c: pad_c.to_ascii_lowercase(),
+        })
+    }
+    // the net and cell of a directive like "trace A 3 4 1"
+    fn text_cell(&self, pad: &str, coordinates: &[&str]) -> Result<(Net, LayerPoint), String> {
+        let numbers = coordinates
+            .iter()
+            .map(|word| {
+                word.parse::<usize>()
+                    .map_err(|e| format!("Invalid number {:?}: {}", word, e))
+            })
+            .collect::<Result<Vec<usize>, String>>()?;
+        let cell = match numbers.as_slice() {
+            [x, y] => LayerPoint::new(*x, *y, 0),
+            [x, y, layer] => LayerPoint::new(*x, *y, *layer),
+            _ => return Err("Expected x y and an optional layer".to_string()),
+        };
+        if cell.point.x >= self.width || cell.point.y >= self.height || cell.layer >= self.layers {
+            return Err(format!(
+                "Cell ({}, {}) on layer {} is outside of the grid",
+                cell.point.x, cell.point.y, cell.layer
+            ));
+        }
+        Ok((Self::text_net(pad)?, cell))
+    }
+    /// Applies one line of the directives section of the text format
+    pub fn apply_directive(&mut self, directive: &str) -> Result<(), String> {
+        let words: Vec<&str> = directive.split_whitespace().collect();
+        match words.as_slice() {
+            ["class", pad, class_name] => {
+                let net = self.pad_net(pad)?;
+                self.design_rules.set_net_class(net, class_name)
+            }
+            ["keepout", keepout @ ..] => {
+                self.keepouts.push(Keepout::parse(keepout)?);
+                Ok(())
+            }
+            ["layers", layers] => {
+                let layers = layers
+                    .parse::<usize>()
+                    .map_err(|e| format!("Invalid number of layers {:?}: {}", layers, e))?;
+                if layers == 0 {
+                    return Err("A grid has at least one layer".to_string());
+                }
+                self.layers = layers;
+                Ok(())
+            }
+            ["smd", pad, coordinates @ ..] => {
+                let (net, cell) = self.text_cell(pad, coordinates)?;
+                if !self
+                    .pads
+                    .get(&net)
+                    .is_some_and(|pads| pads.contains(&cell.point))
+                {
+                    return Err(format!("No pad of net {:?} at the cell", pad));
+                }
+                self.pad_kinds
+                    .insert(cell.point, PadKind::Smd { layer: cell.layer });
+                Ok(())
+            }
+            ["trace", pad, coordinates @ ..] => {
+                let (net, cell) = self.text_cell(pad, coordinates)?;
+                self.traces.entry(net).or_default().insert(cell);
+                Ok(())
+            }
+            ["diagonal", pad, coordinates @ ..] => {
+                let (net, cell) = self.text_cell(pad, coordinates)?;
+                if cell.point.x + 1 >= self.width || cell.point.y + 1 >= self.height {
+                    return Err(
+                        "A diagonal trace needs the cells right of and below its point".to_string(),
+                    );
+                }
+                self.diagonal_traces.entry(net).or_default().insert(cell);
+                Ok(())
+            }
+            ["via", pad, x, y] => {
+                let (net, cell) = self.text_cell(pad, &[x, y])?;
+                self.vias.entry(net).or_default().insert(cell.point);
+                Ok(())
+            }
+            _ => Err("Unknown directive".to_string()),
         }
     }
     pub fn remove_pad(&mut self, point: Point) {
@@ -203,10 +746,16 @@ impl Grid {
                 }
             })
             .collect();
+        self.pad_kinds.remove(&point);
     }
     pub fn insert_pad(&mut self, net: Net, point: Point) {
         self.pads.entry(net).or_default().insert(point);
     }
+    pub fn insert_smd_pad(&mut self, net: Net, point: Point, layer: usize) {
+        assert!(layer < self.layers, "SMD pad layer out of bounds");
+        self.pads.entry(net).or_default().insert(point);
+        self.pad_kinds.insert(point, PadKind::Smd { layer });
+    }
     pub fn to_color_grid(&self) -> ColorGrid {
         let mut color_grid = vec![
             vec![
@@ -219,6 +768,11 @@ impl Grid {
             ];
             self.height
         ];
+        for keepout in &self.keepouts {
+            for LayerPoint { point, .. } in keepout.cells(self.width, self.height, self.layers) {
+                color_grid[point.y][point.x] = KEEPOUT_COLOR;
+            }
+        }
         for (net, points) in &self.pads {
             if let Net::Color {
                 pad_color,
@@ -242,17 +796,39 @@ impl Grid {
                 route_color,
             } = net
             {
-                for point in points {
+                // draw the deeper layers first so that the top layer stays visible
+                let mut points: Vec<&LayerPoint> = points.iter().collect();
+                points.sort_by_key(|p| std::cmp::Reverse(p.layer));
+                for LayerPoint { point, layer } in points {
                     assert!(
                         point.x < self.width && point.y < self.height,
                         "Point out of bounds"
                     );
-                    color_grid[point.y][point.x] = route_color.clone();
+                    // traces on the inner and bottom layers are drawn darker
+                    let shade = |c: u8| (c as usize * 2 / (2 + layer)) as u8;
+                    color_grid[point.y][point.x] = Color {
+                        r: shade(route_color.r),
+                        g: shade(route_color.g),
+                        b: shade(route_color.b),
+                    };
                 }
             } else {
                 panic!("Unsupported Net type for traces: {:?}", net);
             }
         }
+        for (net, points) in &self.vias {
+            if let Net::Color {
+                pad_color,
+                route_color: _,
+            } = net
+            {
+                for point in points {
+                    color_grid[point.y][point.x] = pad_color.clone();
+                }
+            } else {
+                panic!("Unsupported Net type for vias: {:?}", net);
+            }
+        }
         ColorGrid { grid: color_grid }
     }
 }
@@ -1,36 +1,34 @@
-use std::{collections::HashMap, num::NonZeroUsize, sync::Mutex};
-
-use lazy_static::lazy_static;
-
 pub const LENGTH_PENALTY_RATE: f64 = 1.0;
 pub const TURN_PENALTY_RATE: f64 = 3.0;
 pub const HALF_PROBABILITY_RAW_SCORE: f64 = 10.0;
+pub const DEFAULT_SCORE_WEIGHT: f64 = 0.3; // Exponent of the score in the posterior
+pub const DEFAULT_OPPORTUNITY_COST_WEIGHT: f64 = 0.3; // Exponent of the opportunity cost in the posterior
+pub const VIA_COST: f64 = 5.0; // Cost of a layer transition, in grid cells
+pub const NAIVE_GRID_LAYERS: usize = 2; // Every board we route has at least two copper layers
 
 pub const MAX_TRACES_PER_ITERATION: usize = 4; // Maximum number of traces per iteration
 pub const MAX_GENERATION_ATTEMPTS: usize = 10; // Maximum number of attempts to generate a trace
+pub const CANDIDATE_REUSE_PENALTY: f64 = 1.0; // Extra cost of a cell each time a path found for the same pad pair goes through it
+pub const CANDIDATE_COST_JITTER: f64 = 0.05; // Maximum random cost added to every cell when generating candidates, to break ties
+pub const MAX_RIP_UP_ITERATIONS: usize = 50; // Maximum number of rip-up and reroute rounds in naive_route
+
+pub const FIRST_ITERATION_PRIOR_PROBABILITY: f64 = 0.7; // Every iteration gets this share of the prior probability left by the previous ones
+pub const PROBA_CONVERGENCE_TOLERANCE: f64 = 1e-3; // The proba solver stops once no posterior changes more than this in an iteration
+pub const PROBA_MAX_ITERATIONS: usize = 20; // The proba solver gives up after this many iterations
+pub const DEFAULT_PROBA_SEED: u64 = 0; // Seed of the proba grid when proba_init is not given one
+pub const MAX_PROBA_SNAPSHOTS: usize = 100; // The proba grid history drops its oldest snapshots beyond this many
+
+pub const MAX_BOARD_GRID_CELLS: usize = 1_000_000; // Opening a KiCad board fails if the pitch would give more cells than this
+pub const KICAD_TRACK_WIDTH: f64 = 0.25; // Width in mm of an exported trace that is one cell wide, every extra cell adds a pitch
+pub const KICAD_VIA_SIZE: f64 = 0.6; // Diameter in mm of an exported via
+pub const KICAD_VIA_DRILL: f64 = 0.3; // Drill in mm of an exported via
+pub const SES_RESOLUTION: u32 = 10; // Coordinates of an exported Specctra session are in this fraction of a um
+
+pub const MAX_PATHFINDER_ITERATIONS: usize = 50; // Maximum number of negotiation rounds in pathfinder_route
+pub const PATHFINDER_INITIAL_PRESENT_FACTOR: f64 = 0.5; // Penalty per other net sharing a cell in the first round
+pub const PATHFINDER_PRESENT_FACTOR_GROWTH: f64 = 1.5; // The present penalty grows by this factor every round
+pub const PATHFINDER_HISTORY_INCREMENT: f64 = 1.0; // History cost added to a cell each round it is overused
 
-lazy_static! {
-    pub static ref SCORE_WEIGHT: Mutex<f64> = Mutex::new(0.3);
-    pub static ref OPPORTUNITY_COST_WEIGHT: Mutex<f64> = Mutex::new(0.3);
-    pub static ref ITERATION_TO_PRIOR_PROBABILITY: HashMap<NonZeroUsize, f64> = {
-        let mut map = HashMap::new();
-        let mut remaining_probability = 1.0; // Start with a total probability of 1.0
-        let probability_1 = 0.7; // Probability for the first iteration
-        remaining_probability -= probability_1; // Subtract the first iteration's probability
-        let probability_2 = remaining_probability * 0.7;
-        remaining_probability -= probability_2; // Subtract the second iteration's probability
-        let probability_3 = remaining_probability * 0.7; // Third iteration gets half of the remaining probability
-        // remaining_probability -= probability_3; // Subtract the third iteration's probability
-        map.insert(NonZeroUsize::new(1).unwrap(), probability_1);
-        map.insert(NonZeroUsize::new(2).unwrap(), probability_2);
-        map.insert(NonZeroUsize::new(3).unwrap(), probability_3);
-        let mut probability_sum = 0.0;
-        for value in map.values() {
-            probability_sum += value;
-            if probability_sum > 1.0 {
-                panic!("Total prior probability exceeds 1.0: {}", probability_sum);
-            }
-        }
-        map
-    };
-}
+pub const MAX_EXTRACTION_REPAIRS: usize = 100; // Maximum number of repair rounds when extracting a routing from the proba grid
+pub const EXACT_EXTRACTION_MAX_PAD_PAIRS: usize = 16; // Boards with at most this many routed pad pairs are searched exhaustively
+pub const MAX_EXACT_EXTRACTION_NODES: usize = 1_000_000; // The exhaustive search keeps its best selection after this many nodes
@@ -0,0 +1,193 @@
+use std::collections::{BTreeSet, HashSet};
+
+use serde::{Deserialize, Serialize};
+use shared::interface_types::Color;
+
+use crate::grid::{LayerPoint, Point};
+
+pub const KEEPOUT_COLOR: Color = Color {
+    r: 128,
+    g: 128,
+    b: 128,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeepoutShape {
+    Rect { min: Point, max: Point }, // both corners are inside the keepout
+    Polygon(Vec<Point>),             // the vertices in order, cells on the boundary are inside
+}
+
+/// An area where no copper may be placed, e.g. a mounting hole, a board cutout or the area under a connector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Keepout {
+    pub shape: KeepoutShape,
+    pub layers: Option<BTreeSet<usize>>, // None means every layer
+}
+
+// whether p lies on the segment from a to b
+fn on_segment(p: (i64, i64), a: (i64, i64), b: (i64, i64)) -> bool {
+    let cross = (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0);
+    cross == 0
+        && p.0 >= a.0.min(b.0)
+        && p.0 <= a.0.max(b.0)
+        && p.1 >= a.1.min(b.1)
+        && p.1 <= a.1.max(b.1)
+}
+
+impl Keepout {
+    pub fn contains(&self, point: Point) -> bool {
+        match &self.shape {
+            KeepoutShape::Rect { min, max } => {
+                point.x >= min.x && point.x <= max.x && point.y >= min.y && point.y <= max.y
+            }
+            KeepoutShape::Polygon(vertices) => {
+                let p = (point.x as i64, point.y as i64);
+                let vertices: Vec<(i64, i64)> = vertices
+                    .iter()
+                    .map(|vertex| (vertex.x as i64, vertex.y as i64))
+                    .collect();
+                let edges = || {
+                    vertices
+                        .iter()
+                        .zip(vertices.iter().cycle().skip(1))
+                        .map(|(a, b)| (*a, *b))
+                };
+                if edges().any(|(a, b)| on_segment(p, a, b)) {
+                    return true;
+                }
+                // even-odd rule with a ray towards positive x
+                let mut inside = false;
+                for (a, b) in edges() {
+                    if (a.1 > p.1) != (b.1 > p.1) {
+                        // x of the edge at the height of p, compared without division
+                        let lhs = (p.0 - a.0) * (b.1 - a.1);
+                        let rhs = (b.0 - a.0) * (p.1 - a.1);
+                        if (b.1 > a.1 && lhs < rhs) || (b.1 < a.1 && lhs > rhs) {
+                            inside = !inside;
+                        }
+                    }
+                }
+                inside
+            }
+        }
+    }
+    pub fn on_layer(&self, layer: usize) -> bool {
+        self.layers
+            .as_ref()
+            .map(|layers| layers.contains(&layer))
+            .unwrap_or(true)
+    }
+    /// The blocked cells inside the grid
+    pub fn cells(&self, width: usize, height: usize, num_layers: usize) -> HashSet<LayerPoint> {
+        let (min, max) = match &self.shape {
+            KeepoutShape::Rect { min, max } => (*min, *max),
+            KeepoutShape::Polygon(vertices) => {
+                if vertices.is_empty() {
+                    return HashSet::new();
+                }
+                let min = Point {
+                    x: vertices.iter().map(|vertex| vertex.x).min().unwrap(),
+                    y: vertices.iter().map(|vertex| vertex.y).min().unwrap(),
+                };
+                let max = Point {
+                    x: vertices.iter().map(|vertex| vertex.x).max().unwrap(),
+                    y: vertices.iter().map(|vertex| vertex.y).max().unwrap(),
+                };
+                (min, max)
+            }
+        };
+        let mut cells = HashSet::new();
+        for x in min.x..=max.x.min(width.saturating_sub(1)) {
+            for y in min.y..=max.y.min(height.saturating_sub(1)) {
+                let point = Point { x, y };
+                if !self.contains(point) {
+                    continue;
+                }
+                for layer in (0..num_layers).filter(|layer| self.on_layer(*layer)) {
+                    cells.insert(LayerPoint { point, layer });
+                }
+            }
+        }
+        cells
+    }
+    /// Parses the arguments of a keepout directive:
+    /// "rect x0 y0 x1 y1" or "polygon x0 y0 x1 y1 x2 y2 ...", optionally followed by "layers 0,1"
+    pub fn parse(words: &[&str]) -> Result<Keepout, String> {
+        let (words, layers) = match words.iter().position(|word| *word == "layers") {
+            Some(i) => {
+                let layers = words[i + 1..]
+                    .iter()
+                    .flat_map(|word| word.split(','))
+                    .filter(|layer| !layer.is_empty())
+                    .map(|layer| {
+                        layer
+                            .parse::<usize>()
+                            .map_err(|e| format!("Invalid layer {:?}: {}", layer, e))
+                    })
+                    .collect::<Result<BTreeSet<usize>, String>>()?;
+                (&words[..i], Some(layers))
+            }
+            None => (words, None),
+        };
+        let (kind, coordinates) = words
+            .split_first()
+            .ok_or_else(|| "Missing keepout shape".to_string())?;
+        let coordinates = coordinates
+            .iter()
+            .map(|word| {
+                word.parse::<usize>()
+                    .map_err(|e| format!("Invalid coordinate {:?}: {}", word, e))
+            })
+            .collect::<Result<Vec<usize>, String>>()?;
+        if coordinates.len() % 2 != 0 {
+            return Err("Coordinates must come in x y pairs".to_string());
+        }
+        let points: Vec<Point> = coordinates
+            .chunks(2)
+            .map(|chunk| Point {
+                x: chunk[0],
+                y: chunk[1],
+            })
+            .collect();
+        let shape = match (*kind, points.as_slice()) {
+            ("rect", [a, b]) => KeepoutShape::Rect {
+                min: Point {
+                    x: a.x.min(b.x),
+                    y: a.y.min(b.y),
+                },
+                max: Point {
+                    x: a.x.max(b.x),
+                    y: a.y.max(b.y),
+                },
+            },
+            ("rect", _) => return Err("A rect keepout needs two corners".to_string()),
+            ("polygon", points) if points.len() >= 3 => KeepoutShape::Polygon(points.to_vec()),
+            ("polygon", _) => {
+                return Err("A polygon keepout needs at least three vertices".to_string())
+            }
+            (kind, _) => return Err(format!("Unknown keepout shape: {}", kind)),
+        };
+        Ok(Keepout { shape, layers })
+    }
+    /// The keepout directive that parse reads back, e.g. "keepout rect 2 5 4 5 layers 1"
+    pub fn to_directive(&self) -> String {
+        let points = match &self.shape {
+            KeepoutShape::Rect { min, max } => vec![*min, *max],
+            KeepoutShape::Polygon(vertices) => vertices.clone(),
+        };
+        let kind = match &self.shape {
+            KeepoutShape::Rect { .. } => "rect",
+            KeepoutShape::Polygon(_) => "polygon",
+        };
+        let mut directive = format!("keepout {}", kind);
+        for point in points {
+            directive.push_str(&format!(" {} {}", point.x, point.y));
+        }
+        if let Some(layers) = &self.layers {
+            let layers: Vec<String> = layers.iter().map(|layer| layer.to_string()).collect();
+            directive.push_str(&format!(" layers {}", layers.join(",")));
+        }
+        directive
+    }
+}
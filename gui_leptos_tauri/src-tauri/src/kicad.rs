@@ -0,0 +1,637 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+};
+
+use shared::interface_types::Color;
+
+use crate::{
+    grid::{Grid, Net, Point},
+    hyperparameters::{
+        DEFAULT_PROBA_SEED, KICAD_TRACK_WIDTH, KICAD_VIA_DRILL, KICAD_VIA_SIZE,
+        MAX_BOARD_GRID_CELLS,
+    },
+    keepout::{Keepout, KeepoutShape},
+    proba_grid::{NetID, ProbaGridProblem},
+    sexpr::SExpr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KicadPadType {
+    ThroughHole,
+    Smd,
+    Connect,       // an SMD pad without paste, e.g. an edge connector finger
+    NonPlatedHole, // a mounting hole, which has no copper to connect
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KicadPad {
+    pub footprint: String, // the reference of the footprint, e.g. "R1"
+    pub number: String,
+    pub pad_type: KicadPadType,
+    pub x: f64, // board coordinates in mm, y pointing down
+    pub y: f64,
+    pub layers: Vec<String>, // as written in the file, e.g. "*.Cu" or "B.Cu"
+    pub net: Option<usize>,  // the net code, None for unconnected pads
+}
+
+/// The bounding box of the Edge.Cuts graphics, in mm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoardOutline {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+/// The parts of a .kicad_pcb file the router needs
+#[derive(Debug, Clone, PartialEq)]
+pub struct KicadBoard {
+    pub nets: BTreeMap<usize, String>, // net code to net name, without the unconnected net 0
+    pub copper_layers: Vec<String>,    // from the top layer F.Cu to the bottom layer B.Cu
+    pub pads: Vec<KicadPad>,
+    pub outline: Option<BoardOutline>,
+}
+
+/// How board coordinates map to grid cells: cell (x, y) is centered on
+/// (origin_x + x * pitch, origin_y + y * pitch)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridMapping {
+    pub origin_x: f64,
+    pub origin_y: f64,
+    pub pitch: f64,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl GridMapping {
+    /// The cell nearest to the board coordinates, None outside the grid
+    pub fn to_cell(&self, x: f64, y: f64) -> Option<Point> {
+        let x = ((x - self.origin_x) / self.pitch).round();
+        let y = ((y - self.origin_y) / self.pitch).round();
+        if x < 0.0 || y < 0.0 || x >= self.width as f64 || y >= self.height as f64 {
+            return None;
+        }
+        Some(Point {
+            x: x as usize,
+            y: y as usize,
+        })
+    }
+    /// The nearest cell inside the grid, for shapes that may extend past the board
+    pub fn to_clamped_cell(&self, x: f64, y: f64) -> Point {
+        let clamp = |value: f64, origin: f64, size: usize| {
+            ((value - origin) / self.pitch)
+                .round()
+                .clamp(0.0, size.saturating_sub(1) as f64) as usize
+        };
+        Point {
+            x: clamp(x, self.origin_x, self.width),
+            y: clamp(y, self.origin_y, self.height),
+        }
+    }
+    pub fn to_board(&self, point: Point) -> (f64, f64) {
+        (
+            self.origin_x + point.x as f64 * self.pitch,
+            self.origin_y + point.y as f64 * self.pitch,
+        )
+    }
+}
+
+/// A pad snapped to a grid cell
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnappedPad {
+    pub point: Point,
+    pub net: Option<usize>,
+    pub layer: Option<usize>, // None for pads on every copper layer
+}
+
+/// A distinct color for every net code, spreading the hues by the golden ratio
+pub fn net_color(net_code: usize) -> Color {
+    let hue = (net_code as f64 * 0.618_033_988_75).fract() * 6.0;
+    let (saturation, value) = (0.8, 0.9);
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as usize {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    let channel = |c: f64| ((c + m) * 255.0).round() as u8;
+    Color {
+        r: channel(r),
+        g: channel(g),
+        b: channel(b),
+    }
+}
+
+/// The net of the naive grid for a net code, routed halfway between the pad color and white
+pub fn grid_net(net_code: usize) -> Net {
+    let pad_color = net_color(net_code);
+    Net::Color {
+        route_color: Color {
+            r: ((pad_color.r as u32 + 255) / 2) as u8,
+            g: ((pad_color.g as u32 + 255) / 2) as u8,
+            b: ((pad_color.b as u32 + 255) / 2) as u8,
+        },
+        pad_color,
+    }
+}
+
+// F.Cu first, then the inner layers in order, then B.Cu
+fn copper_layer_order(name: &str) -> Option<usize> {
+    match name {
+        "F.Cu" => Some(0),
+        "B.Cu" => Some(usize::MAX),
+        _ => name.strip_prefix("In")?.strip_suffix(".Cu")?.parse().ok(),
+    }
+}
+
+fn point_of(expr: &SExpr) -> Option<(f64, f64)> {
+    Some((expr.number(0)?, expr.number(1)?))
+}
+
+fn layer_of(expr: &SExpr) -> Option<&str> {
+    expr.child("layer")?.atom(0)
+}
+
+fn parse_net(expr: &SExpr, nets: &BTreeMap<usize, String>) -> Option<usize> {
+    let net = expr.child("net")?;
+    // older files give the net code, newer ones may only give the name
+    let code = match net.atom(0)?.parse::<usize>() {
+        Ok(code) => code,
+        Err(_) => {
+            *nets
+                .iter()
+                .find(|(_, name)| Some(name.as_str()) == net.atom(0))?
+                .0
+        }
+    };
+    if code == 0 {
+        None
+    } else {
+        Some(code)
+    }
+}
+
+fn parse_footprint(
+    footprint: &SExpr,
+    nets: &BTreeMap<usize, String>,
+) -> Result<Vec<KicadPad>, String> {
+    let at = footprint
+        .child("at")
+        .ok_or("Footprint without a position")?;
+    let (origin_x, origin_y) = point_of(at).ok_or("Invalid footprint position")?;
+    let rotation = at.number(2).unwrap_or(0.0).to_radians();
+    let reference = footprint
+        .children("property")
+        .find(|property| property.atom(0) == Some("Reference"))
+        .and_then(|property| property.atom(1))
+        .or_else(|| {
+            footprint
+                .children("fp_text")
+                .find(|text| text.atom(0) == Some("reference"))
+                .and_then(|text| text.atom(1))
+        })
+        .unwrap_or(footprint.atom(0).unwrap_or(""))
+        .to_string();
+    let mut pads = Vec::new();
+    for pad in footprint.children("pad") {
+        let number = pad.atom(0).unwrap_or("").to_string();
+        let pad_type = match pad.atom(1) {
+            Some("thru_hole") => KicadPadType::ThroughHole,
+            Some("smd") => KicadPadType::Smd,
+            Some("connect") => KicadPadType::Connect,
+            Some("np_thru_hole") => KicadPadType::NonPlatedHole,
+            other => {
+                return Err(format!(
+                    "Pad {} of {} has an unknown type {:?}",
+                    number, reference, other
+                ))
+            }
+        };
+        let (local_x, local_y) = pad
+            .child("at")
+            .and_then(point_of)
+            .ok_or_else(|| format!("Pad {} of {} has no position", number, reference))?;
+        // pads of flipped footprints are already mirrored in the file, only the rotation is left
+        let (sin, cos) = rotation.sin_cos();
+        pads.push(KicadPad {
+            footprint: reference.clone(),
+            number,
+            pad_type,
+            x: origin_x + local_x * cos + local_y * sin,
+            y: origin_y - local_x * sin + local_y * cos,
+            layers: pad
+                .child("layers")
+                .map(|layers| {
+                    layers
+                        .args()
+                        .iter()
+                        .filter_map(|layer| layer.as_atom().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            net: parse_net(pad, nets),
+        });
+    }
+    Ok(pads)
+}
+
+// the extent of an Edge.Cuts graphic item
+fn outline_points(item: &SExpr) -> Vec<(f64, f64)> {
+    match item.head() {
+        Some("gr_line") | Some("gr_rect") => ["start", "end"]
+            .iter()
+            .filter_map(|corner| point_of(item.child(corner)?))
+            .collect(),
+        // the arc is bounded by its end points and its middle point, which is enough for board outlines
+        Some("gr_arc") => ["start", "mid", "end"]
+            .iter()
+            .filter_map(|corner| point_of(item.child(corner)?))
+            .collect(),
+        Some("gr_circle") => {
+            let (Some((cx, cy)), Some((ex, ey))) = (
+                item.child("center").and_then(point_of),
+                item.child("end").and_then(point_of),
+            ) else {
+                return Vec::new();
+            };
+            let radius = (ex - cx).hypot(ey - cy);
+            vec![(cx - radius, cy - radius), (cx + radius, cy + radius)]
+        }
+        Some("gr_poly") => item
+            .child("pts")
+            .map(|pts| pts.children("xy").filter_map(point_of).collect())
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+impl KicadBoard {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::from_sexpr(&SExpr::parse(text)?)
+    }
+    pub fn from_sexpr(root: &SExpr) -> Result<Self, String> {
+        if root.head() != Some("kicad_pcb") {
+            return Err("Not a KiCad board, expected (kicad_pcb ...)".to_string());
+        }
+        let nets: BTreeMap<usize, String> = root
+            .children("net")
+            .filter_map(|net| Some((net.number(0)? as usize, net.atom(1)?.to_string())))
+            .filter(|(code, _)| *code != 0)
+            .collect();
+        let mut copper_layers: Vec<String> = root
+            .child("layers")
+            .map(|layers| {
+                layers
+                    .args()
+                    .iter()
+                    .filter_map(|layer| layer.atom(0))
+                    .filter(|name| copper_layer_order(name).is_some())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        copper_layers.sort_by_key(|name| copper_layer_order(name));
+        let mut pads = Vec::new();
+        for footprint in root.children("footprint").chain(root.children("module")) {
+            pads.extend(parse_footprint(footprint, &nets)?);
+        }
+        let outline_points: Vec<(f64, f64)> = root
+            .args()
+            .iter()
+            .filter(|item| layer_of(item) == Some("Edge.Cuts"))
+            .flat_map(outline_points)
+            .collect();
+        let outline = if outline_points.is_empty() {
+            None
+        } else {
+            Some(BoardOutline {
+                min_x: outline_points
+                    .iter()
+                    .map(|p| p.0)
+                    .fold(f64::INFINITY, f64::min),
+                min_y: outline_points
+                    .iter()
+                    .map(|p| p.1)
+                    .fold(f64::INFINITY, f64::min),
+                max_x: outline_points
+                    .iter()
+                    .map(|p| p.0)
+                    .fold(f64::NEG_INFINITY, f64::max),
+                max_y: outline_points
+                    .iter()
+                    .map(|p| p.1)
+                    .fold(f64::NEG_INFINITY, f64::max),
+            })
+        };
+        Ok(KicadBoard {
+            nets,
+            copper_layers,
+            pads,
+            outline,
+        })
+    }
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text)
+    }
+    pub fn num_layers(&self) -> usize {
+        self.copper_layers.len().max(1)
+    }
+    /// The grid covering the board outline, or the pads and a margin of one cell if there is no outline
+    pub fn grid_mapping(&self, pitch: f64) -> Result<GridMapping, String> {
+        if !(pitch > 0.0 && pitch.is_finite()) {
+            return Err(format!("Pitch must be a positive number, got {}", pitch));
+        }
+        let outline = match self.outline {
+            Some(outline) => outline,
+            None => {
+                if self.pads.is_empty() {
+                    return Err("The board has neither an outline nor pads".to_string());
+                }
+                BoardOutline {
+                    min_x: self.pads.iter().map(|p| p.x).fold(f64::INFINITY, f64::min) - pitch,
+                    min_y: self.pads.iter().map(|p| p.y).fold(f64::INFINITY, f64::min) - pitch,
+                    max_x: self
+                        .pads
+                        .iter()
+                        .map(|p| p.x)
+                        .fold(f64::NEG_INFINITY, f64::max)
+                        + pitch,
+                    max_y: self
+                        .pads
+                        .iter()
+                        .map(|p| p.y)
+                        .fold(f64::NEG_INFINITY, f64::max)
+                        + pitch,
+                }
+            }
+        };
+        // the small epsilon keeps an outline that is a whole number of cells from losing its last row
+        let cells = |extent: f64| (extent / pitch + 1e-9).floor() as usize + 1;
+        let width = cells(outline.max_x - outline.min_x);
+        let height = cells(outline.max_y - outline.min_y);
+        if width.saturating_mul(height) > MAX_BOARD_GRID_CELLS {
+            return Err(format!(
+                "A pitch of {} mm gives a {}x{} grid, which is more than {} cells",
+                pitch, width, height, MAX_BOARD_GRID_CELLS
+            ));
+        }
+        Ok(GridMapping {
+            origin_x: outline.min_x,
+            origin_y: outline.min_y,
+            pitch,
+            width,
+            height,
+        })
+    }
+    fn pad_layer(&self, pad: &KicadPad) -> Option<usize> {
+        if pad.pad_type == KicadPadType::ThroughHole || pad.pad_type == KicadPadType::NonPlatedHole
+        {
+            return None;
+        }
+        let copper: Vec<usize> = pad
+            .layers
+            .iter()
+            .filter_map(|l| self.copper_layers.iter().position(|c| c == l))
+            .collect();
+        match copper.as_slice() {
+            [layer] => Some(*layer),
+            _ => None, // "*.Cu", "F&B.Cu" or several layers
+        }
+    }
+    /// Every pad on its nearest cell. Pads of the same net on the same cell are merged,
+    /// pads of different nets on the same cell need a finer pitch
+    pub fn snap_pads(&self, mapping: &GridMapping) -> Result<Vec<SnappedPad>, String> {
+        let mut snapped: BTreeMap<Point, (SnappedPad, &KicadPad)> = BTreeMap::new();
+        for pad in &self.pads {
+            let point = mapping.to_cell(pad.x, pad.y).ok_or_else(|| {
+                format!(
+                    "Pad {} of {} at ({}, {}) is outside the board",
+                    pad.number, pad.footprint, pad.x, pad.y
+                )
+            })?;
+            let net = if pad.pad_type == KicadPadType::NonPlatedHole {
+                None
+            } else {
+                pad.net
+            };
+            let layer = self.pad_layer(pad);
+            match snapped.get_mut(&point) {
+                None => {
+                    snapped.insert(point, (SnappedPad { point, net, layer }, pad));
+                }
+                Some((other, other_pad)) => {
+                    if other.net != net {
+                        return Err(format!(
+                            "Pads {} of {} and {} of {} both snap to cell ({}, {}), use a finer pitch",
+                            other_pad.number,
+                            other_pad.footprint,
+                            pad.number,
+                            pad.footprint,
+                            point.x,
+                            point.y
+                        ));
+                    }
+                    if other.layer != layer {
+                        other.layer = None;
+                    }
+                }
+            }
+        }
+        Ok(snapped.into_values().map(|(pad, _)| pad).collect())
+    }
+    /// The net code of every routed net of a grid built from this board, found through its pads
+    pub fn grid_net_codes(
+        &self,
+        grid: &Grid,
+        mapping: &GridMapping,
+    ) -> Result<HashMap<Net, usize>, String> {
+        if (grid.width, grid.height) != (mapping.width, mapping.height) {
+            return Err(format!(
+                "The grid is {}x{} but the board was opened as {}x{}",
+                grid.width, grid.height, mapping.width, mapping.height
+            ));
+        }
+        if grid.layers > self.copper_layers.len() {
+            return Err(format!(
+                "The grid has {} layers but the board only {} copper layers",
+                grid.layers,
+                self.copper_layers.len()
+            ));
+        }
+        let pad_codes: HashMap<Point, usize> = self
+            .snap_pads(mapping)?
+            .into_iter()
+            .filter_map(|pad| Some((pad.point, pad.net?)))
+            .collect();
+        let routed_nets: BTreeSet<&Net> = grid
+            .traces
+            .keys()
+            .chain(grid.diagonal_traces.keys())
+            .chain(grid.vias.keys())
+            .collect();
+        routed_nets
+            .into_iter()
+            .map(|net| {
+                grid.pads
+                    .get(net)
+                    .into_iter()
+                    .flatten()
+                    .find_map(|point| pad_codes.get(point))
+                    .map(|code| (net.clone(), *code))
+                    .ok_or_else(|| format!("Net {:?} has no pad on the board", net))
+            })
+            .collect()
+    }
+    /// A naive grid with one layer per copper layer, the nets colored with net_color
+    pub fn to_grid(&self, pitch: f64) -> Result<Grid, String> {
+        let mapping = self.grid_mapping(pitch)?;
+        let mut grid = Grid::new_with_layers(mapping.width, mapping.height, self.num_layers());
+        for pad in self.snap_pads(&mapping)? {
+            match pad.net {
+                Some(code) => {
+                    let net = grid_net(code);
+                    match pad.layer {
+                        Some(layer) => grid.insert_smd_pad(net, pad.point, layer),
+                        None => grid.insert_pad(net, pad.point),
+                    }
+                }
+                // unconnected pads and holes are only obstacles
+                None => grid.keepouts.push(Keepout {
+                    shape: KeepoutShape::Rect {
+                        min: pad.point,
+                        max: pad.point,
+                    },
+                    layers: pad.layer.map(|layer| BTreeSet::from([layer])),
+                }),
+            }
+        }
+        Ok(grid)
+    }
+    /// A proba grid problem, which has a single layer, with the net codes as net ids
+    pub fn to_proba_grid_problem(&self, pitch: f64) -> Result<ProbaGridProblem, String> {
+        let mapping = self.grid_mapping(pitch)?;
+        let mut problem = ProbaGridProblem::new(mapping.width, mapping.height, DEFAULT_PROBA_SEED);
+        for pad in self.snap_pads(&mapping)? {
+            match pad.net {
+                Some(code) => {
+                    let color = net_color(code);
+                    problem.insert_pad(NetID(code), pad.point, color.clone(), color);
+                }
+                None => problem.keepouts.push(Keepout {
+                    shape: KeepoutShape::Rect {
+                        min: pad.point,
+                        max: pad.point,
+                    },
+                    layers: None,
+                }),
+            }
+        }
+        Ok(problem)
+    }
+}
+
+/// A board opened from a .kicad_pcb file, kept so that the routes can be written into a copy of it
+#[derive(Debug, Clone)]
+pub struct KicadDocument {
+    pub root: SExpr,
+    pub board: KicadBoard,
+    pub mapping: GridMapping,
+}
+
+impl KicadDocument {
+    pub fn parse(text: &str, pitch: f64) -> Result<Self, String> {
+        let root = SExpr::parse(text)?;
+        let board = KicadBoard::from_sexpr(&root)?;
+        let mapping = board.grid_mapping(pitch)?;
+        Ok(KicadDocument {
+            root,
+            board,
+            mapping,
+        })
+    }
+    pub fn open(path: &Path, pitch: f64) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text, pitch)
+    }
+    /// The traces, diagonal traces and vias of a routed grid as KiCad segments and vias.
+    /// Straight runs of cells become a single segment
+    pub fn route_items(&self, grid: &Grid) -> Result<Vec<SExpr>, String> {
+        let net_codes = self.board.grid_net_codes(grid, &self.mapping)?;
+        let at = |head: &str, point: Point| {
+            let (x, y) = self.mapping.to_board(point);
+            SExpr::list(head, vec![SExpr::from_f64(x), SExpr::from_f64(y)])
+        };
+        let layer_name = |layer: usize| SExpr::Str(self.board.copper_layers[layer].clone());
+        let mut items = Vec::new();
+        let mut nets: Vec<(&Net, &usize)> = net_codes.iter().collect();
+        nets.sort();
+        for (net, code) in nets {
+            let width = KICAD_TRACK_WIDTH
+                + grid.design_rules.trace_width(net).saturating_sub(1) as f64 * self.mapping.pitch;
+            for layer in 0..grid.layers {
+                for (start, end) in grid.trace_segments(net, layer) {
+                    items.push(SExpr::list(
+                        "segment",
+                        vec![
+                            at("start", start),
+                            at("end", end),
+                            SExpr::list("width", vec![SExpr::from_f64(width)]),
+                            SExpr::list("layer", vec![layer_name(layer)]),
+                            SExpr::list("net", vec![SExpr::Atom(code.to_string())]),
+                        ],
+                    ));
+                }
+            }
+            let mut vias: Vec<&Point> = grid.vias.get(net).into_iter().flatten().collect();
+            vias.sort();
+            for via in vias {
+                items.push(SExpr::list(
+                    "via",
+                    vec![
+                        at("at", *via),
+                        SExpr::list("size", vec![SExpr::from_f64(KICAD_VIA_SIZE)]),
+                        SExpr::list("drill", vec![SExpr::from_f64(KICAD_VIA_DRILL)]),
+                        SExpr::list(
+                            "layers",
+                            vec![
+                                layer_name(0),
+                                layer_name(self.board.copper_layers.len() - 1),
+                            ],
+                        ),
+                        SExpr::list("net", vec![SExpr::Atom(code.to_string())]),
+                    ],
+                ));
+            }
+        }
+        Ok(items)
+    }
+    /// The source board with its tracks and vias replaced by the routes of the grid
+    pub fn routed(&self, grid: &Grid) -> Result<SExpr, String> {
+        let items = self.route_items(grid)?;
+        let mut root: Vec<SExpr> = self
+            .root
+            .as_list()
+            .unwrap_or_default()
+            .iter()
+            .filter(|item| !matches!(item.head(), Some("segment") | Some("arc") | Some("via")))
+            .cloned()
+            .collect();
+        root.extend(items);
+        Ok(SExpr::List(root))
+    }
+    /// Writes the routed copy of the source board to path
+    pub fn save_routed(&self, grid: &Grid, path: &Path) -> Result<(), String> {
+        let routed = self.routed(grid)?;
+        std::fs::write(path, format!("{}\n", routed))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
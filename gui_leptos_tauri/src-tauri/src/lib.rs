@@ -1,13 +1,25 @@
 use std::{net::TcpStream, sync::Mutex};
 
+pub mod board_file;
 pub mod commands;
+pub mod design_rules;
 pub mod dijkstra;
+pub mod extract_routing;
 pub mod grid;
 pub mod hyperparameters;
+pub mod keepout;
+pub mod kicad;
 pub mod naive_route;
+pub mod pathfinder_route;
 pub mod prim_mst;
 pub mod proba_grid;
+pub mod proba_history;
+pub mod problem_file;
+pub mod router_config;
+pub mod sexpr;
 pub mod solve_proba_grid;
+pub mod specctra;
+pub mod steiner_tree;
 pub mod tauri_state;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
@@ -28,13 +40,34 @@ pub fn run() {
             naive_new_grid,
             naive_click_cell,
             naive_do_route,
+            naive_do_pathfinder_route,
+            naive_set_net_class,
+            naive_open_board,
+            naive_export_board,
+            naive_save_problem,
+            naive_save_solution,
+            naive_load_problem,
             proba_clear,
             proba_click_cell,
+            proba_set_net_class,
+            proba_open_board,
+            proba_export_board,
+            proba_save_problem,
+            proba_save_solution,
+            proba_load_problem,
             proba_init,
             proba_update_posterior,
             proba_next_net,
             proba_next_pair,
             proba_sample,
+            proba_solve,
+            proba_extract,
+            proba_inspect,
+            proba_list_snapshots,
+            proba_restore_snapshot,
+            get_router_config,
+            set_router_config,
+            load_router_config,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,30 +1,64 @@
 use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::BinaryHeap;
+use std::collections::HashMap;
 use std::collections::HashSet;
 
 use crate::dijkstra::*;
 use crate::grid::*;
+use crate::hyperparameters::MAX_RIP_UP_ITERATIONS;
 use crate::prim_mst::prim_mst;
 use crate::proba_grid::TracePath;
+use crate::steiner_tree::{rectilinear_steiner_tree, NetDecomposition};
 use ordered_float::OrderedFloat;
 
-pub fn naive_route(mut unrouted_grid: Grid) -> Result<Grid, String> {
-    unrouted_grid.traces.clear();
-    unrouted_grid.diagonal_traces.clear();
-    let prepare_dijkstra_model_unrouted = |net: Net, start: Point, end: Point| {
-        let other_pads = unrouted_grid.pads_except(&net);
-        DijkstraModel {
-            width: unrouted_grid.width,
-            height: unrouted_grid.height,
-            obstacles: other_pads,
-            diagonal_obstacles: HashSet::new(), // no diagonal obstacles in the unrouted grid
-            start,
-            end,
-        }
+/// A model where only the pads of other nets and the keepouts are in the way
+pub fn prepare_dijkstra_model_unrouted(
+    grid: &Grid,
+    net: &Net,
+    start: Point,
+    end: Point,
+) -> DijkstraModel {
+    let mut obstacles = grid.pad_obstacles(net);
+    obstacles.extend(grid.keepout_obstacles(net));
+    DijkstraModel {
+        width: grid.width,
+        height: grid.height,
+        num_layers: grid.layers,
+        obstacles,
+        diagonal_obstacles: HashSet::new(), // no diagonal obstacles in the unrouted grid
+        start,
+        start_layers: grid.terminal_layers(net, start),
+        goals: pad_goals(end, &grid.terminal_layers(net, end)),
+        costs: PathCosts::default(),
+        search_mode: SearchMode::AStar,
+        cell_costs: HashMap::new(),
+        diagonal_cell_costs: HashMap::new(),
+        allowed_layers: grid.design_rules.allowed_layers(net, grid.layers),
+        vias_allowed: grid.design_rules.vias_allowed(net),
+    }
+}
+
+/// Decomposes every net into pad pairs, weighted by their distance on the unrouted grid
+pub fn net_pad_pairs(
+    unrouted_grid: &Grid,
+    decomposition: NetDecomposition,
+) -> Vec<(OrderedFloat<f64>, Net, PointPair)> {
+    let weighted_pair = |net: &Net, point_pair: PointPair| {
+        let dijkstra_model = prepare_dijkstra_model_unrouted(
+            unrouted_grid,
+            net,
+            point_pair.start(),
+            point_pair.end(),
+        );
+        let distance = dijkstra_model
+            .run()
+            .map(|DijkstraResult { distance, .. }| distance)
+            .unwrap_or(f64::INFINITY);
+        (OrderedFloat(distance), net.clone(), point_pair)
     };
-    // prepare all the pairs of pads to route
-    let pad_pairs: Vec<(OrderedFloat<f64>, Net, PointPair)> = unrouted_grid
+    unrouted_grid
         .pads
         .iter()
         .flat_map(|(net, points)| {
@@ -33,95 +67,283 @@ pub fn naive_route(mut unrouted_grid: Grid) -> Result<Grid, String> {
             let points_vec: Vec<Point> = points.iter().cloned().collect();
             for i in 0..points_vec.len() {
                 for j in (i + 1)..points_vec.len() {
-                    let point1 = points_vec[i];
-                    let point2 = points_vec[j];
-                    let dijkstra_model =
-                        prepare_dijkstra_model_unrouted(net.clone(), point1, point2);
-                    let DijkstraResult { distance, .. } =
-                        dijkstra_model.run().unwrap_or(DijkstraResult {
-                            start: point1,
-                            end: point2,
-                            trace_directions: vec![],
-                            distance: f64::INFINITY,
-                            trace_path: TracePath {
-                                covered: BTreeSet::new(),
-                                diagonal_covered: BTreeSet::new(),
-                            },
-                        });
-                    pairs.push((
-                        OrderedFloat(distance),
-                        net.clone(),
-                        PointPair::new(point1, point2),
-                    ));
+                    pairs.push(PointPair::new(points_vec[i], points_vec[j]));
+                }
+            }
+            match decomposition {
+                NetDecomposition::AllPairs => pairs
+                    .into_iter()
+                    .map(|point_pair| weighted_pair(net, point_pair))
+                    .collect(),
+                NetDecomposition::MinimumSpanningTree => {
+                    let pairs = pairs
+                        .into_iter()
+                        .map(|point_pair| weighted_pair(net, point_pair))
+                        .collect();
+                    // prim's algorithm
+                    prim_mst(pairs)
+                }
+                NetDecomposition::SteinerTree => {
+                    let blocked: HashSet<Point> = unrouted_grid
+                        .pads_except(net)
+                        .into_iter()
+                        .chain(unrouted_grid.keepout_obstacles(net))
+                        .map(|layer_point| layer_point.point)
+                        .collect();
+                    rectilinear_steiner_tree(points, &blocked)
+                        .into_iter()
+                        .map(|point_pair| weighted_pair(net, point_pair))
+                        .collect::<Vec<_>>()
                 }
             }
-
-            // prim's algorithm
-            let pairs = prim_mst(pairs);
-            pairs
         })
+        .collect()
+}
+
+/// A trace longer than its net class allows counts as a routing failure
+fn check_max_length(
+    grid: &Grid,
+    net: &Net,
+    result: DijkstraResult,
+) -> Result<DijkstraResult, String> {
+    match grid.design_rules.max_length(net) {
+        Some(max_length) if result.distance > max_length => Err(format!(
+            "Trace of net {:?} is {:.1} cells long, its net class allows {:.1}",
+            net, result.distance, max_length
+        )),
+        _ => Ok(result),
+    }
+}
+
+/// The copper of a net's routed pairs that is connected to the pad at the point.
+/// Copper only connects on the same layer, or through a via or a pad of the net
+pub fn connected_copper(
+    grid: &Grid,
+    routed_pairs: &BTreeMap<(Net, PointPair), (OrderedFloat<f64>, TracePath)>,
+    net: &Net,
+    point: Point,
+) -> HashSet<LayerPoint> {
+    let mut remaining: Vec<&TracePath> = routed_pairs
+        .iter()
+        .filter(|((routed_net, _), _)| routed_net == net)
+        .map(|(_, (_, trace_path))| trace_path)
         .collect();
+    let net_pads = grid.pads.get(net);
+    let mut copper: HashSet<LayerPoint> = HashSet::new();
+    let mut frontier: HashSet<LayerPoint> = pad_goals(point, &grid.terminal_layers(net, point));
+    // the end cells of a pair are part of its covered cells, so connected paths share a cell
+    loop {
+        let (connected, rest): (Vec<&TracePath>, Vec<&TracePath>) =
+            remaining.into_iter().partition(|trace_path| {
+                trace_path
+                    .covered
+                    .iter()
+                    .any(|layer_point| frontier.contains(layer_point))
+            });
+        if connected.is_empty() {
+            return copper;
+        }
+        for trace_path in connected {
+            copper.extend(trace_path.covered.iter().cloned());
+            frontier.extend(trace_path.covered.iter().cloned());
+            // a via joins its point on every layer, a pad on every layer it has copper on
+            for via in &trace_path.vias {
+                frontier.extend((0..grid.layers).map(|layer| LayerPoint { point: *via, layer }));
+            }
+            for layer_point in &trace_path.covered {
+                if net_pads.is_some_and(|pads| pads.contains(&layer_point.point)) {
+                    frontier.extend(pad_goals(
+                        layer_point.point,
+                        &grid.pad_layers(layer_point.point),
+                    ));
+                }
+            }
+        }
+        remaining = rest;
+    }
+}
+
+pub fn naive_route(unrouted_grid: Grid) -> Result<Grid, String> {
+    naive_route_with_decomposition(unrouted_grid, NetDecomposition::MinimumSpanningTree)
+}
+
+pub fn naive_route_with_decomposition(
+    mut unrouted_grid: Grid,
+    decomposition: NetDecomposition,
+) -> Result<Grid, String> {
+    unrouted_grid.traces.clear();
+    unrouted_grid.diagonal_traces.clear();
+    unrouted_grid.vias.clear();
+    // prepare all the pairs of pads to route
+    let pad_pairs = net_pad_pairs(&unrouted_grid, decomposition);
     let mut priority_queue: BinaryHeap<_> = pad_pairs.into_iter().map(Reverse).collect();
 
-    fn prepare_dijkstra_model(grid: &Grid, net: &Net, start: Point, end: Point) -> DijkstraModel {
-        let other_pads = grid.pads_except(net);
-        let other_routes = grid.routes_except(net);
+    fn prepare_dijkstra_model(
+        grid: &Grid,
+        net: &Net,
+        start: Point,
+        goals: HashSet<LayerPoint>,
+    ) -> DijkstraModel {
+        let other_pads = grid.pad_obstacles(net);
+        let other_routes = grid.route_obstacles(net);
         let other_diagonal_routes = grid.diagonal_routes_except(net);
         let mut obstacles = other_pads;
         obstacles.extend(other_routes);
+        obstacles.extend(grid.keepout_obstacles(net));
         DijkstraModel {
             width: grid.width,
             height: grid.height,
+            num_layers: grid.layers,
             obstacles,
             diagonal_obstacles: other_diagonal_routes,
             start,
-            end,
+            start_layers: grid.terminal_layers(net, start),
+            goals,
+            costs: PathCosts::default(),
+            search_mode: SearchMode::AStar,
+            cell_costs: HashMap::new(),
+            diagonal_cell_costs: HashMap::new(),
+            allowed_layers: grid.design_rules.allowed_layers(net, grid.layers),
+            vias_allowed: grid.design_rules.vias_allowed(net),
+        }
+    }
+    fn add_trace_path(grid: &mut Grid, net: &Net, trace_path: &TracePath) {
+        let TracePath {
+            covered,
+            diagonal_covered,
+            vias,
+        } = trace_path;
+        for point in covered.iter() {
+            assert!(
+                point.point.x < grid.width && point.point.y < grid.height,
+                "Point out of bounds"
+            );
+        }
+        grid.traces
+            .entry(net.clone())
+            .or_default()
+            .extend(covered.iter().cloned());
+        if !diagonal_covered.is_empty() {
+            grid.diagonal_traces
+                .entry(net.clone())
+                .or_default()
+                .extend(diagonal_covered.iter().cloned());
+        }
+        if !vias.is_empty() {
+            grid.vias
+                .entry(net.clone())
+                .or_default()
+                .extend(vias.iter().cloned());
         }
     }
     let mut grid = unrouted_grid.clone();
-    while let Some(Reverse((OrderedFloat(_distance), net, point_pair))) = priority_queue.pop() {
+    // the route of every pair placed so far, so that blocking pairs can be ripped up later
+    let mut routed_pairs: BTreeMap<(Net, PointPair), (OrderedFloat<f64>, TracePath)> =
+        BTreeMap::new();
+    let mut rip_up_iterations = 0;
+    while let Some(Reverse((distance, net, point_pair))) = priority_queue.pop() {
         println!(
             "Routing net: {:?}, from {:?} to {:?}",
             net,
             point_pair.start(),
             point_pair.end()
         );
+        // the pair may end on any copper of its net that is already connected to the end pad
+        let end_copper = connected_copper(&grid, &routed_pairs, &net, point_pair.end());
+        let start_pad = pad_goals(
+            point_pair.start(),
+            &grid.terminal_layers(&net, point_pair.start()),
+        );
+        if !end_copper.is_disjoint(&start_pad) {
+            // both pads are already joined through other pairs of the net
+            routed_pairs.insert((net, point_pair), (distance, TracePath::default()));
+            continue;
+        }
+        let mut goals = pad_goals(
+            point_pair.end(),
+            &grid.terminal_layers(&net, point_pair.end()),
+        );
+        goals.extend(end_copper);
         // construct dijkstra model for the current pair of pads
-        let dijkstra_model =
-            prepare_dijkstra_model(&grid, &net, point_pair.start(), point_pair.end());
+        let dijkstra_model = prepare_dijkstra_model(&grid, &net, point_pair.start(), goals.clone());
         // run dijkstra's algorithm
-        let DijkstraResult {
-            trace_directions, ..
-        } = dijkstra_model.run()?;
-        // add the route to the grid
-        let mut current_point = point_pair.start();
-        grid.traces
-            .entry(net.clone())
-            .or_default()
-            .insert(current_point);
-        for direction in trace_directions {
-            let last_point = current_point;
-            current_point = Point {
-                x: (current_point.x as i32 + direction.x) as usize,
-                y: (current_point.y as i32 + direction.y) as usize,
-            };
-            assert!(
-                current_point.x < grid.width && current_point.y < grid.height,
-                "Point out of bounds"
-            );
-            grid.traces.get_mut(&net).unwrap().insert(current_point);
-            if direction.x != 0 && direction.y != 0 {
-                // if the direction is diagonal, we also add the diagonal trace
-                let diagonal_trace_point = Point {
-                    x: last_point.x.min(current_point.x),
-                    y: last_point.y.min(current_point.y),
-                };
-                grid.diagonal_traces
-                    .entry(net.clone())
-                    .or_default()
-                    .insert(diagonal_trace_point);
+        let trace_path = match dijkstra_model
+            .run()
+            .and_then(|result| check_max_length(&grid, &net, result))
+        {
+            Ok(DijkstraResult { trace_path, .. }) => trace_path,
+            Err(e) => {
+                if rip_up_iterations >= MAX_RIP_UP_ITERATIONS {
+                    return Err(format!(
+                        "{} (gave up after {} rip-up iterations)",
+                        e, rip_up_iterations
+                    ));
+                }
+                rip_up_iterations += 1;
+                // route the pair as if only the pads were in the way,
+                // and rip up every net whose traces cross this route.
+                // whole nets are ripped up, since their other pairs may end on the ripped up copper
+                let DijkstraResult {
+                    trace_path: unblocked_path,
+                    ..
+                } = prepare_dijkstra_model_unrouted(
+                    &unrouted_grid,
+                    &net,
+                    point_pair.start(),
+                    point_pair.end(),
+                )
+                .run()?;
+                let victim_nets: BTreeSet<Net> = routed_pairs
+                    .iter()
+                    .filter(|((victim_net, _), (_, victim_path))| {
+                        *victim_net != net
+                            && victim_path.collides_within(
+                                &unblocked_path,
+                                grid.design_rules.trace_inflation(&net, victim_net),
+                            )
+                    })
+                    .map(|((victim_net, _), _)| victim_net.clone())
+                    .collect();
+                let victims: Vec<(Net, PointPair)> = routed_pairs
+                    .keys()
+                    .filter(|(victim_net, _)| victim_nets.contains(victim_net))
+                    .cloned()
+                    .collect();
+                for (victim_net, victim_pair) in victims {
+                    println!(
+                        "Ripping up net: {:?}, from {:?} to {:?}",
+                        victim_net,
+                        victim_pair.start(),
+                        victim_pair.end()
+                    );
+                    let (victim_distance, _) = routed_pairs
+                        .remove(&(victim_net.clone(), victim_pair))
+                        .unwrap();
+                    // the ripped up pair is routed again after the current pair
+                    priority_queue.push(Reverse((victim_distance, victim_net, victim_pair)));
+                }
+                grid.traces.clear();
+                grid.diagonal_traces.clear();
+                grid.vias.clear();
+                for ((routed_net, _), (_, routed_path)) in routed_pairs.iter() {
+                    add_trace_path(&mut grid, routed_net, routed_path);
+                }
+                match prepare_dijkstra_model(&grid, &net, point_pair.start(), goals)
+                    .run()
+                    .and_then(|result| check_max_length(&grid, &net, result))
+                {
+                    Ok(DijkstraResult { trace_path, .. }) => trace_path,
+                    Err(_) => {
+                        // try the pair again, until the rip-up budget is spent
+                        priority_queue.push(Reverse((distance, net, point_pair)));
+                        continue;
+                    }
+                }
             }
-        }
+        };
+        // add the route to the grid
+        add_trace_path(&mut grid, &net, &trace_path);
+        routed_pairs.insert((net, point_pair), (distance, trace_path));
     }
     assert!(priority_queue.is_empty());
     Ok(grid)
@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::design_rules::inflate;
+use crate::dijkstra::*;
+use crate::grid::*;
+use crate::hyperparameters::{
+    MAX_PATHFINDER_ITERATIONS, PATHFINDER_HISTORY_INCREMENT, PATHFINDER_INITIAL_PRESENT_FACTOR,
+    PATHFINDER_PRESENT_FACTOR_GROWTH,
+};
+use crate::naive_route::net_pad_pairs;
+use crate::proba_grid::TracePath;
+use crate::steiner_tree::NetDecomposition;
+
+// the cells and diagonal squares a routed net occupies
+struct NetUsage {
+    cells: HashSet<LayerPoint>,
+    diagonals: HashSet<LayerPoint>,
+}
+
+fn net_usage(paths: &[TracePath], num_layers: usize) -> NetUsage {
+    let mut cells = HashSet::new();
+    let mut diagonals = HashSet::new();
+    for path in paths {
+        cells.extend(path.covered.iter().cloned());
+        diagonals.extend(path.diagonal_covered.iter().cloned());
+        // a via occupies its point on every layer
+        for via in &path.vias {
+            cells.extend((0..num_layers).map(|layer| LayerPoint { point: *via, layer }));
+        }
+    }
+    NetUsage { cells, diagonals }
+}
+
+// the number of other nets using each cell and each diagonal square,
+// where the cells of other nets are inflated by the trace widths and clearances
+fn occupancy_against(
+    grid: &Grid,
+    net: &Net,
+    usages: &BTreeMap<Net, NetUsage>,
+) -> (HashMap<LayerPoint, usize>, HashMap<LayerPoint, usize>) {
+    let mut cells: HashMap<LayerPoint, usize> = HashMap::new();
+    let mut diagonals: HashMap<LayerPoint, usize> = HashMap::new();
+    for (other, usage) in usages.iter().filter(|(other, _)| *other != net) {
+        let inflation = grid.design_rules.trace_inflation(net, other);
+        for cell in inflate(usage.cells.iter().cloned(), inflation) {
+            *cells.entry(cell).or_default() += 1;
+        }
+        for diagonal in &usage.diagonals {
+            *diagonals.entry(*diagonal).or_default() += 1;
+        }
+    }
+    (cells, diagonals)
+}
+
+// PathFinder cost of a shared resource: (1 + history) * (1 + present_factor * occupancy) - 1,
+// so that a free resource without history costs nothing extra
+fn congestion_costs(
+    occupancy: &HashMap<LayerPoint, usize>,
+    history: &HashMap<LayerPoint, f64>,
+    present_factor: f64,
+) -> HashMap<LayerPoint, f64> {
+    occupancy
+        .keys()
+        .chain(history.keys())
+        .map(|point| {
+            let occupancy = occupancy.get(point).cloned().unwrap_or(0) as f64;
+            let history = history.get(point).cloned().unwrap_or(0.0);
+            let cost = (1.0 + history) * (1.0 + present_factor * occupancy) - 1.0;
+            (*point, cost)
+        })
+        .collect()
+}
+
+/// Negotiated congestion routing: every net is routed with cells of other nets allowed but penalised,
+/// and the penalty of overused cells grows each iteration until no cell is shared
+pub fn pathfinder_route(mut unrouted_grid: Grid) -> Result<Grid, String> {
+    unrouted_grid.traces.clear();
+    unrouted_grid.diagonal_traces.clear();
+    unrouted_grid.vias.clear();
+    let mut net_to_pairs: BTreeMap<Net, Vec<PointPair>> = BTreeMap::new();
+    for (_, net, point_pair) in net_pad_pairs(&unrouted_grid, NetDecomposition::MinimumSpanningTree)
+    {
+        net_to_pairs.entry(net).or_default().push(point_pair);
+    }
+    let mut routes: BTreeMap<Net, Vec<TracePath>> = BTreeMap::new();
+    let mut usages: BTreeMap<Net, NetUsage> = BTreeMap::new();
+    let mut history: HashMap<LayerPoint, f64> = HashMap::new();
+    let mut diagonal_history: HashMap<LayerPoint, f64> = HashMap::new();
+    let mut present_factor = PATHFINDER_INITIAL_PRESENT_FACTOR;
+    for iteration in 1..=MAX_PATHFINDER_ITERATIONS {
+        for (net, point_pairs) in net_to_pairs.iter() {
+            // rip up the net and route it again against the congestion of all other nets
+            usages.remove(net);
+            let (cell_occupancy, diagonal_occupancy) =
+                occupancy_against(&unrouted_grid, net, &usages);
+            let cell_costs = congestion_costs(&cell_occupancy, &history, present_factor);
+            let diagonal_cell_costs =
+                congestion_costs(&diagonal_occupancy, &diagonal_history, present_factor);
+            // pads and keepouts can never be shared
+            let mut obstacles = unrouted_grid.pad_obstacles(net);
+            obstacles.extend(unrouted_grid.keepout_obstacles(net));
+            let mut net_routes = Vec::new();
+            for point_pair in point_pairs {
+                let dijkstra_model = DijkstraModel {
+                    width: unrouted_grid.width,
+                    height: unrouted_grid.height,
+                    num_layers: unrouted_grid.layers,
+                    obstacles: obstacles.clone(),
+                    diagonal_obstacles: HashSet::new(),
+                    start: point_pair.start(),
+                    start_layers: unrouted_grid.terminal_layers(net, point_pair.start()),
+                    goals: pad_goals(
+                        point_pair.end(),
+                        &unrouted_grid.terminal_layers(net, point_pair.end()),
+                    ),
+                    costs: PathCosts::default(),
+                    search_mode: SearchMode::AStar,
+                    cell_costs: cell_costs.clone(),
+                    diagonal_cell_costs: diagonal_cell_costs.clone(),
+                    allowed_layers: unrouted_grid
+                        .design_rules
+                        .allowed_layers(net, unrouted_grid.layers),
+                    vias_allowed: unrouted_grid.design_rules.vias_allowed(net),
+                };
+                let DijkstraResult { trace_path, .. } = dijkstra_model.run()?;
+                net_routes.push(trace_path);
+            }
+            usages.insert(net.clone(), net_usage(&net_routes, unrouted_grid.layers));
+            routes.insert(net.clone(), net_routes);
+        }
+        // a resource is overused when a net uses it while other nets are too close,
+        // and its history grows with the number of those other nets
+        let mut overused_cells: HashMap<LayerPoint, usize> = HashMap::new();
+        let mut overused_diagonals: HashMap<LayerPoint, usize> = HashMap::new();
+        for (net, usage) in usages.iter() {
+            let (cell_occupancy, diagonal_occupancy) =
+                occupancy_against(&unrouted_grid, net, &usages);
+            let mark = |overused: &mut HashMap<LayerPoint, usize>,
+                        used: &HashSet<LayerPoint>,
+                        occupancy: &HashMap<LayerPoint, usize>| {
+                for resource in used {
+                    if let Some(count) = occupancy.get(resource) {
+                        let entry = overused.entry(*resource).or_default();
+                        *entry = (*entry).max(*count);
+                    }
+                }
+            };
+            mark(&mut overused_cells, &usage.cells, &cell_occupancy);
+            mark(
+                &mut overused_diagonals,
+                &usage.diagonals,
+                &diagonal_occupancy,
+            );
+        }
+        println!(
+            "PathFinder iteration {}: {} overused cells, {} overused diagonals",
+            iteration,
+            overused_cells.len(),
+            overused_diagonals.len()
+        );
+        if overused_cells.is_empty() && overused_diagonals.is_empty() {
+            let mut grid = unrouted_grid;
+            for (net, paths) in routes {
+                for path in paths {
+                    grid.traces
+                        .entry(net.clone())
+                        .or_default()
+                        .extend(path.covered);
+                    if !path.diagonal_covered.is_empty() {
+                        grid.diagonal_traces
+                            .entry(net.clone())
+                            .or_default()
+                            .extend(path.diagonal_covered);
+                    }
+                    if !path.vias.is_empty() {
+                        grid.vias.entry(net.clone()).or_default().extend(path.vias);
+                    }
+                }
+            }
+            return Ok(grid);
+        }
+        for (cell, count) in overused_cells {
+            *history.entry(cell).or_default() += PATHFINDER_HISTORY_INCREMENT * count as f64;
+        }
+        for (diagonal, count) in overused_diagonals {
+            *diagonal_history.entry(diagonal).or_default() +=
+                PATHFINDER_HISTORY_INCREMENT * count as f64;
+        }
+        present_factor *= PATHFINDER_PRESENT_FACTOR_GROWTH;
+    }
+    Err(format!(
+        "PathFinder did not resolve congestion after {} iterations",
+        MAX_PATHFINDER_ITERATIONS
+    ))
+}
@@ -1,19 +1,20 @@
 use core::num;
 use std::{
-    cell::RefCell,
-    collections::{BTreeSet, HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
     num::NonZeroUsize,
     vec,
 };
 
-use shared::interface_types::{Color, ColorGrid};
+use rand_chacha::ChaCha8Rng;
+use shared::interface_types::{Color, ColorGrid, NetHeatmap, ProbaInspection, TraceInspection};
 
 use crate::{
-    grid::Point,
-    hyperparameters::{
-        HALF_PROBABILITY_RAW_SCORE, ITERATION_TO_PRIOR_PROBABILITY, LENGTH_PENALTY_RATE,
-        TURN_PENALTY_RATE,
-    },
+    design_rules::{inflate, DesignRules},
+    grid::{LayerPoint, Point},
+    hyperparameters::FIRST_ITERATION_PRIOR_PROBABILITY,
+    keepout::{Keepout, KEEPOUT_COLOR},
+    proba_history::ProbaHistory,
+    router_config::RouterConfig,
 };
 
 #[derive(Debug, Clone)]
@@ -46,17 +47,55 @@ pub struct Direction {
     pub y: i32,
 }
 
-#[derive(Debug, Clone, PartialEq, Hash, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Default, PartialEq, Hash, Eq, PartialOrd, Ord)]
 pub struct TracePath {
-    pub covered: BTreeSet<Point>,          // The points covered by the trace
-    pub diagonal_covered: BTreeSet<Point>, // points in the diagonal
+    pub covered: BTreeSet<LayerPoint>, // The points covered by the trace
+    pub diagonal_covered: BTreeSet<LayerPoint>, // points in the diagonal
+    pub vias: BTreeSet<Point>,         // points where the trace changes layer
 }
 
 impl TracePath {
     pub fn collides_with(&self, other: &TracePath) -> bool {
         // Check if the covered points intersect
-        !self.covered.is_disjoint(&other.covered)
+        if !self.covered.is_disjoint(&other.covered)
             || !self.diagonal_covered.is_disjoint(&other.diagonal_covered)
+        {
+            return true;
+        }
+        // a via occupies its point on every layer
+        let via_collides = |vias: &BTreeSet<Point>, covered: &BTreeSet<LayerPoint>| {
+            covered.iter().any(|point| vias.contains(&point.point))
+        };
+        via_collides(&self.vias, &other.covered) || via_collides(&other.vias, &self.covered)
+    }
+    /// Whether the traces come closer than `distance` cells, e.g. the sum of their radii and the clearance
+    pub fn collides_within(&self, other: &TracePath, distance: usize) -> bool {
+        if distance == 0 {
+            return self.collides_with(other);
+        }
+        // diagonals cannot cross without their end cells coming within one cell of each other
+        let other_inflated = inflate(other.covered.iter().cloned(), distance);
+        let other_inflated_points: HashSet<Point> = other_inflated
+            .iter()
+            .map(|layer_point| layer_point.point)
+            .collect();
+        let other_via_points: HashSet<Point> = inflate(
+            other.vias.iter().map(|point| LayerPoint {
+                point: *point,
+                layer: 0,
+            }),
+            distance,
+        )
+        .into_iter()
+        .map(|layer_point| layer_point.point)
+        .collect();
+        // a via occupies its point on every layer
+        self.covered.iter().any(|layer_point| {
+            other_inflated.contains(layer_point) || other_via_points.contains(&layer_point.point)
+        }) || self
+            .vias
+            .iter()
+            .any(|point| other_inflated_points.contains(point))
     }
 }
 
@@ -83,33 +122,48 @@ pub struct TraceInfo {
     pub trace_directions: Vec<Direction>, // The directions of the trace
     pub trace_length: f64,                // The length of the trace
     pub iteration: NonZeroUsize, // The iteration that the trace belongs to, starting from 1
-    // probability information:
-    pub prior_probability_cache: RefCell<Option<f64>>, // Prior probability of the trace, between 0 and 1
-    pub score_cache: RefCell<Option<f64>>,             // Score of the trace, between 0 and 1
-    // pub old_posterior_unnormalized: RefCell<Option<f64>>, // to be accessed in the next iteration
-    pub posterior_normalized: RefCell<Option<f64>>, // to be accessed in the next iteration
-    pub temp_posterior: RefCell<Option<f64>>,       // serve as a buffer for simultaneous updates
+    // probability information, plain data so that the grid can be shared between threads:
+    pub posterior_normalized: Option<f64>, // to be accessed in the next iteration
 }
 
-impl TraceInfo {
-    fn calculate_score(&self) -> f64 {
-        // calculate turns
-        let mut turns = 0;
-        let mut last_direction = self
-            .trace_directions
-            .first()
-            .cloned()
-            .unwrap_or(Direction { x: 0, y: 0 });
-        if self.trace_directions.len() > 0 {
-            for direction in self.trace_directions.iter().skip(1) {
-                if direction.x != last_direction.x || direction.y != last_direction.y {
-                    turns += 1;
-                }
-                last_direction = direction.clone();
+/// How the prior probability of a pad pair is shared between the iterations that found its traces
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PriorSchedule {
+    /// iteration n gets ratio * (1 - ratio)^(n - 1), leaving (1 - ratio)^n for the traces not found yet
+    Geometric { ratio: f64 },
+}
+
+impl Default for PriorSchedule {
+    fn default() -> Self {
+        PriorSchedule::Geometric {
+            ratio: FIRST_ITERATION_PRIOR_PROBABILITY,
+        }
+    }
+}
+
+impl PriorSchedule {
+    pub fn prior_probability(&self, iteration: NonZeroUsize) -> f64 {
+        match self {
+            PriorSchedule::Geometric { ratio } => {
+                ratio * (1.0 - ratio).powi(iteration.get() as i32 - 1)
             }
         }
-        let score_raw = self.trace_length * LENGTH_PENALTY_RATE + turns as f64 * TURN_PENALTY_RATE;
-        let k = f64::ln(2.0) / HALF_PROBABILITY_RAW_SCORE;
+    }
+    /// The prior probability of the first `num_iterations` iterations together, always below 1
+    pub fn total_prior_probability(&self, num_iterations: usize) -> f64 {
+        match self {
+            PriorSchedule::Geometric { ratio } => 1.0 - (1.0 - ratio).powi(num_iterations as i32),
+        }
+    }
+}
+
+impl TraceInfo {
+    fn calculate_score(&self, config: &RouterConfig) -> f64 {
+        // the raw score is the same objective the router minimises
+        let score_raw = config
+            .path_costs()
+            .path_cost(&self.trace_directions, self.trace_path.vias.len());
+        let k = f64::ln(2.0) / config.half_probability_raw_score;
         let score = f64::exp(-k * score_raw);
         assert!(
             score >= 0.0 && score <= 1.0,
@@ -118,32 +172,29 @@ impl TraceInfo {
         );
         score
     }
-    pub fn get_score(&self) -> f64 {
-        // let mut score_cache = self.score_cache.borrow_mut();
-        // *score_cache.get_or_insert_with(||{
-        //     self.calculate_score()
-        // })
+    pub fn get_score(&self, config: &RouterConfig) -> f64 {
         // we do not use cache until there is performance issue
-        self.calculate_score()
+        self.calculate_score(config)
     }
     fn calculate_normalized_prior_probability(
         &self,
+        prior_schedule: &PriorSchedule,
         num_traces_in_the_same_iteration: usize,
     ) -> f64 {
-        let sum_probability = ITERATION_TO_PRIOR_PROBABILITY
-            .get(&self.iteration)
-            .cloned()
-            .unwrap_or_else(|| panic!("No prior probability for iteration {:?}", self.iteration));
+        let sum_probability = prior_schedule.prior_probability(self.iteration);
         sum_probability / (num_traces_in_the_same_iteration as f64)
     }
     /// this prior probability is not normalized
-    pub fn get_normalized_prior_probability(&self, num_traces_in_the_same_iteration: usize) -> f64 {
-        // let mut prior_probability_cache = self.prior_probability_cache.borrow_mut();
-        // *prior_probability_cache.get_or_insert_with(||{
-        //     self.calculate_prior_probability()
-        // })
+    pub fn get_normalized_prior_probability(
+        &self,
+        prior_schedule: &PriorSchedule,
+        num_traces_in_the_same_iteration: usize,
+    ) -> f64 {
         // we do not use cache until there is performance issue
-        self.calculate_normalized_prior_probability(num_traces_in_the_same_iteration)
+        self.calculate_normalized_prior_probability(
+            prior_schedule,
+            num_traces_in_the_same_iteration,
+        )
     }
 
     // pub fn calculate_fallback_posterior_unnormalized(&self, num_traces_in_the_same_iteration: usize)->f64{
@@ -161,13 +212,13 @@ impl TraceInfo {
     // }
     pub fn get_posterior_normalized_with_fallback(
         &self,
+        prior_schedule: &PriorSchedule,
         num_traces_in_the_same_iteration: usize,
     ) -> f64 {
-        let posterior_normalized = self.posterior_normalized.borrow();
-        if let Some(old_posterior) = posterior_normalized.as_ref() {
-            *old_posterior
+        if let Some(old_posterior) = self.posterior_normalized {
+            old_posterior
         } else {
-            self.get_normalized_prior_probability(num_traces_in_the_same_iteration)
+            self.get_normalized_prior_probability(prior_schedule, num_traces_in_the_same_iteration)
         }
     }
     // call stack: want to sample traces that block the way -> call get_posterior_normalized for other traces
@@ -183,9 +234,24 @@ pub struct ProbaGridProblem {
     pub height: usize,
     pub nets: HashMap<NetID, NetInfo>,
     pub net_to_pads: HashMap<NetID, HashSet<Point>>, // NetID to list of pad coordinates
+    pub design_rules: DesignRules<NetID>,
+    pub keepouts: Vec<Keepout>,
+    pub seed: u64, // seeds the random number generator of the proba grid, so that a run can be reproduced
 }
 
 impl ProbaGridProblem {
+    /// An empty board without nets, design rules or keepouts
+    pub fn new(width: usize, height: usize, seed: u64) -> ProbaGridProblem {
+        ProbaGridProblem {
+            width,
+            height,
+            nets: HashMap::new(),
+            net_to_pads: HashMap::new(),
+            design_rules: DesignRules::default(),
+            keepouts: Vec::new(),
+            seed,
+        }
+    }
     pub fn to_color_grid(&self) -> ColorGrid {
         let mut grid = vec![
             vec![
@@ -198,6 +264,11 @@ impl ProbaGridProblem {
             ];
             self.height
         ];
+        for keepout in &self.keepouts {
+            for LayerPoint { point, .. } in keepout.cells(self.width, self.height, 1) {
+                grid[point.y][point.x] = KEEPOUT_COLOR;
+            }
+        }
         for (net_id, net_info) in &self.nets {
             if let Some(pad_color) = &net_info.pad_color {
                 if let Some(pads) = self.net_to_pads.get(net_id) {
@@ -246,11 +317,16 @@ impl ProbaGridProblem {
 #[derive(Copy, Debug, Clone, PartialEq, Hash, Eq, PartialOrd, Ord)]
 pub struct IterationNum(pub NonZeroUsize);
 
+#[derive(Clone)]
 pub struct ProbaGrid {
     pub width: usize,
     pub height: usize,
     pub nets: HashMap<NetID, NetInfo>,
     pub net_to_pads: HashMap<NetID, HashSet<Point>>, // NetID to list of pad coordinates
+    pub design_rules: DesignRules<NetID>,
+    pub keepouts: Vec<Keepout>,
+    pub prior_schedule: PriorSchedule,
+    pub rng: ChaCha8Rng, // the only source of randomness while sampling, seeded from ProbaGridProblem::seed
     // output
     pub net_to_pad_pairs: HashMap<NetID, HashSet<PadPairID>>, // NetID to PadPairToRouteID to PadPairToRoute
     pub pad_pairs: HashMap<PadPairID, PadPair>,               // PadPairToRouteID to PadPairToRoute
@@ -258,9 +334,10 @@ pub struct ProbaGrid {
     pub pad_pair_to_traces: HashMap<PadPairID, HashMap<IterationNum, HashSet<TraceID>>>, // PadPairID to TraceID
     pub traces: HashMap<TraceID, TraceInfo>, // TraceID to Trace
     pub trace_collision_adjacency: HashMap<TraceID, HashSet<TraceID>>,
+    pub cell_to_traces: HashMap<Point, HashSet<TraceID>>, // the traces covering a cell or with a via on it, on any layer
 
     pub next_iteration: NonZeroUsize, // The next iteration to be processed, starting from 1
-    pub trace_id_generator: Box<dyn Iterator<Item = TraceID> + Send + 'static>, // A generator for TraceID, starting from 0
+    pub next_trace_id: TraceID,       // The ID of the next trace to be sampled, starting from 0
 }
 
 impl ProbaGrid {
@@ -294,6 +371,11 @@ impl ProbaGrid {
             ];
             self.height
         ];
+        for keepout in &self.keepouts {
+            for LayerPoint { point, .. } in keepout.cells(self.width, self.height, 1) {
+                grid[point.y][point.x] = KEEPOUT_COLOR;
+            }
+        }
         for (net_id, net_info) in &self.nets {
             if let Some(pad_color) = &net_info.pad_color {
                 if let Some(pads) = self.net_to_pads.get(net_id) {
@@ -307,14 +389,20 @@ impl ProbaGrid {
                 panic!("NetInfo for NetID {:?} does not have a pad_color", net_id);
             }
         }
-        // iterate through the traces and set the route color
-        for (net_id, pad_pairs) in &self.net_to_pad_pairs {
+        // iterate through the traces and set the route color,
+        // sorted so that overlapping traces are blended in the same order on every run
+        let mut net_to_pad_pairs: Vec<(&NetID, &HashSet<PadPairID>)> =
+            self.net_to_pad_pairs.iter().collect();
+        net_to_pad_pairs.sort_by_key(|(net_id, _)| **net_id);
+        for (net_id, pad_pairs) in net_to_pad_pairs {
             let net_info = self.nets.get(net_id).unwrap();
             let route_color = net_info.route_color.clone().unwrap();
+            let mut pad_pairs: Vec<&PadPairID> = pad_pairs.iter().collect();
+            pad_pairs.sort();
             for pad_pair_id in pad_pairs {
                 // trace_ids is a set of TraceID that combines the traces in each iteration
                 // use flat map
-                let trace_ids = self
+                let mut trace_ids = self
                     .pad_pair_to_traces
                     .get(pad_pair_id)
                     .unwrap()
@@ -322,13 +410,15 @@ impl ProbaGrid {
                     .flat_map(|(_, trace_ids)| trace_ids.iter())
                     .cloned()
                     .collect::<Vec<_>>();
+                trace_ids.sort();
                 for trace_id in trace_ids {
                     let trace = self.traces.get(&trace_id).unwrap();
-                    for point in &trace.trace_path.covered {
+                    for LayerPoint { point, .. } in &trace.trace_path.covered {
                         let original_color = grid[point.y][point.x].clone();
                         let num_traces_in_the_same_iteration =
                             self.get_num_traces_in_the_same_iteration(trace_id);
                         let opacity: f64 = trace.get_posterior_normalized_with_fallback(
+                            &self.prior_schedule,
                             num_traces_in_the_same_iteration,
                         );
                         let new_color = Color {
@@ -349,18 +439,173 @@ impl ProbaGrid {
         }
         ColorGrid { grid }
     }
+    /// The probability that a net covers each cell. The traces of a pad pair exclude each other,
+    /// while its pad pairs are treated as independent
+    pub fn net_occupancy(&self, net_id: NetID) -> Vec<Vec<f64>> {
+        let mut none_probability = vec![vec![1.0; self.width]; self.height];
+        let mut pad_pair_ids: Vec<&PadPairID> = self
+            .net_to_pad_pairs
+            .get(&net_id)
+            .map(|pad_pair_ids| pad_pair_ids.iter().collect())
+            .unwrap_or_default();
+        pad_pair_ids.sort();
+        for pad_pair_id in pad_pair_ids {
+            let mut trace_ids: Vec<TraceID> = self.pad_pair_to_traces[pad_pair_id]
+                .values()
+                .flat_map(|trace_ids| trace_ids.iter().cloned())
+                .collect();
+            trace_ids.sort();
+            let mut pad_pair_probability: HashMap<Point, f64> = HashMap::new();
+            for trace_id in trace_ids {
+                let trace = &self.traces[&trace_id];
+                let probability = trace.get_posterior_normalized_with_fallback(
+                    &self.prior_schedule,
+                    self.get_num_traces_in_the_same_iteration(trace_id),
+                );
+                let points: BTreeSet<Point> = trace
+                    .trace_path
+                    .covered
+                    .iter()
+                    .map(|layer_point| layer_point.point)
+                    .collect();
+                for point in points {
+                    *pad_pair_probability.entry(point).or_default() += probability;
+                }
+            }
+            for (point, probability) in pad_pair_probability {
+                none_probability[point.y][point.x] *= 1.0 - probability.min(1.0);
+            }
+        }
+        let mut occupancy: Vec<Vec<f64>> = none_probability
+            .into_iter()
+            .map(|row| row.into_iter().map(|p| 1.0 - p).collect())
+            .collect();
+        for pad in self.net_to_pads.get(&net_id).into_iter().flatten() {
+            occupancy[pad.y][pad.x] = 1.0;
+        }
+        occupancy
+    }
+    pub fn inspect_trace(
+        &self,
+        trace_id: TraceID,
+        config: &RouterConfig,
+    ) -> Result<TraceInspection, String> {
+        let trace = self
+            .traces
+            .get(&trace_id)
+            .ok_or_else(|| format!("Trace ID {:?} not found in traces", trace_id))?;
+        let mut colliding_traces: Vec<usize> = self
+            .trace_collision_adjacency
+            .get(&trace_id)
+            .into_iter()
+            .flatten()
+            .map(|trace_id| trace_id.0)
+            .collect();
+        colliding_traces.sort();
+        let cells: BTreeSet<(usize, usize)> = trace
+            .trace_path
+            .covered
+            .iter()
+            .map(|layer_point| (layer_point.point.x, layer_point.point.y))
+            .collect();
+        Ok(TraceInspection {
+            trace_id: trace_id.0,
+            net_id: trace.net_id.0,
+            pad_pair_id: trace.pad_pair_id.0,
+            iteration: trace.iteration.get(),
+            score: trace.get_score(config),
+            prior: trace.get_normalized_prior_probability(
+                &self.prior_schedule,
+                self.get_num_traces_in_the_same_iteration(trace_id),
+            ),
+            posterior: trace.posterior_normalized,
+            colliding_traces,
+            cells: cells.into_iter().collect(),
+        })
+    }
+    /// The heatmap of every net and the details of every trace, without the traces of an unfinished sampling pass
+    pub fn inspect(&self, config: &RouterConfig) -> Result<ProbaInspection, String> {
+        let mut net_ids: Vec<NetID> = self.nets.keys().cloned().collect();
+        net_ids.sort();
+        let heatmaps = net_ids
+            .into_iter()
+            .map(|net_id| NetHeatmap {
+                net_id: net_id.0,
+                route_color: self.nets[&net_id].route_color.clone().unwrap(),
+                occupancy: self.net_occupancy(net_id),
+            })
+            .collect();
+        let mut trace_ids: Vec<TraceID> = self.traces.keys().cloned().collect();
+        trace_ids.sort();
+        let traces = trace_ids
+            .into_iter()
+            .map(|trace_id| self.inspect_trace(trace_id, config))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(ProbaInspection { heatmaps, traces })
+    }
+    /// Also draws the traces of an unfinished sampling pass,
+    /// each pad pair sharing the prior probability of the iteration among its new traces
+    pub fn to_color_grid_with_pending(&self, pending_traces: &[TraceInfo]) -> ColorGrid {
+        let mut color_grid = self.to_color_grid();
+        let mut num_pending_traces: HashMap<PadPairID, usize> = HashMap::new();
+        for trace in pending_traces {
+            *num_pending_traces.entry(trace.pad_pair_id).or_default() += 1;
+        }
+        for trace in pending_traces {
+            let route_color = self.nets[&trace.net_id].route_color.clone().unwrap();
+            let opacity = trace.get_normalized_prior_probability(
+                &self.prior_schedule,
+                num_pending_traces[&trace.pad_pair_id],
+            );
+            for LayerPoint { point, .. } in &trace.trace_path.covered {
+                let original_color = &color_grid.grid[point.y][point.x];
+                let new_color = Color {
+                    r: (route_color.r as f64 * opacity + original_color.r as f64 * (1.0 - opacity))
+                        as u8,
+                    g: (route_color.g as f64 * opacity + original_color.g as f64 * (1.0 - opacity))
+                        as u8,
+                    b: (route_color.b as f64 * opacity + original_color.b as f64 * (1.0 - opacity))
+                        as u8,
+                };
+                color_grid.grid[point.y][point.x] = new_color;
+            }
+        }
+        color_grid
+    }
+}
+
+/// The progress of a sampling pass that is advanced one net or one pad pair at a time
+#[derive(Debug, Clone, Default)]
+pub struct SamplingCursor {
+    pub remaining_pad_pairs: VecDeque<(NetID, PadPairID)>, // pad pairs not sampled yet in this pass, grouped by net
+    pub new_traces: Vec<TraceInfo>, // traces sampled so far, added to the grid when the pass is complete
 }
 
 pub enum ProbaGridState {
-    Uninitialized { input: ProbaGridProblem },
-    Initialized { output: ProbaGrid },
+    Uninitialized {
+        input: ProbaGridProblem,
+    },
+    Initialized {
+        output: ProbaGrid,
+        cursor: Option<SamplingCursor>, // None when no sampling pass is in progress
+        history: ProbaHistory,
+    },
 }
 
 impl ProbaGridState {
     pub fn to_color_grid(&self) -> ColorGrid {
         match self {
             ProbaGridState::Uninitialized { input } => input.to_color_grid(),
-            ProbaGridState::Initialized { output } => output.to_color_grid(),
+            ProbaGridState::Initialized {
+                output,
+                cursor: None,
+                ..
+            } => output.to_color_grid(),
+            ProbaGridState::Initialized {
+                output,
+                cursor: Some(cursor),
+                ..
+            } => output.to_color_grid_with_pending(&cursor.new_traces),
         }
     }
 }
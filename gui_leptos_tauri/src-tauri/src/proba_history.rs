@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+
+use shared::interface_types::ProbaSnapshotInfo;
+
+use crate::proba_grid::ProbaGrid;
+
+/// A copy of the proba grid between two iterations, never modified once recorded
+#[derive(Clone)]
+pub struct ProbaSnapshot {
+    pub id: usize,
+    pub parent: Option<usize>, // the snapshot this one was reached from, differs from id - 1 after a restore
+    pub label: String,         // what produced the snapshot, e.g. "update posterior"
+    pub grid: ProbaGrid,
+}
+
+impl ProbaSnapshot {
+    pub fn info(&self) -> ProbaSnapshotInfo {
+        ProbaSnapshotInfo {
+            id: self.id,
+            parent: self.parent,
+            iteration: self.grid.next_iteration.get() - 1,
+            num_traces: self.grid.traces.len(),
+            posteriors_updated: self
+                .grid
+                .traces
+                .values()
+                .all(|trace_info| trace_info.posterior_normalized.is_some()),
+            label: self.label.clone(),
+        }
+    }
+}
+
+/// The snapshots taken since the proba grid was initialized, including the branches left by restoring
+#[derive(Clone, Default)]
+pub struct ProbaHistory {
+    pub snapshots: Vec<ProbaSnapshot>, // ordered by id, dropped snapshots leave gaps in the ids
+    pub current: Option<usize>, // the snapshot the grid was last recorded as or restored from
+    pub next_id: usize,
+}
+
+impl ProbaHistory {
+    /// Records the grid as a child of the current snapshot, keeping at most `max_snapshots`.
+    /// The oldest snapshots off the current branch are dropped first, then the oldest ancestors
+    pub fn record(&mut self, grid: &ProbaGrid, label: &str, max_snapshots: usize) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.snapshots.push(ProbaSnapshot {
+            id,
+            parent: self.current,
+            label: label.to_string(),
+            grid: grid.clone(),
+        });
+        self.current = Some(id);
+        while self.snapshots.len() > max_snapshots.max(1) {
+            let ancestors = self.ancestors(id);
+            let index = self
+                .snapshots
+                .iter()
+                .position(|snapshot| !ancestors.contains(&snapshot.id))
+                .unwrap_or(0);
+            self.snapshots.remove(index);
+        }
+        id
+    }
+    pub fn get(&self, id: usize) -> Option<&ProbaSnapshot> {
+        self.snapshots
+            .binary_search_by_key(&id, |snapshot| snapshot.id)
+            .ok()
+            .map(|index| &self.snapshots[index])
+    }
+    /// The snapshot and the recorded snapshots it was reached from
+    pub fn ancestors(&self, id: usize) -> HashSet<usize> {
+        let mut ancestors = HashSet::new();
+        let mut next = self.get(id);
+        while let Some(snapshot) = next {
+            ancestors.insert(snapshot.id);
+            next = snapshot.parent.and_then(|parent| self.get(parent));
+        }
+        ancestors
+    }
+    pub fn list(&self) -> Vec<ProbaSnapshotInfo> {
+        self.snapshots.iter().map(ProbaSnapshot::info).collect()
+    }
+    /// A copy of the grid in the snapshot, so that the snapshot can be restored again later
+    pub fn restore(&mut self, id: usize) -> Result<ProbaGrid, String> {
+        let snapshot = self
+            .get(id)
+            .ok_or_else(|| format!("Snapshot {} does not exist", id))?;
+        let grid = snapshot.grid.clone();
+        self.current = Some(id);
+        Ok(grid)
+    }
+}
@@ -0,0 +1,575 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    hash::Hash,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use shared::interface_types::Color;
+
+use crate::{
+    design_rules::{DesignRules, NetClass},
+    grid::{Grid, LayerPoint, Net, PadKind, Point},
+    hyperparameters::DEFAULT_PROBA_SEED,
+    keepout::Keepout,
+    proba_grid::{NetID, NetInfo, ProbaGridProblem},
+};
+
+/// The version written to new problem files. Files of another version are rejected when loaded
+pub const PROBLEM_FILE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemFileKind {
+    Problem,  // pads, keepouts and rules
+    Solution, // also the traces, diagonal traces and vias of a routed grid
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProblemMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generator: Option<String>, // the program and version that wrote the file
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PadEntry {
+    pub x: usize,
+    pub y: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<usize>, // the layer of an SMD pad, None for a through-hole pad
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellEntry {
+    pub x: usize,
+    pub y: usize,
+    pub layer: usize,
+}
+
+impl CellEntry {
+    fn new(point: &LayerPoint) -> Self {
+        CellEntry {
+            x: point.point.x,
+            y: point.point.y,
+            layer: point.layer,
+        }
+    }
+    fn layer_point(&self) -> LayerPoint {
+        LayerPoint::new(self.x, self.y, self.layer)
+    }
+}
+
+/// A net is identified by its pad character or, if it has one, its pad color
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetEntry {
+    pub id: usize, // the clearances of the rules refer to nets by id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pad_char: Option<char>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route_char: Option<char>, // defaults to the lowercase pad character
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pad_color: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route_color: Option<Color>, // defaults to the pad color
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_width: Option<usize>, // overrides the width of the net class
+    #[serde(default)]
+    pub pads: Vec<PadEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub traces: Vec<CellEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagonal_traces: Vec<CellEntry>, // the cell at the top left corner of the diagonal
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vias: Vec<Point>,
+}
+
+impl NetEntry {
+    fn grid_net(&self) -> Result<Net, String> {
+        match (&self.pad_color, self.pad_char) {
+            (Some(pad_color), _) => Ok(Net::Color {
+                pad_color: pad_color.clone(),
+                route_color: self.route_color.clone().unwrap_or(pad_color.clone()),
+            }),
+            (None, Some(pad_c)) => Ok(Net::Character {
+                pad_c,
+                route_c: self.route_char.unwrap_or(pad_c.to_ascii_lowercase()),
+            }),
+            (None, None) => Err(format!(
+                "Net {} has neither a pad character nor a pad color",
+                self.id
+            )),
+        }
+    }
+    fn has_routes(&self) -> bool {
+        !(self.traces.is_empty() && self.diagonal_traces.is_empty() && self.vias.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClearanceEntry {
+    pub nets: [usize; 2], // the ids of the two nets
+    pub clearance: usize,
+}
+
+/// The design rules in grid cells. The class and width of each net are kept with the net
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RulesEntry {
+    pub default_trace_width: usize,
+    pub default_clearance: usize,
+    pub net_classes: Vec<NetClass>,
+    pub clearances: Vec<ClearanceEntry>,
+}
+
+impl Default for RulesEntry {
+    fn default() -> Self {
+        rules_entry(&DesignRules::<NetID>::default(), &HashMap::new())
+    }
+}
+
+fn rules_entry<N: Clone + Eq + Hash + Ord>(
+    rules: &DesignRules<N>,
+    ids: &HashMap<N, usize>,
+) -> RulesEntry {
+    let mut net_classes: Vec<NetClass> = rules.net_classes.values().cloned().collect();
+    net_classes.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut clearances: Vec<ClearanceEntry> = rules
+        .clearances
+        .iter()
+        .map(|((a, b), clearance)| ClearanceEntry {
+            nets: [ids[a], ids[b]],
+            clearance: *clearance,
+        })
+        .collect();
+    clearances.sort_by_key(|entry| entry.nets);
+    RulesEntry {
+        default_trace_width: rules.default_trace_width,
+        default_clearance: rules.default_clearance,
+        net_classes,
+        clearances,
+    }
+}
+
+// every net the rules refer to, so that it gets an id even without pads
+fn rule_nets<N: Clone + Eq + Hash + Ord>(rules: &DesignRules<N>) -> impl Iterator<Item = &N> {
+    rules
+        .trace_widths
+        .keys()
+        .chain(rules.net_to_class.keys())
+        .chain(rules.clearances.keys().flat_map(|(a, b)| [a, b]))
+}
+
+/// A versioned routing problem or routed solution, saved as JSON or TOML.
+/// Nets, points and rules are sorted so that the same grid is always written the same way
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProblemFile {
+    pub version: u32,
+    pub kind: ProblemFileKind,
+    #[serde(default)]
+    pub metadata: ProblemMetadata,
+    pub width: usize,
+    pub height: usize,
+    pub layers: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>, // the seed of a proba problem
+    #[serde(default)]
+    pub nets: Vec<NetEntry>,
+    #[serde(default)]
+    pub keepouts: Vec<Keepout>,
+    #[serde(default)]
+    pub rules: RulesEntry,
+}
+
+// read before the rest of the file, so that a file of another version fails with a clear message
+#[derive(Deserialize)]
+struct FileVersion {
+    version: u32,
+}
+
+fn check_version(version: u32) -> Result<(), String> {
+    if version != PROBLEM_FILE_VERSION {
+        return Err(format!(
+            "Unsupported problem file version {}, expected {}",
+            version, PROBLEM_FILE_VERSION
+        ));
+    }
+    Ok(())
+}
+
+impl ProblemFile {
+    fn new(kind: ProblemFileKind, width: usize, height: usize, layers: usize) -> Self {
+        ProblemFile {
+            version: PROBLEM_FILE_VERSION,
+            kind,
+            metadata: ProblemMetadata {
+                generator: Some(format!(
+                    "{} {}",
+                    env!("CARGO_PKG_NAME"),
+                    env!("CARGO_PKG_VERSION")
+                )),
+                ..Default::default()
+            },
+            width,
+            height,
+            layers,
+            seed: None,
+            nets: Vec::new(),
+            keepouts: Vec::new(),
+            rules: RulesEntry::default(),
+        }
+    }
+    /// The pads, keepouts and rules of a grid, and its routes if kind is Solution
+    pub fn from_grid(grid: &Grid, kind: ProblemFileKind) -> Self {
+        let mut file = Self::new(kind, grid.width, grid.height, grid.layers);
+        let nets: BTreeSet<&Net> = grid
+            .pads
+            .keys()
+            .chain(grid.traces.keys())
+            .chain(grid.diagonal_traces.keys())
+            .chain(grid.vias.keys())
+            .chain(rule_nets(&grid.design_rules))
+            .collect();
+        let ids: HashMap<Net, usize> = nets
+            .iter()
+            .enumerate()
+            .map(|(i, net)| ((*net).clone(), i + 1))
+            .collect();
+        let cells = |cells: Option<&HashSet<LayerPoint>>| -> Vec<CellEntry> {
+            let mut cells: Vec<LayerPoint> = cells.into_iter().flatten().copied().collect();
+            cells.sort();
+            cells.iter().map(CellEntry::new).collect()
+        };
+        for net in nets {
+            let (pad_char, route_char, pad_color, route_color) = match net {
+                Net::Character { pad_c, route_c } => (Some(*pad_c), Some(*route_c), None, None),
+                Net::Color {
+                    pad_color,
+                    route_color,
+                } => (
+                    None,
+                    None,
+                    Some(pad_color.clone()),
+                    Some(route_color.clone()),
+                ),
+            };
+            let routed = kind == ProblemFileKind::Solution;
+            let mut vias: Vec<Point> = match grid.vias.get(net) {
+                Some(vias) if routed => vias.iter().copied().collect(),
+                _ => Vec::new(),
+            };
+            vias.sort();
+            file.nets.push(NetEntry {
+                id: ids[net],
+                pad_char,
+                route_char,
+                pad_color,
+                route_color,
+                class: grid.design_rules.net_to_class.get(net).cloned(),
+                trace_width: grid.design_rules.trace_widths.get(net).copied(),
+                pads: grid
+                    .pads
+                    .get(net)
+                    .into_iter()
+                    .flatten()
+                    .map(|point| PadEntry {
+                        x: point.x,
+                        y: point.y,
+                        layer: match grid.pad_kind(*point) {
+                            PadKind::Smd { layer } => Some(layer),
+                            PadKind::ThroughHole => None,
+                        },
+                    })
+                    .collect(),
+                traces: cells(grid.traces.get(net).filter(|_| routed)),
+                diagonal_traces: cells(grid.diagonal_traces.get(net).filter(|_| routed)),
+                vias,
+            });
+        }
+        file.keepouts = grid.keepouts.clone();
+        file.rules = rules_entry(&grid.design_rules, &ids);
+        file
+    }
+    /// A proba problem, which has a single layer and no routes
+    pub fn from_proba_grid_problem(problem: &ProbaGridProblem) -> Self {
+        let mut file = Self::new(ProblemFileKind::Problem, problem.width, problem.height, 1);
+        file.seed = Some(problem.seed);
+        let net_ids: BTreeSet<NetID> = problem
+            .nets
+            .keys()
+            .chain(problem.net_to_pads.keys())
+            .chain(rule_nets(&problem.design_rules))
+            .copied()
+            .collect();
+        for net_id in &net_ids {
+            let info = problem.nets.get(net_id);
+            let mut pads: Vec<&Point> = problem
+                .net_to_pads
+                .get(net_id)
+                .into_iter()
+                .flatten()
+                .collect();
+            pads.sort();
+            file.nets.push(NetEntry {
+                id: net_id.0,
+                pad_char: info.and_then(|info| info.pad_character),
+                route_char: info.and_then(|info| info.route_character),
+                pad_color: info.and_then(|info| info.pad_color.clone()),
+                route_color: info.and_then(|info| info.route_color.clone()),
+                class: problem.design_rules.net_to_class.get(net_id).cloned(),
+                trace_width: problem.design_rules.trace_widths.get(net_id).copied(),
+                pads: pads
+                    .into_iter()
+                    .map(|point| PadEntry {
+                        x: point.x,
+                        y: point.y,
+                        layer: None,
+                    })
+                    .collect(),
+                traces: Vec::new(),
+                diagonal_traces: Vec::new(),
+                vias: Vec::new(),
+            });
+        }
+        file.keepouts = problem.keepouts.clone();
+        let ids = net_ids.iter().map(|net_id| (*net_id, net_id.0)).collect();
+        file.rules = rules_entry(&problem.design_rules, &ids);
+        file
+    }
+    pub fn validate(&self) -> Result<(), String> {
+        check_version(self.version)?;
+        if self.width == 0 || self.height == 0 || self.layers == 0 {
+            return Err(format!(
+                "The grid must have at least one cell and one layer, got {}x{}x{}",
+                self.width, self.height, self.layers
+            ));
+        }
+        let check_cell = |id: usize, x: usize, y: usize, layer: usize| {
+            if x >= self.width || y >= self.height || layer >= self.layers {
+                return Err(format!(
+                    "Net {} has a cell ({}, {}) on layer {} outside of the {}x{}x{} grid",
+                    id, x, y, layer, self.width, self.height, self.layers
+                ));
+            }
+            Ok(())
+        };
+        let class_names: HashSet<&str> = self
+            .rules
+            .net_classes
+            .iter()
+            .map(|net_class| net_class.name.as_str())
+            .collect();
+        let mut ids = HashSet::new();
+        let mut pad_nets: HashMap<(usize, usize), usize> = HashMap::new();
+        for net in &self.nets {
+            if !ids.insert(net.id) {
+                return Err(format!("Net id {} is used twice", net.id));
+            }
+            for pad in &net.pads {
+                check_cell(net.id, pad.x, pad.y, pad.layer.unwrap_or(0))?;
+                match pad_nets.insert((pad.x, pad.y), net.id) {
+                    Some(other) if other != net.id => {
+                        return Err(format!(
+                            "Nets {} and {} both have a pad at ({}, {})",
+                            other, net.id, pad.x, pad.y
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            if self.kind == ProblemFileKind::Problem && net.has_routes() {
+                return Err(format!(
+                    "Net {} has routes, which only a solution file may contain",
+                    net.id
+                ));
+            }
+            for cell in net.traces.iter().chain(&net.diagonal_traces) {
+                check_cell(net.id, cell.x, cell.y, cell.layer)?;
+            }
+            for via in &net.vias {
+                check_cell(net.id, via.x, via.y, 0)?;
+            }
+            if let Some(class) = &net.class {
+                if !class_names.contains(class.as_str()) {
+                    return Err(format!("Net {} has an unknown net class {}", net.id, class));
+                }
+            }
+        }
+        for entry in &self.rules.clearances {
+            if let Some(id) = entry.nets.iter().find(|id| !ids.contains(*id)) {
+                return Err(format!("A clearance refers to the unknown net {}", id));
+            }
+        }
+        Ok(())
+    }
+    fn design_rules<N: Clone + Eq + Hash + Ord>(&self, nets: &HashMap<usize, N>) -> DesignRules<N> {
+        let mut design_rules = DesignRules {
+            default_trace_width: self.rules.default_trace_width,
+            default_clearance: self.rules.default_clearance,
+            trace_widths: HashMap::new(),
+            clearances: HashMap::new(),
+            net_classes: self
+                .rules
+                .net_classes
+                .iter()
+                .map(|net_class| (net_class.name.clone(), net_class.clone()))
+                .collect(),
+            net_to_class: HashMap::new(),
+        };
+        for entry in &self.nets {
+            let net = &nets[&entry.id];
+            if let Some(class) = &entry.class {
+                design_rules.net_to_class.insert(net.clone(), class.clone());
+            }
+            if let Some(trace_width) = entry.trace_width {
+                design_rules.trace_widths.insert(net.clone(), trace_width);
+            }
+        }
+        for entry in &self.rules.clearances {
+            let [a, b] = entry.nets;
+            design_rules.set_clearance(&nets[&a], &nets[&b], entry.clearance);
+        }
+        design_rules
+    }
+    /// The grid of the file, with the routes of a solution
+    pub fn to_grid(&self) -> Result<Grid, String> {
+        self.validate()?;
+        let mut grid = Grid::new_with_layers(self.width, self.height, self.layers);
+        let mut nets: HashMap<usize, Net> = HashMap::new();
+        for entry in &self.nets {
+            let net = entry.grid_net()?;
+            if let Some((id, _)) = nets.iter().find(|(_, other)| **other == net) {
+                return Err(format!("Nets {} and {} are the same net", id, entry.id));
+            }
+            for pad in &entry.pads {
+                let point = Point { x: pad.x, y: pad.y };
+                match pad.layer {
+                    Some(layer) => grid.insert_smd_pad(net.clone(), point, layer),
+                    None => grid.insert_pad(net.clone(), point),
+                }
+            }
+            let cells = |cells: &[CellEntry]| -> HashSet<LayerPoint> {
+                cells.iter().map(CellEntry::layer_point).collect()
+            };
+            if !entry.traces.is_empty() {
+                grid.traces.insert(net.clone(), cells(&entry.traces));
+            }
+            if !entry.diagonal_traces.is_empty() {
+                grid.diagonal_traces
+                    .insert(net.clone(), cells(&entry.diagonal_traces));
+            }
+            if !entry.vias.is_empty() {
+                grid.vias
+                    .insert(net.clone(), entry.vias.iter().copied().collect());
+            }
+            nets.insert(entry.id, net);
+        }
+        grid.keepouts = self.keepouts.clone();
+        grid.design_rules = self.design_rules(&nets);
+        Ok(grid)
+    }
+    /// The proba problem of the file. The proba grid has a single layer, so pad layers and the routes
+    /// of a solution are dropped and every keepout blocks the whole cell
+    pub fn to_proba_grid_problem(&self) -> Result<ProbaGridProblem, String> {
+        self.validate()?;
+        let mut problem = ProbaGridProblem {
+            keepouts: self.keepouts.clone(),
+            ..ProbaGridProblem::new(
+                self.width,
+                self.height,
+                self.seed.unwrap_or(DEFAULT_PROBA_SEED),
+            )
+        };
+        if self.layers > 1 {
+            for keepout in &mut problem.keepouts {
+                keepout.layers = None;
+            }
+        }
+        let mut nets = HashMap::new();
+        for entry in &self.nets {
+            let net_id = NetID(entry.id);
+            if entry.pad_char.is_some() || entry.pad_color.is_some() {
+                problem.nets.insert(
+                    net_id,
+                    NetInfo {
+                        net_id: entry.id,
+                        pad_character: entry.pad_char,
+                        route_character: entry.route_char,
+                        pad_color: entry.pad_color.clone(),
+                        route_color: entry.route_color.clone(),
+                    },
+                );
+            }
+            if !entry.pads.is_empty() {
+                problem.net_to_pads.insert(
+                    net_id,
+                    entry
+                        .pads
+                        .iter()
+                        .map(|pad| Point { x: pad.x, y: pad.y })
+                        .collect(),
+                );
+            }
+            nets.insert(entry.id, net_id);
+        }
+        problem.design_rules = self.design_rules(&nets);
+        Ok(problem)
+    }
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let version: FileVersion =
+            serde_json::from_str(text).map_err(|e| format!("Invalid problem file: {}", e))?;
+        check_version(version.version)?;
+        let file: ProblemFile =
+            serde_json::from_str(text).map_err(|e| format!("Invalid problem file: {}", e))?;
+        file.validate()?;
+        Ok(file)
+    }
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let version: FileVersion =
+            toml::from_str(text).map_err(|e| format!("Invalid problem file: {}", e))?;
+        check_version(version.version)?;
+        let file: ProblemFile =
+            toml::from_str(text).map_err(|e| format!("Invalid problem file: {}", e))?;
+        file.validate()?;
+        Ok(file)
+    }
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+    /// Loads a .json or .toml problem file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&text),
+            Some("toml") => Self::from_toml(&text),
+            _ => Err(format!(
+                "Unknown problem file format {}, expected .json or .toml",
+                path.display()
+            )),
+        }
+    }
+    /// Saves to a .json or .toml file
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => self.to_json()?,
+            Some("toml") => self.to_toml()?,
+            _ => {
+                return Err(format!(
+                    "Unknown problem file format {}, expected .json or .toml",
+                    path.display()
+                ))
+            }
+        };
+        std::fs::write(path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
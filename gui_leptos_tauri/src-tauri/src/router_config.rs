@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dijkstra::PathCosts,
+    hyperparameters::{
+        CANDIDATE_COST_JITTER, CANDIDATE_REUSE_PENALTY, DEFAULT_OPPORTUNITY_COST_WEIGHT,
+        DEFAULT_SCORE_WEIGHT, HALF_PROBABILITY_RAW_SCORE, LENGTH_PENALTY_RATE,
+        MAX_GENERATION_ATTEMPTS, MAX_PROBA_SNAPSHOTS, MAX_TRACES_PER_ITERATION, TURN_PENALTY_RATE,
+        VIA_COST,
+    },
+    steiner_tree::NetDecomposition,
+};
+
+/// The tunable parameters of the routers, defaulting to the constants in hyperparameters.rs.
+/// Missing fields of a config file keep their default
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouterConfig {
+    pub length_penalty_rate: f64,
+    pub turn_penalty_rate: f64,
+    pub via_cost: f64,
+    pub half_probability_raw_score: f64, // the raw score at which a trace scores one half
+    pub score_weight: f64,
+    pub opportunity_cost_weight: f64,
+    pub max_traces_per_iteration: usize,
+    pub max_generation_attempts: usize,
+    pub candidate_reuse_penalty: f64,
+    pub candidate_cost_jitter: f64,
+    pub max_snapshots: usize, // snapshots kept in the history of the proba grid
+    // how multi-pad nets are broken into pad pairs, None keeps the default of each router
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decomposition: Option<NetDecomposition>,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        RouterConfig {
+            length_penalty_rate: LENGTH_PENALTY_RATE,
+            turn_penalty_rate: TURN_PENALTY_RATE,
+            via_cost: VIA_COST,
+            half_probability_raw_score: HALF_PROBABILITY_RAW_SCORE,
+            score_weight: DEFAULT_SCORE_WEIGHT,
+            opportunity_cost_weight: DEFAULT_OPPORTUNITY_COST_WEIGHT,
+            max_traces_per_iteration: MAX_TRACES_PER_ITERATION,
+            max_generation_attempts: MAX_GENERATION_ATTEMPTS,
+            candidate_reuse_penalty: CANDIDATE_REUSE_PENALTY,
+            candidate_cost_jitter: CANDIDATE_COST_JITTER,
+            max_snapshots: MAX_PROBA_SNAPSHOTS,
+            decomposition: None,
+        }
+    }
+}
+
+impl RouterConfig {
+    /// The costs the candidates are routed and scored with
+    pub fn path_costs(&self) -> PathCosts {
+        PathCosts {
+            length: self.length_penalty_rate,
+            diagonal: self.length_penalty_rate * (2.0f64).sqrt(),
+            turn: self.turn_penalty_rate,
+            via: self.via_cost,
+        }
+    }
+    pub fn validate(&self) -> Result<(), String> {
+        let non_negative = [
+            ("length_penalty_rate", self.length_penalty_rate),
+            ("turn_penalty_rate", self.turn_penalty_rate),
+            ("via_cost", self.via_cost),
+            ("score_weight", self.score_weight),
+            ("opportunity_cost_weight", self.opportunity_cost_weight),
+            ("candidate_reuse_penalty", self.candidate_reuse_penalty),
+            ("candidate_cost_jitter", self.candidate_cost_jitter),
+        ];
+        for (name, value) in non_negative {
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(format!(
+                    "{} must be a non-negative number, got {}",
+                    name, value
+                ));
+            }
+        }
+        if !(self.half_probability_raw_score > 0.0 && self.half_probability_raw_score.is_finite()) {
+            return Err(format!(
+                "half_probability_raw_score must be positive, got {}",
+                self.half_probability_raw_score
+            ));
+        }
+        if self.max_traces_per_iteration == 0 || self.max_generation_attempts == 0 {
+            return Err(
+                "max_traces_per_iteration and max_generation_attempts must be at least 1"
+                    .to_string(),
+            );
+        }
+        if self.max_snapshots == 0 {
+            return Err("max_snapshots must be at least 1".to_string());
+        }
+        Ok(())
+    }
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let config: RouterConfig =
+            toml::from_str(text).map_err(|e| format!("Invalid router config: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let config: RouterConfig =
+            serde_json::from_str(text).map_err(|e| format!("Invalid router config: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+    /// Loads a .toml or .json config file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("json") => Self::from_json(&text),
+            _ => Err(format!(
+                "Unknown router config format {}, expected .toml or .json",
+                path.display()
+            )),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt;
+
+/// An S-expression as used by KiCad files. Quoted strings are kept apart from bare atoms
+/// so that a file is written back the way it was read
+#[derive(Debug, Clone, PartialEq)]
+pub enum SExpr {
+    Atom(String),
+    Str(String),
+    List(Vec<SExpr>),
+}
+
+impl SExpr {
+    /// Parses a single S-expression, e.g. the whole content of a .kicad_pcb file
+    pub fn parse(text: &str) -> Result<SExpr, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            position: 0,
+            line: 1,
+        };
+        parser.skip_whitespace();
+        let expr = parser.parse_expr()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(format!(
+                "Unexpected content after the S-expression at line {}",
+                parser.line
+            ));
+        }
+        Ok(expr)
+    }
+    /// A list starting with the bare atom head, e.g. SExpr::list("net", vec![SExpr::from_f64(1.0)])
+    pub fn list(head: &str, args: Vec<SExpr>) -> SExpr {
+        let mut items = vec![SExpr::Atom(head.to_string())];
+        items.extend(args);
+        SExpr::List(items)
+    }
+    /// A bare number, rounded to a nanometre when the unit is mm
+    pub fn from_f64(value: f64) -> SExpr {
+        // adding 0.0 turns -0 into 0
+        SExpr::Atom(format!("{}", (value * 1e6).round() / 1e6 + 0.0))
+    }
+    pub fn as_atom(&self) -> Option<&str> {
+        match self {
+            SExpr::Atom(atom) | SExpr::Str(atom) => Some(atom),
+            SExpr::List(_) => None,
+        }
+    }
+    pub fn as_list(&self) -> Option<&[SExpr]> {
+        match self {
+            SExpr::List(items) => Some(items),
+            _ => None,
+        }
+    }
+    /// The first atom of a list, e.g. "pad" for (pad "1" smd rect ...)
+    pub fn head(&self) -> Option<&str> {
+        self.as_list()?.first()?.as_atom()
+    }
+    /// The items of a list after its head
+    pub fn args(&self) -> &[SExpr] {
+        match self.as_list() {
+            Some(items) if !items.is_empty() => &items[1..],
+            _ => &[],
+        }
+    }
+    /// The child lists with the given head, in order
+    pub fn children<'a>(&'a self, head: &'a str) -> impl Iterator<Item = &'a SExpr> + 'a {
+        self.args()
+            .iter()
+            .filter(move |child| child.head() == Some(head))
+    }
+    pub fn child(&self, head: &str) -> Option<&SExpr> {
+        self.args().iter().find(|child| child.head() == Some(head))
+    }
+    /// The i-th argument as an atom
+    pub fn atom(&self, i: usize) -> Option<&str> {
+        self.args().get(i)?.as_atom()
+    }
+    /// The i-th argument as a number
+    pub fn number(&self, i: usize) -> Option<f64> {
+        self.atom(i)?.parse().ok()
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+    }
+    fn parse_expr(&mut self) -> Result<SExpr, String> {
+        match self.peek() {
+            None => Err("Unexpected end of the S-expression".to_string()),
+            Some('(') => {
+                self.next();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        None => {
+                            return Err(format!("Unclosed list at line {}", self.line));
+                        }
+                        Some(')') => {
+                            self.next();
+                            return Ok(SExpr::List(items));
+                        }
+                        Some(_) => items.push(self.parse_expr()?),
+                    }
+                }
+            }
+            Some(')') => Err(format!("Unexpected ')' at line {}", self.line)),
+            Some('"') => {
+                self.next();
+                let mut atom = String::new();
+                loop {
+                    match self.next() {
+                        None => return Err(format!("Unclosed string at line {}", self.line)),
+                        Some('"') => return Ok(SExpr::Str(atom)),
+                        Some('\\') => match self.next() {
+                            Some('n') => atom.push('\n'),
+                            Some('t') => atom.push('\t'),
+                            Some(c) => atom.push(c),
+                            None => return Err(format!("Unclosed string at line {}", self.line)),
+                        },
+                        Some(c) => atom.push(c),
+                    }
+                }
+            }
+            Some(_) => {
+                let mut atom = String::new();
+                while let Some(c) = self.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    atom.push(c);
+                    self.next();
+                }
+                Ok(SExpr::Atom(atom))
+            }
+        }
+    }
+}
+
+// lists without sublists stay on one line, the others put every sublist on its own line, like KiCad does
+fn write_expr(f: &mut fmt::Formatter<'_>, expr: &SExpr, depth: usize) -> fmt::Result {
+    match expr {
+        SExpr::Atom(atom) => write!(f, "{}", atom),
+        SExpr::Str(text) => write!(
+            f,
+            "\"{}\"",
+            text.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+        ),
+        SExpr::List(items) => {
+            write!(f, "(")?;
+            let nested = items.iter().any(|item| item.as_list().is_some());
+            for (i, item) in items.iter().enumerate() {
+                if nested && item.as_list().is_some() {
+                    write!(f, "\n{}", "\t".repeat(depth + 1))?;
+                } else if i > 0 {
+                    write!(f, " ")?;
+                }
+                write_expr(f, item, depth + 1)?;
+            }
+            if nested {
+                write!(f, "\n{}", "\t".repeat(depth))?;
+            }
+            write!(f, ")")
+        }
+    }
+}
+
+impl fmt::Display for SExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_expr(f, self, 0)
+    }
+}
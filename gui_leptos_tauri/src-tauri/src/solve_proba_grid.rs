@@ -1,22 +1,24 @@
 use std::{
-    cell::RefCell,
     collections::{BTreeSet, HashMap, HashSet},
     num::NonZeroUsize,
 };
 
 use rand::distr::weighted::WeightedIndex;
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
 
 use crate::{
-    dijkstra::DijkstraModel,
-    grid::{Point, PointPair},
-    hyperparameters::{
-        HALF_PROBABILITY_RAW_SCORE, LENGTH_PENALTY_RATE, MAX_GENERATION_ATTEMPTS,
-        MAX_TRACES_PER_ITERATION, OPPORTUNITY_COST_WEIGHT, SCORE_WEIGHT, TURN_PENALTY_RATE,
-    },
+    design_rules::inflate,
+    dijkstra::{pad_goals, DijkstraModel, DijkstraResult, SearchMode},
+    grid::{LayerPoint, Point, PointPair},
+    hyperparameters::{PROBA_CONVERGENCE_TOLERANCE, PROBA_MAX_ITERATIONS},
     proba_grid::{
-        IterationNum, NetID, PadPair, PadPairID, ProbaGrid, ProbaGridProblem, TraceID, TraceInfo,
+        Direction, IterationNum, NetID, PadPair, PadPairID, PriorSchedule, ProbaGrid,
+        ProbaGridProblem, SamplingCursor, TraceID, TraceInfo, TracePath,
     },
+    router_config::RouterConfig,
+    steiner_tree::{rectilinear_spanning_tree, rectilinear_steiner_tree, NetDecomposition},
 };
 
 // pub fn first_iteration_prior(
@@ -178,7 +180,7 @@ use crate::{
 //     Ok(output)
 // }
 
-pub fn update_posterior(grid: &mut ProbaGrid) -> Result<(), String> {
+pub fn update_posterior(grid: &mut ProbaGrid, config: &RouterConfig) -> Result<(), String> {
     // // Update the posterior probabilities based on the prior anchor and collision adjacency
     // let ProbaGrid {
     //     width,
@@ -193,100 +195,42 @@ pub fn update_posterior(grid: &mut ProbaGrid) -> Result<(), String> {
     //     trace_collision_adjacency,
     //     next_iteration
     // } = grid;
-    for (trace_id, trace_info) in grid.traces.iter() {
-        let adjacent_traces = grid
-            .trace_collision_adjacency
-            .get(trace_id)
-            .ok_or_else(|| {
-                format!(
-                    "Trace ID {:?} not found in trace_collision_adjacency",
-                    trace_id
-                )
-            })?;
-        let mut proba_product = 1.0;
-        for adjacent_trace_id in adjacent_traces {
-            let adjacent_trace_info = grid.traces.get(adjacent_trace_id).ok_or_else(|| {
-                format!(
-                    "Adjacent Trace ID {:?} not found in traces",
-                    adjacent_trace_id
-                )
-            })?;
-            // get num traces in the same iteration
-            let num_traces_in_the_same_iteration =
-                grid.get_num_traces_in_the_same_iteration(*adjacent_trace_id);
-            let probability_normalized = adjacent_trace_info
-                .get_posterior_normalized_with_fallback(num_traces_in_the_same_iteration);
-            let one_minus_proba = 1.0 - probability_normalized;
-            assert!(
-                one_minus_proba > 0.0,
-                "One minus probability must be greater than 0"
-            );
-            proba_product *= one_minus_proba;
-        }
-        let target_posterior = proba_product;
-        assert!(
-            target_posterior >= 0.0 && target_posterior <= 1.0,
-            "Target posterior must be between 0 and 1"
-        );
-        // get num traces in the same iteration
-        let num_traces_in_the_same_iteration = grid.get_num_traces_in_the_same_iteration(*trace_id);
-        let current_posterior =
-            trace_info.get_posterior_normalized_with_fallback(num_traces_in_the_same_iteration);
-        let opportunity_cost = target_posterior / current_posterior;
-        let score = trace_info.get_score();
-        let score_weight = *SCORE_WEIGHT.lock().unwrap();
-        let opportunity_cost_weight = *OPPORTUNITY_COST_WEIGHT.lock().unwrap();
-        let posterior_unnormalized = 1.0
-            * f64::powf(score, score_weight)
-            * f64::powf(opportunity_cost, opportunity_cost_weight);
-        let posterior_normalized = trace_info
-            .get_normalized_prior_probability(num_traces_in_the_same_iteration)
-            * posterior_unnormalized;
-        let mut temp_posterior = trace_info.temp_posterior.borrow_mut();
-        *temp_posterior = Some(posterior_normalized);
-    }
+    // the new posteriors only depend on the old ones, so every trace is computed in parallel
+    let trace_ids: Vec<TraceID> = grid.traces.keys().cloned().collect();
+    let grid_ref: &ProbaGrid = grid;
+    let temp_posteriors: HashMap<TraceID, f64> = trace_ids
+        .par_iter()
+        .map(|trace_id| Ok((*trace_id, temp_posterior(grid_ref, *trace_id, config)?)))
+        .collect::<Result<HashMap<_, _>, String>>()?;
     // convert the temp posterior to the final posterior
+    let target_total_probability = grid
+        .prior_schedule
+        .total_prior_probability(grid.next_iteration.get() - 1);
+    // the geometric schedule only rounds to 1.0 after dozens of iterations
+    assert!(
+        target_total_probability <= 1.0,
+        "Total prior probability must not exceed 1.0, but got {}",
+        target_total_probability
+    );
+    let mut posteriors: Vec<(TraceID, f64)> = Vec::new();
     for (_pad_pair_id, trace_ids) in grid.pad_pair_to_traces.iter() {
-        // trace_infos in the same pad pair
-        let trace_infos = trace_ids
+        // trace_infos in the same pad pair, sorted so that they are summed in a fixed order
+        let mut trace_ids: Vec<TraceID> = trace_ids
             .iter()
-            .flat_map(|(_, trace_ids)| trace_ids.iter())
-            .map(|trace_id| {
-                grid.traces
-                    .get(trace_id)
-                    .expect(format!("Trace ID {:?} not found in traces", trace_id).as_str())
-            })
-            .collect::<Vec<_>>();
-        let current_total_probability: f64 = trace_infos
+            .flat_map(|(_, trace_ids)| trace_ids.iter().cloned())
+            .collect();
+        trace_ids.sort();
+        if trace_ids.is_empty() {
+            continue; // the pad pair has not found a trace yet
+        }
+        let current_total_probability: f64 = trace_ids
             .iter()
-            .map(|trace_info| {
-                let temp_posterior = trace_info.temp_posterior.borrow();
-                *temp_posterior
-                    .as_ref()
-                    .expect("Temporary posterior must be set before updating the final posterior")
-            })
+            .map(|trace_id| temp_posteriors[trace_id])
             .sum();
         assert!(
             current_total_probability > 0.0,
             "Total probability must be greater than 0"
         );
-        let mut target_total_probability = 0.0;
-        for iteration_num in (1..grid.next_iteration.get()).map(|i| NonZeroUsize::new(i).unwrap()) {
-            let prior_probability = crate::hyperparameters::ITERATION_TO_PRIOR_PROBABILITY
-                .get(&iteration_num)
-                .ok_or_else(|| {
-                    format!(
-                        "Iteration {:?} not found in ITERATION_TO_PRIOR_PROBABILITY",
-                        iteration_num
-                    )
-                })?;
-            target_total_probability += *prior_probability;
-        }
-        assert!(
-            target_total_probability < 1.0,
-            "Total prior probability must be less than 1.0, but got {}",
-            target_total_probability
-        );
         // try to change here
         // let normalization_factor = target_total_probability/ current_total_probability;
         let normalization_factor = if current_total_probability >= 1.0 {
@@ -294,56 +238,150 @@ pub fn update_posterior(grid: &mut ProbaGrid) -> Result<(), String> {
         } else {
             1.0
         };
-        for trace_info in trace_infos {
-            let mut temp_posterior = trace_info.temp_posterior.borrow_mut();
-            {
-                let temp_posterior = *temp_posterior
-                    .as_ref()
-                    .expect("Temporary posterior must be set before updating the final posterior");
-                let mut posterior_normalized = trace_info.posterior_normalized.borrow_mut();
-                *posterior_normalized = Some(temp_posterior * normalization_factor);
-            }
-            // reset the temporary posterior
-            *temp_posterior = None;
+        for trace_id in trace_ids {
+            posteriors.push((trace_id, temp_posteriors[&trace_id] * normalization_factor));
         }
     }
+    for (trace_id, posterior) in posteriors {
+        grid.traces.get_mut(&trace_id).unwrap().posterior_normalized = Some(posterior);
+    }
     Ok(())
 }
 
+/// The posterior of a trace before it is normalized with the other traces of its pad pair
+fn temp_posterior(
+    grid: &ProbaGrid,
+    trace_id: TraceID,
+    config: &RouterConfig,
+) -> Result<f64, String> {
+    let trace_info = &grid.traces[&trace_id];
+    let adjacent_traces = grid
+        .trace_collision_adjacency
+        .get(&trace_id)
+        .ok_or_else(|| {
+            format!(
+                "Trace ID {:?} not found in trace_collision_adjacency",
+                trace_id
+            )
+        })?;
+    // multiplied in a fixed order, so that a seed reproduces the posteriors bit for bit
+    let mut adjacent_traces: Vec<&TraceID> = adjacent_traces.iter().collect();
+    adjacent_traces.sort();
+    let mut proba_product = 1.0;
+    for adjacent_trace_id in adjacent_traces {
+        let adjacent_trace_info = grid.traces.get(adjacent_trace_id).ok_or_else(|| {
+            format!(
+                "Adjacent Trace ID {:?} not found in traces",
+                adjacent_trace_id
+            )
+        })?;
+        // get num traces in the same iteration
+        let num_traces_in_the_same_iteration =
+            grid.get_num_traces_in_the_same_iteration(*adjacent_trace_id);
+        let probability_normalized = adjacent_trace_info.get_posterior_normalized_with_fallback(
+            &grid.prior_schedule,
+            num_traces_in_the_same_iteration,
+        );
+        let one_minus_proba = 1.0 - probability_normalized;
+        assert!(
+            one_minus_proba > 0.0,
+            "One minus probability must be greater than 0"
+        );
+        proba_product *= one_minus_proba;
+    }
+    let target_posterior = proba_product;
+    assert!(
+        target_posterior >= 0.0 && target_posterior <= 1.0,
+        "Target posterior must be between 0 and 1"
+    );
+    // get num traces in the same iteration
+    let num_traces_in_the_same_iteration = grid.get_num_traces_in_the_same_iteration(trace_id);
+    let current_posterior = trace_info.get_posterior_normalized_with_fallback(
+        &grid.prior_schedule,
+        num_traces_in_the_same_iteration,
+    );
+    let opportunity_cost = target_posterior / current_posterior;
+    let score = trace_info.get_score(config);
+    let posterior_unnormalized = 1.0
+        * f64::powf(score, config.score_weight)
+        * f64::powf(opportunity_cost, config.opportunity_cost_weight);
+    let posterior_normalized = trace_info
+        .get_normalized_prior_probability(&grid.prior_schedule, num_traces_in_the_same_iteration)
+        * posterior_unnormalized;
+    Ok(posterior_normalized)
+}
+
 pub fn initialize_proba_grid(input: ProbaGridProblem) -> Result<ProbaGrid, String> {
+    initialize_proba_grid_with_decomposition(input, NetDecomposition::AllPairs)
+}
+
+pub fn initialize_proba_grid_with_decomposition(
+    input: ProbaGridProblem,
+    decomposition: NetDecomposition,
+) -> Result<ProbaGrid, String> {
     let ProbaGridProblem {
         width,
         height,
         nets,
         net_to_pads,
+        design_rules,
+        keepouts,
+        seed,
     } = input;
 
     let mut pad_pair_id_generator = (0..).map(PadPairID);
     let mut pad_pairs: HashMap<PadPairID, PadPair> = HashMap::new();
-    let net_to_pad_pairs = net_to_pads
-        .iter()
+    // pad pair IDs are handed out in the order of the nets, so that a seed reproduces the same run
+    let mut sorted_net_to_pads: Vec<(&NetID, &HashSet<Point>)> = net_to_pads.iter().collect();
+    sorted_net_to_pads.sort_by_key(|(net_id, _)| **net_id);
+    let net_to_pad_pairs = sorted_net_to_pads
+        .into_iter()
         .map(|(net_id, pad_set)| {
             let mut pairs_set = HashSet::new();
-            let points_vec: Vec<_> = pad_set.iter().cloned().collect();
-            for i in 0..points_vec.len() {
-                for j in (i + 1)..points_vec.len() {
-                    let point_pair = PointPair::new(points_vec[i], points_vec[j]);
-                    let start = point_pair.start();
-                    let end = point_pair.end();
-                    assert_ne!(
-                        start, end,
-                        "Pad pair start and end points must be different"
-                    );
-                    let pad_pair_id = pad_pair_id_generator.next().unwrap();
-                    let pad_pair = PadPair {
-                        net_id: *net_id,
-                        pad_pair_id,
-                        start,
-                        end,
-                    };
-                    pairs_set.insert(pad_pair_id);
-                    pad_pairs.insert(pad_pair_id, pad_pair);
+            let pad_set: BTreeSet<Point> = pad_set.iter().cloned().collect();
+            let point_pairs: Vec<PointPair> = match decomposition {
+                NetDecomposition::AllPairs => {
+                    let points_vec: Vec<_> = pad_set.iter().cloned().collect();
+                    let mut point_pairs = vec![];
+                    for i in 0..points_vec.len() {
+                        for j in (i + 1)..points_vec.len() {
+                            point_pairs.push(PointPair::new(points_vec[i], points_vec[j]));
+                        }
+                    }
+                    point_pairs
                 }
+                NetDecomposition::MinimumSpanningTree => rectilinear_spanning_tree(&pad_set),
+                NetDecomposition::SteinerTree => {
+                    let blocked: HashSet<Point> = net_to_pads
+                        .iter()
+                        .filter(|(other_net_id, _)| *other_net_id != net_id)
+                        .flat_map(|(_, points)| points.iter().cloned())
+                        .chain(
+                            keepouts
+                                .iter()
+                                .flat_map(|keepout| keepout.cells(width, height, 1))
+                                .map(|layer_point| layer_point.point),
+                        )
+                        .collect();
+                    rectilinear_steiner_tree(&pad_set, &blocked)
+                }
+            };
+            for point_pair in point_pairs {
+                let start = point_pair.start();
+                let end = point_pair.end();
+                assert_ne!(
+                    start, end,
+                    "Pad pair start and end points must be different"
+                );
+                let pad_pair_id = pad_pair_id_generator.next().unwrap();
+                let pad_pair = PadPair {
+                    net_id: *net_id,
+                    pad_pair_id,
+                    start,
+                    end,
+                };
+                pairs_set.insert(pad_pair_id);
+                pad_pairs.insert(pad_pair_id, pad_pair);
             }
             (net_id.clone(), pairs_set)
         })
@@ -357,14 +395,19 @@ pub fn initialize_proba_grid(input: ProbaGridProblem) -> Result<ProbaGrid, Strin
         height,
         nets,
         net_to_pads,
+        design_rules,
+        keepouts,
+        prior_schedule: PriorSchedule::default(),
+        rng: ChaCha8Rng::seed_from_u64(seed),
         net_to_pad_pairs,
         pad_pairs,
         visited_traces: BTreeSet::new(),
         traces: HashMap::new(),
         pad_pair_to_traces,
         trace_collision_adjacency: HashMap::new(),
+        cell_to_traces: HashMap::new(),
         next_iteration: NonZeroUsize::new(1).unwrap(), // Start with the first iteration
-        trace_id_generator: Box::new((0..).map(TraceID)),
+        next_trace_id: TraceID(0),
     };
     Ok(grid)
 }
@@ -385,234 +428,584 @@ pub fn initialize_proba_grid(input: ProbaGridProblem) -> Result<ProbaGrid, Strin
 // straight: 70% (pull: score, opportunity cost), detour once: 30%*70%, detour twice: ...
 // all traces belonging to "detour once" will be grouped together and has a total probability of 1-sum of straight probability
 // the sum probability will be allocated based on score,
-pub fn sample_new_traces(grid: &mut ProbaGrid) -> Result<(), String> {
+pub fn sample_new_traces(grid: &mut ProbaGrid, config: &RouterConfig) -> Result<(), String> {
+    let mut cursor = start_sampling(grid);
+    finish_sampling(grid, &mut cursor, config)
+}
+
+/// Starts a sampling pass that visits the nets in the order of their IDs
+pub fn start_sampling(grid: &ProbaGrid) -> SamplingCursor {
+    let mut net_to_pad_pairs: Vec<(&NetID, &HashSet<PadPairID>)> =
+        grid.net_to_pad_pairs.iter().collect();
+    net_to_pad_pairs.sort_by_key(|(net_id, _)| **net_id);
+    let remaining_pad_pairs = net_to_pad_pairs
+        .into_iter()
+        .flat_map(|(net_id, pad_pair_ids)| {
+            let mut pad_pair_ids: Vec<PadPairID> = pad_pair_ids.iter().cloned().collect();
+            pad_pair_ids.sort();
+            pad_pair_ids
+                .into_iter()
+                .map(move |pad_pair_id| (*net_id, pad_pair_id))
+        })
+        .collect();
+    SamplingCursor {
+        remaining_pad_pairs,
+        new_traces: Vec::new(),
+    }
+}
+
+/// Samples the remaining pad pairs of the pass, the same as calling sample_next_net until the pass is complete.
+/// The nets are sampled in parallel, since they are only sampled against the traces of the previous passes
+pub fn finish_sampling(
+    grid: &mut ProbaGrid,
+    cursor: &mut SamplingCursor,
+    config: &RouterConfig,
+) -> Result<(), String> {
+    // every net gets a seed of its own, drawn in the order sample_next_net would draw them
+    let mut remaining_nets: Vec<(NetID, Vec<PadPairID>, u64)> = vec![];
+    for (net_id, pad_pair_id) in cursor.remaining_pad_pairs.drain(..) {
+        match remaining_nets.last_mut() {
+            Some((last_net_id, pad_pair_ids, _)) if *last_net_id == net_id => {
+                pad_pair_ids.push(pad_pair_id)
+            }
+            _ => remaining_nets.push((net_id, vec![pad_pair_id], grid.rng.random())),
+        }
+    }
+    let grid_ref: &ProbaGrid = grid;
+    let candidates = remaining_nets
+        .par_iter()
+        .map(|(net_id, pad_pair_ids, seed)| {
+            let mut rng = ChaCha8Rng::seed_from_u64(*seed);
+            generate_candidates(grid_ref, *net_id, pad_pair_ids, config, &mut rng)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    for ((net_id, _, _), candidates) in remaining_nets.into_iter().zip(candidates) {
+        let new_traces = register_candidates(grid, net_id, candidates);
+        cursor.new_traces.extend(new_traces);
+    }
+    end_pass_if_complete(grid, cursor);
+    Ok(())
+}
+
+/// Samples all remaining pad pairs of the next net, returns whether the pass is complete
+pub fn sample_next_net(
+    grid: &mut ProbaGrid,
+    cursor: &mut SamplingCursor,
+    config: &RouterConfig,
+) -> Result<bool, String> {
+    if let Some((net_id, _)) = cursor.remaining_pad_pairs.front().cloned() {
+        let pad_pair_ids: Vec<PadPairID> = cursor
+            .remaining_pad_pairs
+            .iter()
+            .take_while(|(next_net_id, _)| *next_net_id == net_id)
+            .map(|(_, pad_pair_id)| *pad_pair_id)
+            .collect();
+        let new_traces = sample_pad_pairs(grid, net_id, &pad_pair_ids, config)?;
+        cursor.remaining_pad_pairs.drain(..pad_pair_ids.len());
+        cursor.new_traces.extend(new_traces);
+    }
+    Ok(end_pass_if_complete(grid, cursor))
+}
+
+/// Samples the next pad pair on its own, returns whether the pass is complete
+pub fn sample_next_pair(
+    grid: &mut ProbaGrid,
+    cursor: &mut SamplingCursor,
+    config: &RouterConfig,
+) -> Result<bool, String> {
+    if let Some((net_id, pad_pair_id)) = cursor.remaining_pad_pairs.front().cloned() {
+        let new_traces = sample_pad_pairs(grid, net_id, &[pad_pair_id], config)?;
+        cursor.remaining_pad_pairs.pop_front();
+        cursor.new_traces.extend(new_traces);
+    }
+    Ok(end_pass_if_complete(grid, cursor))
+}
+
+fn end_pass_if_complete(grid: &mut ProbaGrid, cursor: &mut SamplingCursor) -> bool {
+    if !cursor.remaining_pad_pairs.is_empty() {
+        return false;
+    }
+    add_new_traces(grid, std::mem::take(&mut cursor.new_traces));
+    true
+}
+
+/// A path found for a pad pair, before it is given a trace ID
+struct Candidate {
+    pad_pair_id: PadPairID,
+    trace_path: TracePath,
+    trace_directions: Vec<Direction>,
+    trace_length: f64,
+}
+
+/// Samples new traces for some pad pairs of a net against traces sampled for the other nets.
+/// The traces are not added to the grid, since the other nets of the pass have to be sampled against the same traces
+fn sample_pad_pairs(
+    grid: &mut ProbaGrid,
+    net_id: NetID,
+    pad_pair_ids: &[PadPairID],
+    config: &RouterConfig,
+) -> Result<Vec<TraceInfo>, String> {
+    let mut rng = ChaCha8Rng::seed_from_u64(grid.rng.random());
+    let candidates = generate_candidates(grid, net_id, pad_pair_ids, config, &mut rng)?;
+    Ok(register_candidates(grid, net_id, candidates))
+}
+
+/// Gives the new candidates trace IDs, in the order they were found
+fn register_candidates(
+    grid: &mut ProbaGrid,
+    net_id: NetID,
+    candidates: Vec<Candidate>,
+) -> Vec<TraceInfo> {
+    let mut new_traces = Vec::new();
+    for candidate in candidates {
+        if !grid.visited_traces.insert(candidate.trace_path.clone()) {
+            continue;
+        }
+        let pad_pair = &grid.pad_pairs[&candidate.pad_pair_id];
+        let trace_id = grid.next_trace_id;
+        grid.next_trace_id = TraceID(trace_id.0 + 1);
+        new_traces.push(TraceInfo {
+            net_id,
+            pad_pair_id: candidate.pad_pair_id,
+            trace_id,
+            start: pad_pair.start,
+            end: pad_pair.end,
+            trace_path: candidate.trace_path,
+            trace_directions: candidate.trace_directions,
+            trace_length: candidate.trace_length,
+            iteration: grid.next_iteration,
+            posterior_normalized: None, // No posterior normalized in the first iteration
+        });
+    }
+    new_traces
+}
+
+// the extra cost of the orthogonal and the diagonal cells of the paths already found
+type CellPenalties = (HashMap<LayerPoint, f64>, HashMap<LayerPoint, f64>);
+
+/// Finds new paths for the pad pairs of a net, the pad pairs being routed in parallel.
+/// Only reads the grid, so that the nets of a pass can be sampled in parallel as well
+fn generate_candidates(
+    grid: &ProbaGrid,
+    net_id: NetID,
+    pad_pair_ids: &[PadPairID],
+    config: &RouterConfig,
+    rng: &mut ChaCha8Rng,
+) -> Result<Vec<Candidate>, String> {
     let ProbaGrid {
         width,
         height,
-        nets: _,
-        net_to_pads: _,
+        design_rules,
+        keepouts,
+        prior_schedule,
+        net_to_pads,
         net_to_pad_pairs,
         pad_pairs,
         visited_traces,
         traces,
         pad_pair_to_traces,
-        trace_collision_adjacency,
         next_iteration,
-        trace_id_generator,
+        ..
     } = grid;
-    // sample new traces for each net
-    let mut new_traces: Vec<TraceInfo> = Vec::new();
-    for (net_id, pad_pair_ids) in net_to_pad_pairs.iter() {
-        println!("Sampling new traces for net ID: {:?}", net_id);
-        let mut num_generated_traces: HashMap<PadPairID, usize> = pad_pair_ids
+    let net_id = &net_id;
+    let mut candidates: Vec<Candidate> = Vec::new();
+    let mut found_paths: HashSet<TracePath> = HashSet::new();
+    println!("Sampling new traces for net ID: {:?}", net_id);
+    let mut num_generated_traces: HashMap<PadPairID, usize> = pad_pair_ids
+        .iter()
+        .map(|pad_pair_id| (*pad_pair_id, 0))
+        .collect();
+    // cells of the paths already found for a pad pair get more expensive, so that the next attempt finds another path
+    let mut reuse_penalties: HashMap<PadPairID, CellPenalties> = HashMap::new();
+    let mut generation_attempts: usize = 0;
+    // while there is a pad pair that has less than MAX_TRACES traces
+    while num_generated_traces
+        .values()
+        .any(|&count| count < config.max_traces_per_iteration)
+        && generation_attempts < config.max_generation_attempts
+    {
+        println!("Generation attempt: {}", generation_attempts + 1);
+        generation_attempts += 1;
+        // randomly generate a trace for each pad pair of other nets (in a rare case the trace will not be generated)
+        // the pad pairs and their candidates are sorted, so that a seed always draws the same traces
+        let mut other_pad_pair_ids: Vec<PadPairID> = net_to_pad_pairs
             .iter()
-            .map(|pad_pair_id| (*pad_pair_id, 0))
+            .filter(|(other_net_id, _)| *other_net_id != net_id)
+            .flat_map(|(_, pad_pair_ids)| pad_pair_ids.iter().cloned())
             .collect();
-        let mut generation_attempts: usize = 0;
-        // while there is a pad pair that has less than MAX_TRACES traces
-        while num_generated_traces
-            .values()
-            .any(|&count| count < MAX_TRACES_PER_ITERATION)
-            && generation_attempts < MAX_GENERATION_ATTEMPTS
-        {
-            println!("Generation attempt: {}", generation_attempts + 1);
-            generation_attempts += 1;
-            // randomly generate a trace for each pad pair of other nets (in a rare case the trace will not be generated)
-            let obstacle_traces: HashMap<PadPairID, Option<TraceID>> = net_to_pad_pairs.iter()
-                .filter(|(other_net_id, _)| *other_net_id != net_id)
-                .flat_map(|(_, pad_pair_ids)| {
-                    pad_pair_ids.iter().map::<Result<(PadPairID, Option<TraceID>),String>, _>(|pad_pair_id| {
-                        let candidate_trace_ids = pad_pair_to_traces.get(pad_pair_id).unwrap();
-                        // use flat map to merge a hashmap into a vector, removing the keys
-                        let candidate_trace_ids = candidate_trace_ids.iter()
-                            .flat_map(|(_, trace_ids)|trace_ids.iter())
-                            .cloned()
-                            .collect::<Vec<_>>();
-
-                        let mut sum_probability = 0.0;
-                        let mut probabilities: Vec<f64> = Vec::new();
-                        // get the sum probability and all the probabilities of the candidate traces
-                        for candidate_trace_id in candidate_trace_ids.iter(){
-                            let candidate_trace = traces.get(candidate_trace_id).unwrap();
-                            // we need a normalized fallback probability
-                            let posterior_normalized = candidate_trace.posterior_normalized.borrow();
-                            let posterior_normalized = posterior_normalized.as_ref()
-                                .ok_or_else(|| format!("Posterior normalized for trace ID {:?} is None. Call update posterior before calling sample new traces", candidate_trace_id))
-                                .map_err(|e| e)?;
-                            sum_probability += *posterior_normalized;
-                            probabilities.push(*posterior_normalized);
-                        }
-                        let mut assumed_sum_probability = 0.0;
-                        for iteration in (1..next_iteration.get()).map(|i| NonZeroUsize::new(i).unwrap()) {
-                            let prior_probability = crate::hyperparameters::ITERATION_TO_PRIOR_PROBABILITY
-                                .get(&iteration)
-                                .ok_or_else(|| format!("Iteration {:?} not found in ITERATION_TO_PRIOR_PROBABILITY", iteration))?;
-                            assumed_sum_probability += *prior_probability;
-                        }
-                        // assert!(f64::abs(sum_probability - assumed_sum_probability) < 1e-6, 
-                        //     "Sum of probabilities {} does not match assumed sum probability {}", 
-                        //     sum_probability, assumed_sum_probability);
-                        probabilities.push(1.0 - sum_probability); // add the probability of not choosing any trace
-                        let dist = WeightedIndex::new(probabilities)
-                            .map_err(|e| format!("Failed to create WeightedIndex: {}", e))?;
-                        let mut rng = rand::rng();
-                        let index = dist.sample(&mut rng);
-                        let chosen_trace_id = if index < candidate_trace_ids.len() {
-                            Some(candidate_trace_ids[index])
-                        } else {
-                            None // No trace chosen
-                        };
-                        Ok((*pad_pair_id, chosen_trace_id))
-                    })
-                })
-                .collect::<Result<HashMap<_, _>, _>>()
-                .map_err(|e|e)?;
-            // create a Dijkstra model that contains all the obstacles from other nets
-            // this can be reused for all pad pairs in this net
-            let mut obstacles: HashSet<Point> = HashSet::new();
-            let mut diagonal_obstacles: HashSet<Point> = HashSet::new();
-            for (_, trace_id) in obstacle_traces.iter() {
-                if let Some(trace_id) = trace_id {
-                    let trace_info = traces
-                        .get(trace_id)
-                        .ok_or_else(|| format!("Trace ID {:?} not found in traces", trace_id))?;
-                    obstacles.extend(trace_info.trace_path.covered.iter().cloned());
-                    diagonal_obstacles
-                        .extend(trace_info.trace_path.diagonal_covered.iter().cloned());
-                }
+        other_pad_pair_ids.sort();
+        let obstacle_traces: HashMap<PadPairID, Option<TraceID>> = other_pad_pair_ids.iter()
+            .map::<Result<(PadPairID, Option<TraceID>),String>, _>(|pad_pair_id| {
+                    let candidate_trace_ids = pad_pair_to_traces.get(pad_pair_id).unwrap();
+                    // use flat map to merge a hashmap into a vector, removing the keys
+                    let mut candidate_trace_ids = candidate_trace_ids.iter()
+                        .flat_map(|(_, trace_ids)|trace_ids.iter())
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    candidate_trace_ids.sort();
+
+                    let mut sum_probability = 0.0;
+                    let mut probabilities: Vec<f64> = Vec::new();
+                    // get the sum probability and all the probabilities of the candidate traces
+                    for candidate_trace_id in candidate_trace_ids.iter(){
+                        let candidate_trace = traces.get(candidate_trace_id).unwrap();
+                        // we need a normalized fallback probability
+                        let posterior_normalized = candidate_trace.posterior_normalized.as_ref()
+                            .ok_or_else(|| format!("Posterior normalized for trace ID {:?} is None. Call update posterior before calling sample new traces", candidate_trace_id))
+                            .map_err(|e| e)?;
+                        sum_probability += *posterior_normalized;
+                        probabilities.push(*posterior_normalized);
+                    }
+                    let assumed_sum_probability = prior_schedule.total_prior_probability(next_iteration.get() - 1);
+                    // assert!(f64::abs(sum_probability - assumed_sum_probability) < 1e-6, 
+                    //     "Sum of probabilities {} does not match assumed sum probability {}", 
+                    //     sum_probability, assumed_sum_probability);
+                    probabilities.push((1.0 - sum_probability).max(0.0)); // add the probability of not choosing any trace
+                    let dist = WeightedIndex::new(probabilities)
+                        .map_err(|e| format!("Failed to create WeightedIndex: {}", e))?;
+                    let index = dist.sample(&mut *rng);
+                    let chosen_trace_id = if index < candidate_trace_ids.len() {
+                        Some(candidate_trace_ids[index])
+                    } else {
+                        None // No trace chosen
+                    };
+                    Ok((*pad_pair_id, chosen_trace_id))
+            })
+            .collect::<Result<HashMap<_, _>, _>>()
+            .map_err(|e|e)?;
+        // create a Dijkstra model that contains all the obstacles from other nets
+        // this can be reused for all pad pairs in this net
+        let mut obstacles: HashSet<LayerPoint> = inflate(
+            keepouts
+                .iter()
+                .flat_map(|keepout| keepout.cells(*width, *height, 1)),
+            design_rules.trace_radius(net_id),
+        );
+        // pads of other nets, as in Grid::pad_obstacles
+        for (other, pads) in net_to_pads.iter().filter(|(other, _)| *other != net_id) {
+            obstacles.extend(inflate(
+                pads.iter().map(|point| LayerPoint {
+                    point: *point,
+                    layer: 0,
+                }),
+                design_rules.pad_inflation(net_id, other),
+            ));
+        }
+        let mut diagonal_obstacles: HashSet<LayerPoint> = HashSet::new();
+        for (_, trace_id) in obstacle_traces.iter() {
+            if let Some(trace_id) = trace_id {
+                let trace_info = traces
+                    .get(trace_id)
+                    .ok_or_else(|| format!("Trace ID {:?} not found in traces", trace_id))?;
+                // the probabilistic grid is single layered, so vias only block layer 0
+                let via_points = trace_info.trace_path.vias.iter().map(|point| LayerPoint {
+                    point: *point,
+                    layer: 0,
+                });
+                // keep the widths of both traces and the clearance between the nets apart
+                obstacles.extend(inflate(
+                    trace_info
+                        .trace_path
+                        .covered
+                        .iter()
+                        .cloned()
+                        .chain(via_points),
+                    design_rules.trace_inflation(net_id, &trace_info.net_id),
+                ));
+                diagonal_obstacles.extend(trace_info.trace_path.diagonal_covered.iter().cloned());
             }
-            let dijkstra_model = DijkstraModel {
-                width: *width,
-                height: *height,
-                obstacles,
-                diagonal_obstacles,
-                start: Point { x: 0, y: 0 }, // Placeholder, will be set for each pad pair
-                end: Point { x: 0, y: 0 },   // Placeholder, will be set for each pad pair
-            };
-            for pad_pair_id in pad_pair_ids.iter() {
-                if num_generated_traces.get(pad_pair_id).unwrap() >= &MAX_TRACES_PER_ITERATION {
-                    continue; // Skip if the maximum number of traces for this pad pair is reached
-                }
+        }
+        // a small random cost on every cell breaks the ties between equally good paths differently in every attempt
+        let mut cell_costs: HashMap<LayerPoint, f64> = HashMap::new();
+        for y in 0..*height {
+            for x in 0..*width {
+                let jitter = rng.random::<f64>() * config.candidate_cost_jitter;
+                cell_costs.insert(LayerPoint::new(x, y, 0), jitter);
+            }
+        }
+        let dijkstra_model = DijkstraModel {
+            width: *width,
+            height: *height,
+            num_layers: 1,
+            obstacles,
+            diagonal_obstacles,
+            start: Point { x: 0, y: 0 }, // Placeholder, will be set for each pad pair
+            start_layers: BTreeSet::from([0]),
+            goals: HashSet::new(), // Placeholder, will be set for each pad pair
+            costs: config.path_costs(),
+            search_mode: SearchMode::AStar,
+            cell_costs,
+            diagonal_cell_costs: HashMap::new(),
+            allowed_layers: BTreeSet::from([0]),
+            vias_allowed: false, // the probabilistic grid is single layered
+        };
+        let mut routed_pad_pairs = vec![];
+        for pad_pair_id in pad_pair_ids.iter() {
+            if num_generated_traces[pad_pair_id] >= config.max_traces_per_iteration {
+                continue; // Skip if the maximum number of traces for this pad pair is reached
+            }
+            let pad_pair = pad_pairs.get(pad_pair_id).ok_or_else(|| {
+                format!("PadPairID {:?} not found in net_to_pad_pairs", pad_pair_id)
+            })?;
+            routed_pad_pairs.push((*pad_pair_id, pad_pair));
+        }
+        let results: Vec<(PadPairID, Result<DijkstraResult, String>)> = routed_pad_pairs
+            .par_iter()
+            .map(|(pad_pair_id, pad_pair)| {
                 let mut dijkstra_model_copy = dijkstra_model.clone();
-                let pad_pair = pad_pairs.get(pad_pair_id).ok_or_else(|| {
-                    format!("PadPairID {:?} not found in net_to_pad_pairs", pad_pair_id)
-                })?;
                 dijkstra_model_copy.start = pad_pair.start;
-                dijkstra_model_copy.end = pad_pair.end;
-                let result = dijkstra_model_copy.run();
-                let result = match result {
-                    Ok(res) => res,
-                    Err(e) => {
-                        println!(
-                            "Dijkstra's algorithm failed for pad pair ID {:?}: {}",
-                            pad_pair_id, e
-                        );
-                        continue; // Skip this pad pair if Dijkstra's algorithm fails
+                dijkstra_model_copy.goals = pad_goals(pad_pair.end, &BTreeSet::from([0]));
+                if let Some((penalties, diagonal_penalties)) = reuse_penalties.get(pad_pair_id) {
+                    for (point, penalty) in penalties.iter() {
+                        *dijkstra_model_copy.cell_costs.entry(*point).or_default() += penalty;
                     }
-                };
-                let trace_path = result.trace_path;
-                if visited_traces.contains(&trace_path) {
-                    println!("Trace path already visited, skipping");
-                    continue; // Skip if the trace path has already been visited
-                } 
-                visited_traces.insert(trace_path.clone());
-                // cannot add the new traces directly to the current container
-                let trace_id = trace_id_generator.next().unwrap();
-                let trace_info = TraceInfo {
-                    net_id: *net_id,
-                    pad_pair_id: *pad_pair_id,
-                    trace_id,
-                    start: pad_pair.start,
-                    end: pad_pair.end,
-                    trace_path,
-                    trace_directions: result.trace_directions,
-                    trace_length: result.distance,
-                    iteration: *next_iteration,
-                    prior_probability_cache: RefCell::new(None), // No prior probability cache in the first iteration
-                    posterior_normalized: RefCell::new(None), // No posterior normalized in the first iteration
-                    score_cache: RefCell::new(None), // No score cache in the first iteration
-                    temp_posterior: RefCell::new(None), // No temporary posterior unnormalized in the first iteration
-                };
-
-                // traces.insert(trace_id, trace_info);
-                // pad_pair_to_traces.get_mut(pad_pair_id).unwrap()
-                //     .entry(IterationNum(*next_iteration))
-                //     .or_default()
-                //     .insert(trace_id);
-                new_traces.push(trace_info);
-
-                let num = num_generated_traces.get_mut(pad_pair_id).unwrap();
-                *num += 1; // Increment the number of generated traces for this pad pair
+                    dijkstra_model_copy.diagonal_cell_costs = diagonal_penalties.clone();
+                }
+                (*pad_pair_id, dijkstra_model_copy.run())
+            })
+            .collect();
+        // the results are handled in the order of the pad pairs, so that the thread scheduling does not matter
+        for (pad_pair_id, result) in results {
+            let result = match result {
+                Ok(res) => res,
+                Err(e) => {
+                    println!(
+                        "Dijkstra's algorithm failed for pad pair ID {:?}: {}",
+                        pad_pair_id, e
+                    );
+                    continue; // Skip this pad pair if Dijkstra's algorithm fails
+                }
+            };
+            if let Some(max_length) = design_rules.max_length(net_id) {
+                if result.distance > max_length {
+                    println!(
+                        "Trace for pad pair ID {:?} exceeds the max length of its net class, skipping",
+                        pad_pair_id
+                    );
+                    continue; // Skip traces the net class does not allow
+                }
+            }
+            let trace_path = result.trace_path;
+            let (penalties, diagonal_penalties) = reuse_penalties.entry(pad_pair_id).or_default();
+            for point in trace_path.covered.iter() {
+                *penalties.entry(*point).or_default() += config.candidate_reuse_penalty;
+            }
+            for point in trace_path.diagonal_covered.iter() {
+                *diagonal_penalties.entry(*point).or_default() += config.candidate_reuse_penalty;
+            }
+            if visited_traces.contains(&trace_path) || !found_paths.insert(trace_path.clone()) {
+                println!("Trace path already visited, skipping");
+                continue; // Skip if the trace path has already been visited
             }
+            candidates.push(Candidate {
+                pad_pair_id,
+                trace_path,
+                trace_directions: result.trace_directions,
+                trace_length: result.distance,
+            });
+
+            let num = num_generated_traces.get_mut(&pad_pair_id).unwrap();
+            *num += 1; // Increment the number of generated traces for this pad pair
         }
     }
-    for trace_info in new_traces.iter() {
+    Ok(candidates)
+}
+
+/// Adds the traces of a completed pass to the grid and moves on to the next iteration.
+/// Only the new traces are checked for collisions, against the traces near them in cell_to_traces
+fn add_new_traces(grid: &mut ProbaGrid, new_traces: Vec<TraceInfo>) {
+    let ProbaGrid {
+        net_to_pad_pairs,
+        design_rules,
+        traces,
+        pad_pair_to_traces,
+        trace_collision_adjacency,
+        cell_to_traces,
+        next_iteration,
+        ..
+    } = grid;
+    let net_ids: Vec<NetID> = net_to_pad_pairs.keys().cloned().collect();
+    for trace_info in new_traces.into_iter() {
         let pad_pair_id = trace_info.pad_pair_id;
         let trace_id = trace_info.trace_id;
-        // Insert the trace into the pad_pair_to_traces map
-        let old = traces.insert(trace_id, trace_info.clone());
-        assert!(
-            old.is_none(),
-            "Trace ID {:?} already exists in traces",
-            trace_id
-        );
+        // two crossing diagonals come within one cell of each other without sharing one
+        let search_distance = net_ids
+            .iter()
+            .filter(|net_id| **net_id != trace_info.net_id)
+            .map(|net_id| design_rules.trace_inflation(&trace_info.net_id, net_id))
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let nearby_points: HashSet<Point> = inflate(
+            trace_points(&trace_info).map(|point| LayerPoint { point, layer: 0 }),
+            search_distance,
+        )
+        .into_iter()
+        .map(|layer_point| layer_point.point)
+        .collect();
+        let nearby_traces: BTreeSet<TraceID> = nearby_points
+            .iter()
+            .filter_map(|point| cell_to_traces.get(point))
+            .flat_map(|trace_ids| trace_ids.iter().cloned())
+            .collect();
+        let mut colliding_traces = HashSet::new();
+        for other_trace_id in nearby_traces {
+            let other_trace = traces
+                .get(&other_trace_id)
+                .expect("Trace ID not found in traces");
+            if other_trace.net_id == trace_info.net_id {
+                continue;
+            }
+            let collide = trace_info.trace_path.collides_within(
+                &other_trace.trace_path,
+                design_rules.trace_inflation(&trace_info.net_id, &other_trace.net_id),
+            );
+            if collide {
+                colliding_traces.insert(other_trace_id);
+                trace_collision_adjacency
+                    .get_mut(&other_trace_id)
+                    .unwrap()
+                    .insert(trace_id);
+            }
+        }
+        trace_collision_adjacency.insert(trace_id, colliding_traces);
+        for point in trace_points(&trace_info) {
+            cell_to_traces.entry(point).or_default().insert(trace_id);
+        }
         pad_pair_to_traces
             .get_mut(&pad_pair_id)
             .unwrap()
             .entry(IterationNum(*next_iteration))
             .or_default()
             .insert(trace_id);
+        let old = traces.insert(trace_id, trace_info);
+        assert!(
+            old.is_none(),
+            "Trace ID {:?} already exists in traces",
+            trace_id
+        );
     }
-    // update trace collision adjacency
-    *trace_collision_adjacency = traces
-        .iter()
-        .map(|(trace_id, _)| (trace_id.clone(), HashSet::new()))
-        .collect();
-    let net_to_traces: HashMap<NetID, HashMap<TraceID, &TraceInfo>> = net_to_pad_pairs
+    // Increment the iteration number for the next round of sampling
+    *next_iteration = NonZeroUsize::new(next_iteration.get() + 1).unwrap();
+}
+
+/// The cells a trace is indexed under in cell_to_traces
+fn trace_points(trace_info: &TraceInfo) -> impl Iterator<Item = Point> + '_ {
+    trace_info
+        .trace_path
+        .covered
         .iter()
-        .map(|(net_id, pad_pair_ids)| {
-            let trace_id_to_info: HashMap<TraceID, &TraceInfo> = pad_pair_ids
-                .iter()
-                .flat_map(|pad_pair_id| {
-                    pad_pair_to_traces
-                        .get(pad_pair_id)
-                        .unwrap()
-                        .iter()
-                        .flat_map(|(_, trace_ids)| trace_ids.iter())
-                        .cloned()
-                })
-                .map(|trace_id| {
-                    let trace_info = traces.get(&trace_id).expect("Trace ID not found in traces");
-                    (trace_id, trace_info)
-                })
-                .collect();
-            (*net_id, trace_id_to_info)
-        })
-        .collect();
+        .map(|layer_point| layer_point.point)
+        .chain(trace_info.trace_path.vias.iter().cloned())
+}
 
-    let traces_vec = net_to_traces.into_values().collect::<Vec<_>>();
-    for i in 0..traces_vec.len() {
-        for j in (i + 1)..traces_vec.len() {
-            let trace_set_a = &traces_vec[i];
-            let trace_set_b = &traces_vec[j];
-            for (trace_id_a, trace_a) in trace_set_a {
-                for (trace_id_b, trace_b) in trace_set_b {
-                    let collide = trace_a.trace_path.collides_with(&trace_b.trace_path);
-                    if collide {
-                        trace_collision_adjacency
-                            .get_mut(trace_id_a)
-                            .unwrap()
-                            .insert(trace_id_b.clone());
-                        trace_collision_adjacency
-                            .get_mut(trace_id_b)
-                            .unwrap()
-                            .insert(trace_id_a.clone());
-                    }
-                }
-            }
+/// When solve_proba_grid stops alternating sampling and posterior updates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConvergenceCriterion {
+    pub tolerance: f64, // converged once no posterior changes more than this in an iteration, new traces counting from 0
+    pub max_iterations: usize, // gives up after this many iterations even if the posteriors still move
+}
+
+impl Default for ConvergenceCriterion {
+    fn default() -> Self {
+        ConvergenceCriterion {
+            tolerance: PROBA_CONVERGENCE_TOLERANCE,
+            max_iterations: PROBA_MAX_ITERATIONS,
         }
     }
-    // Increment the iteration number for the next round of sampling
-    *next_iteration = NonZeroUsize::new(next_iteration.get() + 1).unwrap();
-    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvergenceReport {
+    pub criterion: ConvergenceCriterion,
+    pub converged: bool,
+    pub max_posterior_changes: Vec<f64>, // the largest posterior change of every iteration the solver ran
+}
+
+impl ConvergenceReport {
+    pub fn iterations(&self) -> usize {
+        self.max_posterior_changes.len()
+    }
+    pub fn summary(&self) -> String {
+        let last_change = self.max_posterior_changes.last().cloned().unwrap_or(0.0);
+        if self.converged {
+            format!(
+                "Converged after {} iterations: the largest posterior change {:.2e} is within the tolerance {:.2e}",
+                self.iterations(),
+                last_change,
+                self.criterion.tolerance
+            )
+        } else {
+            format!(
+                "Stopped after {} iterations without converging: the largest posterior change {:.2e} exceeds the tolerance {:.2e}",
+                self.iterations(),
+                last_change,
+                self.criterion.tolerance
+            )
+        }
+    }
+}
+
+/// Alternates sampling new traces and updating the posteriors until the posteriors stabilise
+pub fn solve_proba_grid(
+    grid: &mut ProbaGrid,
+    config: &RouterConfig,
+    criterion: &ConvergenceCriterion,
+) -> Result<ConvergenceReport, String> {
+    solve_proba_grid_observed(grid, config, criterion, |_| {})
+}
+
+/// Like solve_proba_grid, calling `on_iteration` with the grid after every iteration, e.g. to take snapshots
+pub fn solve_proba_grid_observed(
+    grid: &mut ProbaGrid,
+    config: &RouterConfig,
+    criterion: &ConvergenceCriterion,
+    mut on_iteration: impl FnMut(&ProbaGrid),
+) -> Result<ConvergenceReport, String> {
+    // sampling needs the posteriors of the traces found so far
+    if grid
+        .traces
+        .values()
+        .any(|trace_info| trace_info.posterior_normalized.is_none())
+    {
+        update_posterior(grid, config)?;
+    }
+    let mut max_posterior_changes = vec![];
+    for _ in 0..criterion.max_iterations {
+        let old_posteriors: HashMap<TraceID, f64> = grid
+            .traces
+            .iter()
+            .map(|(trace_id, trace_info)| (*trace_id, trace_info.posterior_normalized.unwrap()))
+            .collect();
+        sample_new_traces(grid, config)?;
+        update_posterior(grid, config)?;
+        on_iteration(grid);
+        let max_posterior_change = grid
+            .traces
+            .iter()
+            .map(|(trace_id, trace_info)| {
+                let new_posterior = trace_info.posterior_normalized.unwrap();
+                let old_posterior = old_posteriors.get(trace_id).cloned().unwrap_or(0.0);
+                (new_posterior - old_posterior).abs()
+            })
+            .fold(0.0, f64::max);
+        println!(
+            "Iteration {}: largest posterior change {}",
+            grid.next_iteration.get() - 1,
+            max_posterior_change
+        );
+        max_posterior_changes.push(max_posterior_change);
+        if max_posterior_change <= criterion.tolerance {
+            return Ok(ConvergenceReport {
+                criterion: *criterion,
+                converged: true,
+                max_posterior_changes,
+            });
+        }
+    }
+    Ok(ConvergenceReport {
+        criterion: *criterion,
+        converged: false,
+        max_posterior_changes,
+    })
 }
@@ -0,0 +1,599 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    hash::Hash,
+    path::Path,
+};
+
+use crate::{
+    design_rules::{DesignRules, NetClass, ViaPolicy},
+    grid::{Grid, Net, Point},
+    hyperparameters::{KICAD_TRACK_WIDTH, KICAD_VIA_DRILL, KICAD_VIA_SIZE, SES_RESOLUTION},
+    keepout::{Keepout, KeepoutShape},
+    kicad::{grid_net, BoardOutline, GridMapping, KicadBoard, KicadPad, KicadPadType},
+    proba_grid::{NetID, ProbaGridProblem},
+    sexpr::SExpr,
+};
+
+/// The width and clearance of a Specctra rule in mm, None where the rule does not set them
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DsnRule {
+    pub width: Option<f64>,
+    pub clearance: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DsnClass {
+    pub name: String,
+    pub nets: Vec<String>,
+    pub rule: DsnRule,
+}
+
+/// A shape in mm with y pointing down, like the coordinates of a .kicad_pcb file
+#[derive(Debug, Clone, PartialEq)]
+pub enum DsnShape {
+    Rect { min: (f64, f64), max: (f64, f64) },
+    Polygon(Vec<(f64, f64)>), // also the points of a path
+    Circle { center: (f64, f64), radius: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DsnKeepout {
+    pub shape: DsnShape,
+    pub layer: Option<String>, // None for every signal layer
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DsnVia {
+    pub name: String, // the padstack the session refers to
+    pub diameter: f64,
+}
+
+/// A Specctra .dsn design. The pads, nets, layers and outline use the board model of .kicad_pcb
+/// files, so that both formats are snapped to the grid the same way
+#[derive(Debug, Clone, PartialEq)]
+pub struct DsnDesign {
+    pub name: String, // the name of the pcb, which the session refers to
+    pub board: KicadBoard,
+    pub keepouts: Vec<DsnKeepout>,
+    pub rule: DsnRule, // the default rule of the structure
+    pub classes: Vec<DsnClass>,
+    pub via: Option<DsnVia>, // the first via of the structure
+}
+
+// the length of a unit in mm
+fn unit_in_mm(unit: &str) -> Result<f64, String> {
+    match unit {
+        "inch" => Ok(25.4),
+        "mil" => Ok(0.0254),
+        "cm" => Ok(10.0),
+        "mm" => Ok(1.0),
+        "um" => Ok(0.001),
+        _ => Err(format!("Unknown Specctra unit {}", unit)),
+    }
+}
+
+// converts coordinates of the file to mm, flipping the y axis which points up in Specctra
+struct Units {
+    mm: f64, // the length of a file unit in mm
+}
+
+impl Units {
+    fn length(&self, value: f64) -> f64 {
+        value * self.mm
+    }
+    fn point(&self, x: f64, y: f64) -> (f64, f64) {
+        (x * self.mm, -y * self.mm)
+    }
+}
+
+// the numbers among the arguments, skipping the first `skip` ones
+fn numbers(expr: &SExpr, skip: usize) -> Vec<f64> {
+    expr.args()
+        .iter()
+        .skip(skip)
+        .filter_map(|arg| arg.as_atom()?.parse().ok())
+        .collect()
+}
+
+// a shape and its layer, e.g. (rect F.Cu 0 0 10 10) or (path pcb 0 0 0 10 0)
+fn parse_shape(expr: &SExpr, units: &Units) -> Option<(String, DsnShape)> {
+    let layer = expr.atom(0)?.to_string();
+    let points = |values: &[f64]| -> Vec<(f64, f64)> {
+        values
+            .chunks_exact(2)
+            .map(|xy| units.point(xy[0], xy[1]))
+            .collect()
+    };
+    let shape = match expr.head()? {
+        "rect" => {
+            let corners = points(&numbers(expr, 1));
+            let [(x0, y0), (x1, y1)] = corners.as_slice() else {
+                return None;
+            };
+            DsnShape::Rect {
+                min: (x0.min(*x1), y0.min(*y1)),
+                max: (x0.max(*x1), y0.max(*y1)),
+            }
+        }
+        // the first number is the aperture width
+        "polygon" | "path" => DsnShape::Polygon(points(numbers(expr, 1).get(1..)?)),
+        "circle" => {
+            let values = numbers(expr, 1);
+            let diameter = *values.first()?;
+            let center = match values.as_slice() {
+                [_, x, y] => units.point(*x, *y),
+                _ => (0.0, 0.0),
+            };
+            DsnShape::Circle {
+                center,
+                radius: units.length(diameter) / 2.0,
+            }
+        }
+        _ => return None,
+    };
+    Some((layer, shape))
+}
+
+fn shape_extent(shape: &DsnShape) -> Vec<(f64, f64)> {
+    match shape {
+        DsnShape::Rect { min, max } => vec![*min, *max],
+        DsnShape::Polygon(points) => points.clone(),
+        DsnShape::Circle { center, radius } => vec![
+            (center.0 - radius, center.1 - radius),
+            (center.0 + radius, center.1 + radius),
+        ],
+    }
+}
+
+fn parse_rule(expr: Option<&SExpr>, units: &Units) -> DsnRule {
+    let Some(rule) = expr else {
+        return DsnRule::default();
+    };
+    DsnRule {
+        width: rule
+            .child("width")
+            .and_then(|width| width.number(0))
+            .map(|width| units.length(width)),
+        // clearances with a (type ...) only apply to some objects, e.g. smd_smd
+        clearance: rule
+            .children("clearance")
+            .find(|clearance| clearance.child("type").is_none())
+            .and_then(|clearance| clearance.number(0))
+            .map(|clearance| units.length(clearance)),
+    }
+}
+
+// the number of cells a trace of this width spans
+fn width_cells(width: f64, pitch: f64) -> usize {
+    ((width / pitch - 1e-9).ceil() as usize).max(1)
+}
+
+// the number of free cells between traces of this width so that their copper keeps the clearance
+fn clearance_cells(clearance: f64, width: f64, pitch: f64) -> usize {
+    ((clearance + width) / pitch - 1e-9).ceil().max(1.0) as usize - 1
+}
+
+impl DsnDesign {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        // the parser section declares the quote character with a lone quote, which is not a valid string
+        let root = SExpr::parse(&text.replace(r#"(string_quote ")"#, r#"(string_quote "\"")"#))?;
+        if root.head() != Some("pcb") {
+            return Err("Not a Specctra design, expected (pcb ...)".to_string());
+        }
+        // coordinates are in the unit, or in fractions of the resolution unit if there is no unit
+        let units = match (root.child("unit"), root.child("resolution")) {
+            (Some(unit), _) => Units {
+                mm: unit_in_mm(unit.atom(0).unwrap_or(""))?,
+            },
+            (None, Some(resolution)) => Units {
+                mm: unit_in_mm(resolution.atom(0).unwrap_or(""))?
+                    / resolution.number(1).unwrap_or(1.0),
+            },
+            (None, None) => Units { mm: 0.0254 }, // the Specctra default is mil
+        };
+        let structure = root
+            .child("structure")
+            .ok_or("The design has no structure")?;
+        let copper_layers: Vec<String> = structure
+            .children("layer")
+            .filter_map(|layer| layer.atom(0))
+            .map(str::to_string)
+            .collect();
+        let outline_points: Vec<(f64, f64)> = structure
+            .children("boundary")
+            .flat_map(|boundary| boundary.args())
+            .filter_map(|shape| parse_shape(shape, &units))
+            .flat_map(|(_, shape)| shape_extent(&shape))
+            .collect();
+        let outline = if outline_points.is_empty() {
+            None
+        } else {
+            let fold = |f: fn(f64, f64) -> f64, start: f64, coordinate: fn(&(f64, f64)) -> f64| {
+                outline_points.iter().map(coordinate).fold(start, f)
+            };
+            Some(BoardOutline {
+                min_x: fold(f64::min, f64::INFINITY, |p| p.0),
+                min_y: fold(f64::min, f64::INFINITY, |p| p.1),
+                max_x: fold(f64::max, f64::NEG_INFINITY, |p| p.0),
+                max_y: fold(f64::max, f64::NEG_INFINITY, |p| p.1),
+            })
+        };
+        // via_keepout only forbids vias, which the grid cannot express
+        let keepouts = structure
+            .args()
+            .iter()
+            .filter(|item| matches!(item.head(), Some("keepout") | Some("wire_keepout")))
+            .flat_map(|keepout| keepout.args())
+            .filter_map(|shape| parse_shape(shape, &units))
+            .map(|(layer, shape)| DsnKeepout {
+                shape,
+                layer: if layer == "signal" { None } else { Some(layer) },
+            })
+            .collect();
+
+        let library = root.child("library");
+        let padstacks: HashMap<&str, Vec<(String, DsnShape)>> = library
+            .into_iter()
+            .flat_map(|library| library.children("padstack"))
+            .filter_map(|padstack| {
+                let shapes = padstack
+                    .children("shape")
+                    .flat_map(|shape| shape.args())
+                    .filter_map(|shape| parse_shape(shape, &units))
+                    .collect();
+                Some((padstack.atom(0)?, shapes))
+            })
+            .collect();
+        // image name to its pins: padstack, pin id and position
+        let images: HashMap<&str, Vec<(&str, &str, f64, f64)>> = library
+            .into_iter()
+            .flat_map(|library| library.children("image"))
+            .filter_map(|image| {
+                let pins = image
+                    .children("pin")
+                    .filter_map(|pin| {
+                        // (pin padstack [(rotate angle)] id x y)
+                        let atoms: Vec<&str> =
+                            pin.args().iter().filter_map(SExpr::as_atom).collect();
+                        let [padstack, id, x, y] = atoms.as_slice() else {
+                            return None;
+                        };
+                        Some((*padstack, *id, x.parse().ok()?, y.parse().ok()?))
+                    })
+                    .collect();
+                Some((image.atom(0)?, pins))
+            })
+            .collect();
+
+        let network = root.child("network");
+        let nets: BTreeMap<usize, String> = network
+            .into_iter()
+            .flat_map(|network| network.children("net"))
+            .filter_map(|net| net.atom(0))
+            .enumerate()
+            .map(|(i, name)| (i + 1, name.to_string()))
+            .collect();
+        let pin_to_net: HashMap<&str, usize> = network
+            .into_iter()
+            .flat_map(|network| network.children("net"))
+            .enumerate()
+            .flat_map(|(i, net)| {
+                net.child("pins")
+                    .into_iter()
+                    .flat_map(|pins| pins.args())
+                    .filter_map(SExpr::as_atom)
+                    .map(move |pin| (pin, i + 1))
+            })
+            .collect();
+        let classes = network
+            .into_iter()
+            .flat_map(|network| network.children("class"))
+            .map(|class| DsnClass {
+                name: class.atom(0).unwrap_or("").to_string(),
+                nets: class
+                    .args()
+                    .iter()
+                    .skip(1)
+                    .filter_map(SExpr::as_atom)
+                    .map(str::to_string)
+                    .collect(),
+                rule: parse_rule(class.child("rule"), &units),
+            })
+            .collect();
+
+        let mut pads = Vec::new();
+        for component in root
+            .child("placement")
+            .into_iter()
+            .flat_map(|placement| placement.children("component"))
+        {
+            let image_name = component.atom(0).unwrap_or("");
+            let pins = images
+                .get(image_name)
+                .ok_or_else(|| format!("Unknown image {}", image_name))?;
+            for place in component.children("place") {
+                // (place reference x y side rotation), unplaced components have no position
+                let reference = place.atom(0).unwrap_or("");
+                let (Some(x), Some(y)) = (place.number(1), place.number(2)) else {
+                    continue;
+                };
+                let back = place.atom(3) == Some("back");
+                let (sin, cos) = place.number(4).unwrap_or(0.0).to_radians().sin_cos();
+                for (padstack, id, pin_x, pin_y) in pins {
+                    // the image of a component on the back is mirrored before it is rotated
+                    let pin_x = if back { -pin_x } else { *pin_x };
+                    let (board_x, board_y) =
+                        units.point(x + pin_x * cos - pin_y * sin, y + pin_x * sin + pin_y * cos);
+                    let layers: BTreeSet<&str> = padstacks
+                        .get(padstack)
+                        .into_iter()
+                        .flatten()
+                        .map(|(layer, _)| layer.as_str())
+                        .collect();
+                    let (pad_type, layers) = match layers.iter().next() {
+                        Some(layer) if layers.len() == 1 && *layer != "signal" => {
+                            // padstacks describe the front side, the back side uses the opposite layer
+                            let layer = match copper_layers.iter().position(|l| l == layer) {
+                                Some(i) if back => {
+                                    copper_layers[copper_layers.len() - 1 - i].clone()
+                                }
+                                _ => layer.to_string(),
+                            };
+                            (KicadPadType::Smd, vec![layer])
+                        }
+                        _ => (KicadPadType::ThroughHole, vec!["*.Cu".to_string()]),
+                    };
+                    pads.push(KicadPad {
+                        footprint: reference.to_string(),
+                        number: id.to_string(),
+                        pad_type,
+                        x: board_x,
+                        y: board_y,
+                        layers,
+                        net: pin_to_net
+                            .get(format!("{}-{}", reference, id).as_str())
+                            .copied(),
+                    });
+                }
+            }
+        }
+
+        let via = structure
+            .child("via")
+            .and_then(|via| via.atom(0))
+            .map(|name| {
+                let diameter = padstacks
+                    .get(name)
+                    .into_iter()
+                    .flatten()
+                    .find_map(|(_, shape)| match shape {
+                        DsnShape::Circle { radius, .. } => Some(radius * 2.0),
+                        _ => None,
+                    })
+                    .unwrap_or(KICAD_VIA_SIZE);
+                DsnVia {
+                    name: name.to_string(),
+                    diameter,
+                }
+            });
+        Ok(DsnDesign {
+            name: root.atom(0).unwrap_or("").to_string(),
+            board: KicadBoard {
+                nets,
+                copper_layers,
+                pads,
+                outline,
+            },
+            keepouts,
+            rule: parse_rule(structure.child("rule"), &units),
+            classes,
+            via,
+        })
+    }
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text)
+    }
+    fn default_width(&self) -> f64 {
+        self.rule.width.unwrap_or(KICAD_TRACK_WIDTH)
+    }
+    fn class_of(&self, net_name: &str) -> Option<&DsnClass> {
+        self.classes
+            .iter()
+            .find(|class| class.nets.iter().any(|net| net == net_name))
+    }
+    /// The wire width of a net in mm, from its class or the default rule
+    pub fn net_width(&self, net_name: &str) -> f64 {
+        self.class_of(net_name)
+            .and_then(|class| class.rule.width)
+            .unwrap_or(self.default_width())
+    }
+    /// The rules of the design in grid cells, one net class per Specctra class
+    pub fn design_rules<N: Clone + Eq + Hash + Ord>(
+        &self,
+        pitch: f64,
+        net: impl Fn(usize) -> N,
+    ) -> DesignRules<N> {
+        let mut design_rules = DesignRules::default();
+        let width = self.default_width();
+        design_rules.default_trace_width = width_cells(width, pitch);
+        if let Some(clearance) = self.rule.clearance {
+            design_rules.default_clearance = clearance_cells(clearance, width, pitch);
+        }
+        for class in &self.classes {
+            let class_width = class.rule.width.unwrap_or(width);
+            let clearance = class.rule.clearance.or(self.rule.clearance).unwrap_or(0.0);
+            design_rules.net_classes.insert(
+                class.name.clone(),
+                NetClass {
+                    name: class.name.clone(),
+                    trace_width: width_cells(class_width, pitch),
+                    clearance: clearance_cells(clearance, class_width, pitch),
+                    allowed_layers: None,
+                    max_length: None,
+                    via_policy: ViaPolicy::Allowed,
+                },
+            );
+            for (code, _) in self
+                .board
+                .nets
+                .iter()
+                .filter(|(_, name)| class.nets.contains(name))
+            {
+                design_rules
+                    .net_to_class
+                    .insert(net(*code), class.name.clone());
+            }
+        }
+        design_rules
+    }
+    fn grid_keepouts(&self, mapping: &GridMapping, num_layers: usize) -> Vec<Keepout> {
+        self.keepouts
+            .iter()
+            .map(|keepout| {
+                let cell = |(x, y): (f64, f64)| mapping.to_clamped_cell(x, y);
+                let shape = match &keepout.shape {
+                    DsnShape::Polygon(points) => {
+                        KeepoutShape::Polygon(points.iter().copied().map(cell).collect())
+                    }
+                    // circles are blocked by their bounding box
+                    shape => {
+                        let extent = shape_extent(shape);
+                        KeepoutShape::Rect {
+                            min: cell(extent[0]),
+                            max: cell(extent[1]),
+                        }
+                    }
+                };
+                let layers = match (&keepout.layer, num_layers) {
+                    (Some(layer), num_layers) if num_layers > 1 => Some(
+                        self.board
+                            .copper_layers
+                            .iter()
+                            .position(|l| l == layer)
+                            .into_iter()
+                            .collect(),
+                    ),
+                    _ => None,
+                };
+                Keepout { shape, layers }
+            })
+            .collect()
+    }
+    /// A naive grid with the pads, keepouts and rules of the design
+    pub fn to_grid(&self, pitch: f64) -> Result<Grid, String> {
+        let mapping = self.board.grid_mapping(pitch)?;
+        let mut grid = self.board.to_grid(pitch)?;
+        grid.keepouts
+            .extend(self.grid_keepouts(&mapping, grid.layers));
+        grid.design_rules = self.design_rules(pitch, grid_net);
+        Ok(grid)
+    }
+    /// A proba grid problem, whose single layer is blocked by the keepouts of every layer
+    pub fn to_proba_grid_problem(&self, pitch: f64) -> Result<ProbaGridProblem, String> {
+        let mapping = self.board.grid_mapping(pitch)?;
+        let mut problem = self.board.to_proba_grid_problem(pitch)?;
+        problem.keepouts.extend(self.grid_keepouts(&mapping, 1));
+        problem.design_rules = self.design_rules(pitch, NetID);
+        Ok(problem)
+    }
+    /// A Specctra session with the wires and vias of a grid built from this design
+    pub fn session(&self, grid: &Grid, pitch: f64) -> Result<SExpr, String> {
+        let mapping = self.board.grid_mapping(pitch)?;
+        let net_codes = self.board.grid_net_codes(grid, &mapping)?;
+        // session coordinates are in resolution units, SES_RESOLUTION per um
+        let length =
+            |mm: f64| SExpr::Atom(format!("{}", (mm * 1000.0 * SES_RESOLUTION as f64).round()));
+        let coordinates = |point: Point| {
+            let (x, y) = mapping.to_board(point);
+            [length(x), length(-y)]
+        };
+        let resolution = || {
+            SExpr::list(
+                "resolution",
+                vec![
+                    SExpr::Atom("um".to_string()),
+                    SExpr::Atom(SES_RESOLUTION.to_string()),
+                ],
+            )
+        };
+        let layer_name = |layer: usize| SExpr::Str(self.board.copper_layers[layer].clone());
+        let via = self.via.clone().unwrap_or_else(|| DsnVia {
+            name: format!(
+                "Via[0-{}]_{}:{}_um",
+                self.board.copper_layers.len().saturating_sub(1),
+                KICAD_VIA_SIZE * 1000.0,
+                KICAD_VIA_DRILL * 1000.0
+            ),
+            diameter: KICAD_VIA_SIZE,
+        });
+
+        let mut nets: Vec<(&Net, &usize)> = net_codes.iter().collect();
+        nets.sort();
+        let mut network_out = Vec::new();
+        let mut has_vias = false;
+        for (net, code) in nets {
+            let name = &self.board.nets[code];
+            let width = self.net_width(name);
+            let mut items = vec![SExpr::Str(name.clone())];
+            for layer in 0..grid.layers {
+                for (start, end) in grid.trace_segments(net, layer) {
+                    let mut path = vec![layer_name(layer), length(width)];
+                    path.extend(coordinates(start));
+                    path.extend(coordinates(end));
+                    items.push(SExpr::list("wire", vec![SExpr::list("path", path)]));
+                }
+            }
+            let mut vias: Vec<&Point> = grid.vias.get(net).into_iter().flatten().collect();
+            vias.sort();
+            for point in vias {
+                has_vias = true;
+                let mut args = vec![SExpr::Str(via.name.clone())];
+                args.extend(coordinates(*point));
+                items.push(SExpr::list("via", args));
+            }
+            network_out.push(SExpr::list("net", items));
+        }
+
+        let mut routes = vec![resolution()];
+        if has_vias {
+            let mut padstack = vec![SExpr::Str(via.name.clone())];
+            for layer in 0..self.board.copper_layers.len() {
+                padstack.push(SExpr::list(
+                    "shape",
+                    vec![SExpr::list(
+                        "circle",
+                        vec![
+                            layer_name(layer),
+                            length(via.diameter),
+                            SExpr::Atom("0".to_string()),
+                            SExpr::Atom("0".to_string()),
+                        ],
+                    )],
+                ));
+            }
+            padstack.push(SExpr::list("attach", vec![SExpr::Atom("off".to_string())]));
+            routes.push(SExpr::list(
+                "library_out",
+                vec![SExpr::list("padstack", padstack)],
+            ));
+        }
+        routes.push(SExpr::list("network_out", network_out));
+        Ok(SExpr::list(
+            "session",
+            vec![
+                SExpr::Str(self.name.clone()),
+                SExpr::list("base_design", vec![SExpr::Str(self.name.clone())]),
+                SExpr::list("placement", vec![resolution()]),
+                SExpr::list("was_is", Vec::new()),
+                SExpr::list("routes", routes),
+            ],
+        ))
+    }
+    pub fn save_session(&self, grid: &Grid, pitch: f64, path: &Path) -> Result<(), String> {
+        let session = self.session(grid, pitch)?;
+        std::fs::write(path, format!("{}\n", session))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
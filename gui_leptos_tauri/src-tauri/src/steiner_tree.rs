@@ -0,0 +1,114 @@
+use std::collections::{BTreeSet, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::grid::{Point, PointPair};
+
+/// How the pads of a multi-pad net are broken into pad pairs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetDecomposition {
+    AllPairs,            // every pair of pads of the net, O(n^2) pairs
+    MinimumSpanningTree, // the edges of the minimum spanning tree over the pads
+    SteinerTree,         // the edges of a rectilinear Steiner tree, which may add junction points
+}
+
+fn rectilinear_distance(a: Point, b: Point) -> usize {
+    a.x.abs_diff(b.x) + a.y.abs_diff(b.y)
+}
+
+// Prim's algorithm on the complete graph with rectilinear distances, returns the total length and the edges
+fn rectilinear_mst(points: &[Point]) -> (usize, Vec<(usize, usize)>) {
+    if points.len() < 2 {
+        return (0, vec![]);
+    }
+    let mut in_tree = vec![false; points.len()];
+    let mut best_distance = vec![usize::MAX; points.len()];
+    let mut best_parent = vec![0; points.len()];
+    let mut total = 0;
+    let mut edges = vec![];
+    best_distance[0] = 0;
+    for _ in 0..points.len() {
+        let next = (0..points.len())
+            .filter(|i| !in_tree[*i])
+            .min_by_key(|i| (best_distance[*i], *i))
+            .unwrap();
+        in_tree[next] = true;
+        if next != 0 {
+            total += best_distance[next];
+            edges.push((best_parent[next], next));
+        }
+        for i in 0..points.len() {
+            let distance = rectilinear_distance(points[next], points[i]);
+            if !in_tree[i] && distance < best_distance[i] {
+                best_distance[i] = distance;
+                best_parent[i] = next;
+            }
+        }
+    }
+    (total, edges)
+}
+
+/// The edges of the rectilinear minimum spanning tree over the points
+pub fn rectilinear_spanning_tree(points: &BTreeSet<Point>) -> Vec<PointPair> {
+    let points: Vec<Point> = points.iter().cloned().collect();
+    let (_, edges) = rectilinear_mst(&points);
+    edges
+        .into_iter()
+        .map(|(a, b)| PointPair::new(points[a], points[b]))
+        .collect()
+}
+
+/// Approximates the rectilinear Steiner minimal tree of the terminals with the iterated 1-Steiner heuristic:
+/// Steiner points are picked from the Hanan grid one at a time as long as they shorten the spanning tree.
+/// Points in `blocked` (e.g. pads of other nets) are never used as Steiner points.
+pub fn rectilinear_steiner_tree(
+    terminals: &BTreeSet<Point>,
+    blocked: &HashSet<Point>,
+) -> Vec<PointPair> {
+    let mut points: Vec<Point> = terminals.iter().cloned().collect();
+    let xs: BTreeSet<usize> = terminals.iter().map(|point| point.x).collect();
+    let ys: BTreeSet<usize> = terminals.iter().map(|point| point.y).collect();
+    let mut candidates: BTreeSet<Point> = xs
+        .iter()
+        .flat_map(|x| ys.iter().map(move |y| Point { x: *x, y: *y }))
+        .filter(|point| !terminals.contains(point) && !blocked.contains(point))
+        .collect();
+    let (mut current_length, _) = rectilinear_mst(&points);
+    loop {
+        let mut best: Option<(usize, Point)> = None;
+        for candidate in candidates.iter() {
+            points.push(*candidate);
+            let (length, _) = rectilinear_mst(&points);
+            points.pop();
+            if length < best.map(|(length, _)| length).unwrap_or(current_length) {
+                best = Some((length, *candidate));
+            }
+        }
+        match best {
+            Some((length, point)) => {
+                points.push(point);
+                candidates.remove(&point);
+                current_length = length;
+            }
+            None => break,
+        }
+    }
+    // Steiner points with degree 2 or less never shorten a rectilinear tree, so they are dropped
+    loop {
+        let (_, edges) = rectilinear_mst(&points);
+        let removable = (terminals.len()..points.len())
+            .find(|i| edges.iter().filter(|(a, b)| *a == *i || *b == *i).count() <= 2);
+        match removable {
+            Some(i) => {
+                points.remove(i);
+            }
+            None => {
+                return edges
+                    .into_iter()
+                    .map(|(a, b)| PointPair::new(points[a], points[b]))
+                    .collect();
+            }
+        }
+    }
+}
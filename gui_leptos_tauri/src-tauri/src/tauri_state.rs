@@ -3,18 +3,28 @@ use std::{collections::HashMap, sync::Mutex};
 use lazy_static::lazy_static;
 
 use crate::{
+    board_file::BoardFile,
+    design_rules::DesignRules,
     grid::Grid,
+    hyperparameters::{DEFAULT_PROBA_SEED, NAIVE_GRID_LAYERS},
     proba_grid::{ProbaGridProblem, ProbaGridState},
+    router_config::RouterConfig,
 };
 
 pub struct TauriState {
     pub naive_grid: Grid,
     pub proba_grid: ProbaGridState,
+    pub router_config: RouterConfig,
+    pub naive_board: Option<BoardFile>, // the board the naive grid was opened from
+    pub proba_board: Option<BoardFile>, // the board the proba grid was opened from
 }
 
 lazy_static! {
     pub static ref TAURI_STATE: Mutex<TauriState> = Mutex::new(TauriState {
-        naive_grid: Grid::new(10, 10), // Initialize with a default grid size
-        proba_grid: ProbaGridState::Uninitialized { input: ProbaGridProblem{width: 10, height: 10, nets: HashMap::new(), net_to_pads: HashMap::new()} }
+        naive_grid: Grid::new_with_layers(10, 10, NAIVE_GRID_LAYERS), // Initialize with a default grid size
+        proba_grid: ProbaGridState::Uninitialized { input: ProbaGridProblem{width: 10, height: 10, nets: HashMap::new(), net_to_pads: HashMap::new(), design_rules: DesignRules::default(), keepouts: Vec::new(), seed: DEFAULT_PROBA_SEED} },
+        router_config: RouterConfig::default(),
+        naive_board: None,
+        proba_board: None,
     });
 }
@@ -0,0 +1,93 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use gui_leptos_tauri_lib::{
+    dijkstra::{DijkstraModel, PathCosts, SearchMode},
+    grid::{LayerPoint, Point},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+fn random_model(rng: &mut StdRng, search_mode: SearchMode) -> DijkstraModel {
+    let (width, height) = (20, 15);
+    let start = Point { x: 0, y: 0 };
+    let end = Point {
+        x: width - 1,
+        y: height - 1,
+    };
+    let obstacles: HashSet<LayerPoint> = (0..80)
+        .map(|_| LayerPoint::new(rng.random_range(0..width), rng.random_range(0..height), 0))
+        .filter(|p| p.point != start && p.point != end)
+        .collect();
+    DijkstraModel {
+        width,
+        height,
+        num_layers: 1,
+        obstacles,
+        diagonal_obstacles: HashSet::new(),
+        start,
+        start_layers: BTreeSet::from([0]),
+        goals: HashSet::from([LayerPoint {
+            point: end,
+            layer: 0,
+        }]),
+        costs: PathCosts::default(),
+        search_mode,
+        cell_costs: HashMap::new(),
+        diagonal_cell_costs: HashMap::new(),
+        allowed_layers: BTreeSet::from([0]),
+        vias_allowed: true,
+    }
+}
+
+#[test]
+fn test_a_star_matches_dijkstra_distance() {
+    for seed in 0..20 {
+        let dijkstra = random_model(&mut StdRng::seed_from_u64(seed), SearchMode::Dijkstra);
+        let a_star = random_model(&mut StdRng::seed_from_u64(seed), SearchMode::AStar);
+        match (dijkstra.run(), a_star.run()) {
+            (Ok(dijkstra_result), Ok(a_star_result)) => {
+                assert!(
+                    (dijkstra_result.distance - a_star_result.distance).abs() < 1e-9,
+                    "A* distance {} differs from Dijkstra distance {} for seed {}",
+                    a_star_result.distance,
+                    dijkstra_result.distance,
+                    seed
+                );
+                let covered = &a_star_result.trace_path.covered;
+                assert!(covered.contains(&LayerPoint {
+                    point: a_star.start,
+                    layer: 0
+                }));
+                assert!(covered.contains(&LayerPoint {
+                    point: a_star_result.end,
+                    layer: 0
+                }));
+                assert!(a_star.goals.contains(&LayerPoint {
+                    point: a_star_result.end,
+                    layer: 0
+                }));
+            }
+            (Err(_), Err(_)) => {}
+            _ => panic!("A* and Dijkstra disagree on reachability for seed {}", seed),
+        }
+    }
+}
+
+#[test]
+fn test_a_star_expands_fewer_nodes() {
+    // on an open grid the heuristic leads A* straight to the goal, Dijkstra searches in every direction
+    let mut dijkstra = random_model(&mut StdRng::seed_from_u64(0), SearchMode::Dijkstra);
+    dijkstra.obstacles.clear();
+    let a_star = DijkstraModel {
+        search_mode: SearchMode::AStar,
+        ..dijkstra.clone()
+    };
+    let dijkstra_result = dijkstra.run().unwrap();
+    let a_star_result = a_star.run().unwrap();
+    assert!((dijkstra_result.distance - a_star_result.distance).abs() < 1e-9);
+    assert!(
+        a_star_result.expanded < dijkstra_result.expanded,
+        "A* expanded {} nodes, Dijkstra {}",
+        a_star_result.expanded,
+        dijkstra_result.expanded
+    );
+}
@@ -0,0 +1,40 @@
+use std::collections::HashSet;
+
+use shared::interface_types::Color;
+
+use gui_leptos_tauri_lib::{
+    grid::Point,
+    hyperparameters::MAX_TRACES_PER_ITERATION,
+    proba_grid::{NetID, ProbaGridProblem},
+    router_config::RouterConfig,
+    solve_proba_grid::{initialize_proba_grid, sample_new_traces, update_posterior},
+};
+
+#[test]
+fn test_lone_pad_pair_gets_distinct_candidates() {
+    // without other nets the sampled obstacles never change, so only the penalties can move the path
+    let mut problem = ProbaGridProblem::new(8, 6, 0);
+    let red = Color { r: 255, g: 0, b: 0 };
+    for point in [Point { x: 1, y: 2 }, Point { x: 6, y: 3 }] {
+        problem.insert_pad(NetID(0), point, red.clone(), red.clone());
+    }
+    let config = RouterConfig::default();
+    let mut grid = initialize_proba_grid(problem).unwrap();
+    for iteration in 1..=2 {
+        sample_new_traces(&mut grid, &config).unwrap();
+        update_posterior(&mut grid, &config).unwrap();
+        let num_traces: usize = grid
+            .pad_pair_to_traces
+            .values()
+            .flat_map(|iterations| iterations.values())
+            .map(|trace_ids| trace_ids.len())
+            .sum();
+        assert_eq!(num_traces, iteration * MAX_TRACES_PER_ITERATION);
+    }
+    let paths: HashSet<_> = grid
+        .traces
+        .values()
+        .map(|trace_info| trace_info.trace_path.clone())
+        .collect();
+    assert_eq!(paths.len(), grid.traces.len());
+}
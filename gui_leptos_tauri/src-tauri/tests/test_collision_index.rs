@@ -0,0 +1,73 @@
+use std::collections::{HashMap, HashSet};
+
+use shared::interface_types::Color;
+
+use gui_leptos_tauri_lib::{
+    grid::Point,
+    proba_grid::{NetID, ProbaGridProblem, TraceID},
+    router_config::RouterConfig,
+    solve_proba_grid::{initialize_proba_grid, sample_new_traces, update_posterior},
+};
+
+#[test]
+fn test_incremental_adjacency_matches_all_pairs() {
+    let mut problem = ProbaGridProblem::new(12, 9, 3);
+    let nets = [
+        (
+            Color { r: 255, g: 0, b: 0 },
+            [Point { x: 0, y: 4 }, Point { x: 11, y: 4 }],
+        ),
+        (
+            Color { r: 0, g: 255, b: 0 },
+            [Point { x: 3, y: 0 }, Point { x: 8, y: 8 }],
+        ),
+        (
+            Color { r: 0, g: 0, b: 255 },
+            [Point { x: 8, y: 0 }, Point { x: 3, y: 8 }],
+        ),
+    ];
+    for (i, (color, pads)) in nets.into_iter().enumerate() {
+        for pad in pads {
+            problem.insert_pad(NetID(i), pad, color.clone(), color.clone());
+        }
+    }
+    // a wide net collides with traces that do not share a cell with it
+    problem
+        .design_rules
+        .set_net_class(NetID(0), "power")
+        .unwrap();
+    let config = RouterConfig::default();
+    let mut grid = initialize_proba_grid(problem).unwrap();
+    for _ in 0..3 {
+        sample_new_traces(&mut grid, &config).unwrap();
+        update_posterior(&mut grid, &config).unwrap();
+    }
+
+    let mut expected: HashMap<TraceID, HashSet<TraceID>> = grid
+        .traces
+        .keys()
+        .map(|trace_id| (*trace_id, HashSet::new()))
+        .collect();
+    for (trace_id_a, trace_a) in grid.traces.iter() {
+        for (trace_id_b, trace_b) in grid.traces.iter() {
+            let distance = grid
+                .design_rules
+                .trace_inflation(&trace_a.net_id, &trace_b.net_id);
+            if trace_a.net_id != trace_b.net_id
+                && trace_a
+                    .trace_path
+                    .collides_within(&trace_b.trace_path, distance)
+            {
+                expected.get_mut(trace_id_a).unwrap().insert(*trace_id_b);
+            }
+        }
+    }
+    assert!(expected.values().any(|colliding| !colliding.is_empty()));
+    assert_eq!(grid.trace_collision_adjacency, expected);
+
+    for (trace_id, trace_info) in grid.traces.iter() {
+        for layer_point in trace_info.trace_path.covered.iter() {
+            assert!(grid.cell_to_traces[&layer_point.point].contains(trace_id));
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::BTreeSet;
+
+use gui_leptos_tauri_lib::{
+    design_rules::DesignRules,
+    grid::{Grid, LayerPoint, Net},
+    naive_route::naive_route,
+    proba_grid::TracePath,
+};
+
+fn net(c: char) -> Net {
+    Net::Character {
+        pad_c: c,
+        route_c: c.to_ascii_lowercase(),
+    }
+}
+
+fn chebyshev(a: &LayerPoint, b: &LayerPoint) -> usize {
+    a.point
+        .x
+        .abs_diff(b.point.x)
+        .max(a.point.y.abs_diff(b.point.y))
+}
+
+#[test]
+fn test_inflation_is_symmetric() {
+    let mut rules: DesignRules<Net> = DesignRules::default();
+    rules.set_trace_width(net('A'), 3);
+    rules.set_clearance(&net('B'), &net('A'), 2);
+    assert_eq!(rules.clearance(&net('A'), &net('B')), 2);
+    assert_eq!(rules.clearance(&net('A'), &net('C')), 0);
+    assert_eq!(rules.trace_inflation(&net('A'), &net('B')), 3);
+    assert_eq!(rules.trace_inflation(&net('B'), &net('A')), 3);
+    assert_eq!(rules.pad_inflation(&net('B'), &net('A')), 2);
+}
+
+#[test]
+fn test_collides_within_clearance() {
+    let row = |y: usize| TracePath {
+        covered: (0..5).map(|x| LayerPoint::new(x, y, 0)).collect(),
+        diagonal_covered: BTreeSet::new(),
+        vias: BTreeSet::new(),
+    };
+    assert!(!row(0).collides_within(&row(2), 0));
+    assert!(!row(0).collides_within(&row(2), 1));
+    assert!(row(0).collides_within(&row(2), 2));
+    assert!(row(0).collides_within(&row(0), 0));
+}
+
+#[test]
+fn test_wide_trace_detours_around_other_net() {
+    // A is five cells wide, so its center line has to stay two cells away from B
+    // and goes around B instead of straight along the top row
+    let input = "\
+############
+#A        A#
+#          #
+#   B  B   #
+#          #
+#          #
+#          #
+############";
+    let mut grid = Grid::from_string(input);
+    grid.design_rules.set_trace_width(net('A'), 5);
+    let routed_grid = naive_route(grid).unwrap();
+    let a_traces = &routed_grid.traces[&net('A')];
+    let b_copper: Vec<LayerPoint> = routed_grid.traces[&net('B')].iter().cloned().collect();
+    for a in a_traces {
+        for b in &b_copper {
+            assert!(
+                chebyshev(a, b) >= 3,
+                "{:?} of A is too close to {:?} of B",
+                a,
+                b
+            );
+        }
+    }
+    assert!(a_traces.len() > 10, "A should detour around B");
+}
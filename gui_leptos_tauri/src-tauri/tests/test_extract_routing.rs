@@ -0,0 +1,75 @@
+use shared::interface_types::Color;
+
+use gui_leptos_tauri_lib::{
+    extract_routing::extract_routing,
+    grid::{Net, Point},
+    proba_grid::{NetID, ProbaGrid, ProbaGridProblem},
+    router_config::RouterConfig,
+    solve_proba_grid::{initialize_proba_grid, sample_new_traces, update_posterior},
+};
+
+const RED: Color = Color { r: 255, g: 0, b: 0 };
+const BLUE: Color = Color { r: 0, g: 0, b: 255 };
+
+fn problem(width: usize, height: usize, red: &[Point], blue: &[Point]) -> ProbaGridProblem {
+    let mut problem = ProbaGridProblem::new(width, height, 0);
+    for point in red {
+        problem.insert_pad(NetID(0), *point, RED, RED);
+    }
+    for point in blue {
+        problem.insert_pad(NetID(1), *point, BLUE, BLUE);
+    }
+    problem
+}
+
+fn solve(problem: ProbaGridProblem, passes: usize) -> ProbaGrid {
+    let config = RouterConfig::default();
+    let mut grid = initialize_proba_grid(problem).unwrap();
+    for _ in 0..passes {
+        sample_new_traces(&mut grid, &config).unwrap();
+        update_posterior(&mut grid, &config).unwrap();
+    }
+    grid
+}
+
+fn net(color: Color) -> Net {
+    Net::Color {
+        pad_color: color.clone(),
+        route_color: color,
+    }
+}
+
+#[test]
+fn test_extract_avoids_collisions() {
+    // the straight red trace runs through the blue pads, so red has to take a detour
+    let grid = solve(
+        problem(
+            7,
+            5,
+            &[Point { x: 0, y: 2 }, Point { x: 6, y: 2 }],
+            &[Point { x: 2, y: 1 }, Point { x: 4, y: 3 }],
+        ),
+        3,
+    );
+    let routed = extract_routing(&grid).unwrap();
+    let red = &routed.traces[&net(RED)];
+    let blue = &routed.traces[&net(BLUE)];
+    assert!(red.is_disjoint(blue));
+    assert!(red.iter().any(|p| p.point == Point { x: 6, y: 2 }));
+    assert!(blue.iter().any(|p| p.point == Point { x: 4, y: 3 }));
+    assert_eq!(routed.pads[&net(RED)].len(), 2);
+}
+
+#[test]
+fn test_extract_fails_when_nets_must_cross() {
+    let grid = solve(
+        problem(
+            5,
+            5,
+            &[Point { x: 0, y: 2 }, Point { x: 4, y: 2 }],
+            &[Point { x: 2, y: 0 }, Point { x: 2, y: 4 }],
+        ),
+        2,
+    );
+    assert!(extract_routing(&grid).is_err());
+}
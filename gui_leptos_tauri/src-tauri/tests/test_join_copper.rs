@@ -0,0 +1,106 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use gui_leptos_tauri_lib::{
+    dijkstra::{DijkstraModel, PathCosts, SearchMode},
+    grid::{Grid, LayerPoint, Net, Point, PointPair},
+    naive_route::{connected_copper, naive_route_with_decomposition},
+    proba_grid::TracePath,
+    steiner_tree::NetDecomposition,
+};
+use ordered_float::OrderedFloat;
+
+#[test]
+fn test_search_ends_on_nearest_goal() {
+    // a horizontal wire from (2, 8) to (12, 8), the pad at (7, 1) should drop straight onto it
+    let goals: HashSet<LayerPoint> = (2..=12).map(|x| LayerPoint::new(x, 8, 0)).collect();
+    for search_mode in [SearchMode::Dijkstra, SearchMode::AStar] {
+        let model = DijkstraModel {
+            width: 15,
+            height: 10,
+            num_layers: 1,
+            obstacles: HashSet::new(),
+            diagonal_obstacles: HashSet::new(),
+            start: Point { x: 7, y: 1 },
+            start_layers: BTreeSet::from([0]),
+            goals: goals.clone(),
+            costs: PathCosts::default(),
+            search_mode,
+            cell_costs: HashMap::new(),
+            diagonal_cell_costs: HashMap::new(),
+            allowed_layers: BTreeSet::from([0]),
+            vias_allowed: true,
+        };
+        let result = model.run().unwrap();
+        assert_eq!(result.end, Point { x: 7, y: 8 });
+        assert!((result.distance - 7.0).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn test_connected_pair_adds_no_copper() {
+    let input = "\
+###########
+#A       A#
+#         #
+#         #
+#         #
+#    A    #
+###########";
+    let grid = Grid::from_string(input);
+    let routed_grid = naive_route_with_decomposition(grid, NetDecomposition::AllPairs).unwrap();
+    let traces = routed_grid.traces.values().next().unwrap();
+    // the two diagonals are routed first, so the top pads are already joined
+    // and the longest pair must not lay a redundant wire along the top row
+    assert_eq!(traces.len(), 9);
+}
+
+#[test]
+fn test_copper_under_smd_pad_is_not_connected() {
+    // a trace on layer 1 joins the through hole pads of net A and passes under its SMD pad on layer 0
+    let net = Net::Character {
+        pad_c: 'A',
+        route_c: 'a',
+    };
+    let (left, smd, right) = (
+        Point { x: 0, y: 0 },
+        Point { x: 2, y: 0 },
+        Point { x: 4, y: 0 },
+    );
+    let mut grid = Grid::new_with_layers(5, 3, 2);
+    grid.insert_pad(net.clone(), left);
+    grid.insert_pad(net.clone(), right);
+    grid.insert_smd_pad(net.clone(), smd, 0);
+    let under = TracePath {
+        covered: (0..5).map(|x| LayerPoint::new(x, 0, 1)).collect(),
+        ..Default::default()
+    };
+    let mut routed_pairs = BTreeMap::from([(
+        (net.clone(), PointPair::new(left, right)),
+        (OrderedFloat(4.0), under.clone()),
+    )]);
+    assert!(connected_copper(&grid, &routed_pairs, &net, smd).is_empty());
+    let under_cells: HashSet<LayerPoint> = under.covered.iter().cloned().collect();
+    assert_eq!(
+        connected_copper(&grid, &routed_pairs, &net, right),
+        under_cells
+    );
+
+    // a via below the SMD pad joins it to the trace
+    let drop = TracePath {
+        covered: BTreeSet::from([
+            LayerPoint::new(2, 0, 0),
+            LayerPoint::new(2, 1, 0),
+            LayerPoint::new(2, 1, 1),
+            LayerPoint::new(2, 0, 1),
+        ]),
+        vias: BTreeSet::from([Point { x: 2, y: 1 }]),
+        ..Default::default()
+    };
+    routed_pairs.insert(
+        (net.clone(), PointPair::new(smd, right)),
+        (OrderedFloat(2.0), drop),
+    );
+    let copper = connected_copper(&grid, &routed_pairs, &net, smd);
+    assert!(copper.is_superset(&under_cells));
+    assert!(copper.contains(&LayerPoint::new(2, 1, 0)));
+}
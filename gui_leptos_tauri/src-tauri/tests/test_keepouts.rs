@@ -0,0 +1,113 @@
+use std::collections::BTreeSet;
+
+use gui_leptos_tauri_lib::{
+    grid::{Grid, LayerPoint, Net, Point},
+    keepout::{Keepout, KeepoutShape},
+    naive_route::naive_route,
+};
+
+fn net(c: char) -> Net {
+    Net::Character {
+        pad_c: c,
+        route_c: c.to_ascii_lowercase(),
+    }
+}
+
+#[test]
+fn test_polygon_contains() {
+    // a triangle with the right angle at the origin
+    let keepout = Keepout {
+        shape: KeepoutShape::Polygon(vec![
+            Point { x: 0, y: 0 },
+            Point { x: 4, y: 0 },
+            Point { x: 0, y: 4 },
+        ]),
+        layers: None,
+    };
+    assert!(keepout.contains(Point { x: 0, y: 0 }));
+    assert!(keepout.contains(Point { x: 1, y: 1 }));
+    assert!(keepout.contains(Point { x: 2, y: 2 }), "on the hypotenuse");
+    assert!(!keepout.contains(Point { x: 3, y: 2 }));
+    assert!(!keepout.contains(Point { x: 4, y: 4 }));
+    assert_eq!(keepout.cells(10, 10, 1).len(), 15);
+    // cells outside the grid are dropped
+    assert_eq!(keepout.cells(2, 2, 1).len(), 4);
+}
+
+#[test]
+fn test_parse_directive() {
+    assert_eq!(
+        Keepout::parse(&["rect", "3", "4", "1", "2", "layers", "0,1"]).unwrap(),
+        Keepout {
+            shape: KeepoutShape::Rect {
+                min: Point { x: 1, y: 2 },
+                max: Point { x: 3, y: 4 },
+            },
+            layers: Some(BTreeSet::from([0, 1])),
+        }
+    );
+    assert!(Keepout::parse(&["rect", "1", "2", "3"]).is_err());
+    assert!(Keepout::parse(&["polygon", "0", "0", "1", "1"]).is_err());
+    assert!(Keepout::parse(&["circle", "1", "1"]).is_err());
+
+    let grid = Grid::from_string(
+        "\
+#######
+#A   A#
+#     #
+#######
+keepout polygon 2 0 3 1 2 1",
+    );
+    assert_eq!(grid.keepouts.len(), 1);
+    assert_eq!(grid.keepouts[0].cells(grid.width, grid.height, 1).len(), 3);
+}
+
+#[test]
+fn test_text_keepout_round_trip() {
+    let text = "\
+#######
+#A # A#
+#  #  #
+#     #
+#######
+";
+    let grid = Grid::from_string(text);
+    assert_eq!(grid.width, 5);
+    assert_eq!(grid.height, 3);
+    assert_eq!(grid.keepouts.len(), 2);
+    assert!(grid.to_string().contains("#A # A#"));
+}
+
+#[test]
+fn test_route_around_keepout() {
+    let grid = Grid::from_string(
+        "\
+#######
+#A # A#
+#  #  #
+#     #
+#######
+",
+    );
+    let routed = naive_route(grid).unwrap();
+    let traces = &routed.traces[&net('A')];
+    assert!(traces.iter().all(|p| p.point.x != 2 || p.point.y == 2));
+    assert!(traces.contains(&LayerPoint {
+        point: Point { x: 2, y: 2 },
+        layer: 0,
+    }));
+}
+
+#[test]
+fn test_layer_keepout() {
+    // the keepout splits the top layer, so the trace has to go through the bottom layer
+    let mut grid = Grid::new_with_layers(7, 3, 2);
+    grid.insert_pad(net('A'), Point { x: 0, y: 1 });
+    grid.insert_pad(net('A'), Point { x: 6, y: 1 });
+    grid.apply_directive("keepout rect 3 0 3 2 layers 0")
+        .unwrap();
+    let routed = naive_route(grid).unwrap();
+    let traces = &routed.traces[&net('A')];
+    assert!(!traces.iter().any(|p| p.point.x == 3 && p.layer == 0));
+    assert!(traces.iter().any(|p| p.point.x == 3 && p.layer == 1));
+}
@@ -0,0 +1,166 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use gui_leptos_tauri_lib::{
+    grid::{LayerPoint, Net, Point},
+    kicad::{KicadBoard, KicadDocument},
+    naive_route::naive_route,
+    sexpr::SExpr,
+};
+
+// two footprints with a pad at each corner of a 2x2 square, pads 1 and 4 on net 1, pads 2 and 3 on net 2
+const SQUARE_BOARD: &str = r#"(kicad_pcb
+	(version 20241229)
+	(layers
+		(0 "F.Cu" signal)
+		(2 "B.Cu" signal)
+		(25 "Edge.Cuts" user)
+	)
+	(net 0 "")
+	(net 1 "A")
+	(net 2 "B")
+	(footprint "Test:Left"
+		(layer "F.Cu")
+		(at 10 20)
+		(property "Reference" "U1")
+		(pad "1" smd rect (at 1 1) (size 0.5 0.5) (layers "F.Cu") (net 1 "A"))
+		(pad "2" smd rect (at 1 3) (size 0.5 0.5) (layers "F.Cu") (net 2 "B"))
+	)
+	(footprint "Test:Right"
+		(layer "F.Cu")
+		(at 10 20)
+		(property "Reference" "U2")
+		(pad "3" smd rect (at 3 1) (size 0.5 0.5) (layers "F.Cu") (net 2 "B"))
+		(pad "4" smd rect (at 3 3) (size 0.5 0.5) (layers "F.Cu") (net 1 "A"))
+	)
+	(gr_rect (start 10 20) (end 14 24) (layer "Edge.Cuts"))
+	(segment (start 11 21) (end 13 23) (width 0.2) (layer "F.Cu") (net 1))
+)
+"#;
+
+// start, end, layer and net code
+type Segment = ((f64, f64), (f64, f64), String, usize);
+
+fn segments(items: &[SExpr]) -> Vec<Segment> {
+    items
+        .iter()
+        .filter(|item| item.head() == Some("segment"))
+        .map(|item| {
+            let point = |head: &str| {
+                let at = item.child(head).unwrap();
+                (at.number(0).unwrap(), at.number(1).unwrap())
+            };
+            (
+                point("start"),
+                point("end"),
+                item.child("layer").unwrap().atom(0).unwrap().to_string(),
+                item.child("net").unwrap().number(0).unwrap() as usize,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_sexpr_round_trip() {
+    let text = fs::read_to_string("../../kicad2grid/tutorial.kicad_pcb").unwrap();
+    let root = SExpr::parse(&text).unwrap();
+    assert_eq!(SExpr::parse(&root.to_string()).unwrap(), root);
+    assert_eq!(
+        SExpr::list(
+            "net",
+            vec![SExpr::from_f64(-0.0), SExpr::Str("a \"b\"".to_string())]
+        )
+        .to_string(),
+        "(net 0 \"a \\\"b\\\"\")"
+    );
+}
+
+#[test]
+fn test_diagonal_runs() {
+    let document = KicadDocument::parse(SQUARE_BOARD, 1.0).unwrap();
+    let mut grid = document.board.to_grid(1.0).unwrap();
+    assert_eq!((grid.width, grid.height), (5, 5));
+    // net A falls from (1, 1) to (3, 3), net B rises from (1, 3) to (3, 1)
+    let net = |x: usize, y: usize| {
+        grid.pads
+            .iter()
+            .find(|(_, points)| points.iter().any(|p| (p.x, p.y) == (x, y)))
+            .unwrap()
+            .0
+            .clone()
+    };
+    let (a, b): (Net, Net) = (net(1, 1), net(1, 3));
+    grid.traces.insert(
+        a.clone(),
+        HashSet::from([
+            LayerPoint::new(1, 1, 0),
+            LayerPoint::new(2, 2, 0),
+            LayerPoint::new(3, 3, 0),
+        ]),
+    );
+    grid.diagonal_traces.insert(
+        a,
+        HashSet::from([LayerPoint::new(1, 1, 0), LayerPoint::new(2, 2, 0)]),
+    );
+    grid.traces.insert(
+        b.clone(),
+        HashSet::from([
+            LayerPoint::new(1, 3, 0),
+            LayerPoint::new(2, 2, 1),
+            LayerPoint::new(3, 1, 1),
+        ]),
+    );
+    grid.diagonal_traces
+        .insert(b.clone(), HashSet::from([LayerPoint::new(2, 1, 1)]));
+    grid.vias.insert(b, HashSet::from([Point { x: 2, y: 2 }]));
+    let items = document.route_items(&grid).unwrap();
+    let mut segments = segments(&items);
+    segments.sort_by_key(|segment| segment.3);
+    assert_eq!(
+        segments,
+        vec![
+            ((11.0, 21.0), (13.0, 23.0), "F.Cu".to_string(), 1),
+            ((13.0, 21.0), (12.0, 22.0), "B.Cu".to_string(), 2),
+        ]
+    );
+    let via = items
+        .iter()
+        .find(|item| item.head() == Some("via"))
+        .unwrap();
+    assert_eq!(via.child("at").unwrap().number(0), Some(12.0));
+    assert_eq!(via.child("net").unwrap().atom(0), Some("2"));
+}
+
+#[test]
+fn test_export_tutorial() {
+    let document =
+        KicadDocument::open(Path::new("../../kicad2grid/tutorial.kicad_pcb"), 2.54).unwrap();
+    let grid = naive_route(document.board.to_grid(2.54).unwrap()).unwrap();
+    let items = document.route_items(&grid).unwrap();
+    let segments = segments(&items);
+    assert!(!segments.is_empty());
+    // every connected pad lies on a segment of its net
+    for pad in &document.board.pads {
+        let cell = document.mapping.to_cell(pad.x, pad.y).unwrap();
+        let (x, y) = document.mapping.to_board(cell);
+        let on_segment = segments.iter().any(|((x0, y0), (x1, y1), _, net)| {
+            let cross = (x1 - x0) * (y - y0) - (y1 - y0) * (x - x0);
+            Some(*net) == pad.net
+                && cross.abs() < 1e-6
+                && x >= x0.min(*x1) - 1e-6
+                && x <= x0.max(*x1) + 1e-6
+                && y >= y0.min(*y1) - 1e-6
+                && y <= y0.max(*y1) + 1e-6
+        });
+        assert!(on_segment, "pad {} of {}", pad.number, pad.footprint);
+    }
+
+    // the copy keeps the board and replaces the tracks of the source
+    let path = std::env::temp_dir().join("test_kicad_export_tutorial.kicad_pcb");
+    document.save_routed(&grid, &path).unwrap();
+    let text = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let routed = SExpr::parse(&text).unwrap();
+    assert_eq!(KicadBoard::from_sexpr(&routed).unwrap(), document.board);
+    assert_eq!(routed.children("segment").count(), segments.len());
+    assert!(routed.child("zone").is_some());
+}
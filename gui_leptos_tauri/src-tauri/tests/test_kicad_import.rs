@@ -0,0 +1,101 @@
+use std::{collections::BTreeSet, path::Path};
+
+use gui_leptos_tauri_lib::{
+    grid::{Net, PadKind, Point},
+    kicad::{net_color, KicadBoard, KicadPadType},
+    naive_route::naive_route,
+    proba_grid::NetID,
+    sexpr::SExpr,
+};
+
+fn tutorial() -> KicadBoard {
+    KicadBoard::load(Path::new("../../kicad2grid/tutorial.kicad_pcb")).unwrap()
+}
+
+#[test]
+fn test_parse_sexpr() {
+    let expr = SExpr::parse("(pad \"1\" smd (at 1.5 -2) (net 3 \"/a \\\"b\\\"\"))").unwrap();
+    assert_eq!(expr.head(), Some("pad"));
+    assert_eq!(expr.atom(0), Some("1"));
+    assert_eq!(expr.child("at").unwrap().number(1), Some(-2.0));
+    assert_eq!(expr.child("net").unwrap().atom(1), Some("/a \"b\""));
+    assert!(SExpr::parse("(a (b)").is_err());
+    assert!(SExpr::parse("(a) b").is_err());
+}
+
+#[test]
+fn test_parse_tutorial() {
+    let board = tutorial();
+    assert_eq!(board.copper_layers, vec!["F.Cu", "B.Cu"]);
+    assert_eq!(
+        board.nets.values().collect::<Vec<_>>(),
+        vec!["VCC", "GND", "/led"]
+    );
+    assert_eq!(board.pads.len(), 6);
+    let outline = board.outline.unwrap();
+    assert_eq!(
+        (outline.min_x, outline.min_y, outline.max_x, outline.max_y),
+        (58.0, 44.0, 103.0, 85.0)
+    );
+    // the routed segments of the tutorial end on the pads, which checks the footprint rotations
+    let pad = |footprint: &str, number: &str| {
+        board
+            .pads
+            .iter()
+            .find(|pad| pad.footprint == footprint && pad.number == number)
+            .unwrap()
+    };
+    let close = |pad: &gui_leptos_tauri_lib::kicad::KicadPad, x: f64, y: f64| {
+        (pad.x - x).abs() < 1e-6 && (pad.y - y).abs() < 1e-6
+    };
+    assert!(close(pad("D1", "2"), 65.46, 77.54));
+    assert!(close(pad("R1", "2"), 81.65, 78.0));
+    assert!(close(pad("BT1", "1"), 94.68, 60.0));
+    assert!(close(pad("BT1", "2"), 65.32, 60.0));
+    assert_eq!(pad("BT1", "1").pad_type, KicadPadType::Smd);
+    assert_eq!(pad("BT1", "1").net, Some(1));
+}
+
+#[test]
+fn test_tutorial_to_grid() {
+    let board = tutorial();
+    let grid = board.to_grid(2.54).unwrap();
+    assert_eq!((grid.width, grid.height, grid.layers), (18, 17, 2));
+    assert_eq!(grid.pads.len(), 3);
+    let vcc = grid
+        .pads
+        .iter()
+        .find(|(net, _)| matches!(net, Net::Color { pad_color, .. } if *pad_color == net_color(1)))
+        .unwrap();
+    assert_eq!(
+        vcc.1,
+        &BTreeSet::from([Point { x: 14, y: 6 }, Point { x: 14, y: 13 }])
+    );
+    // the battery holder is an SMD part on the bottom layer
+    assert_eq!(
+        grid.pad_kind(Point { x: 14, y: 6 }),
+        PadKind::Smd { layer: 1 }
+    );
+    assert_eq!(grid.pad_kind(Point { x: 14, y: 13 }), PadKind::ThroughHole);
+    naive_route(grid).unwrap();
+}
+
+#[test]
+fn test_tutorial_to_proba_grid_problem() {
+    let problem = tutorial().to_proba_grid_problem(2.54).unwrap();
+    assert_eq!((problem.width, problem.height), (18, 17));
+    assert_eq!(
+        problem.net_to_pads.keys().collect::<BTreeSet<_>>(),
+        BTreeSet::from([&NetID(1), &NetID(2), &NetID(3)])
+    );
+    assert!(problem.net_to_pads[&NetID(3)].contains(&Point { x: 3, y: 13 }));
+}
+
+#[test]
+fn test_pitch_errors() {
+    let board = tutorial();
+    assert!(board.to_grid(0.0).is_err());
+    assert!(board.to_grid(0.001).is_err(), "too many cells");
+    // at a coarse pitch the two LED pads fall on the same cell
+    assert!(board.to_grid(10.0).is_err());
+}
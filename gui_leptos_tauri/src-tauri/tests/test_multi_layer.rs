@@ -0,0 +1,52 @@
+use gui_leptos_tauri_lib::{
+    grid::{Grid, LayerPoint, Net, Point},
+    naive_route::naive_route,
+};
+
+fn wall_grid(layers: usize) -> Grid {
+    // net A has to cross a wall of SMD pads of net B on the top layer
+    let mut grid = Grid::new_with_layers(7, 5, layers);
+    let net_a = Net::Character {
+        pad_c: 'A',
+        route_c: 'a',
+    };
+    let net_b = Net::Character {
+        pad_c: 'B',
+        route_c: 'b',
+    };
+    grid.insert_smd_pad(net_a.clone(), Point { x: 0, y: 2 }, 0);
+    grid.insert_smd_pad(net_a, Point { x: 6, y: 2 }, 0);
+    for y in 0..5 {
+        grid.insert_smd_pad(net_b.clone(), Point { x: 3, y }, 0);
+    }
+    grid
+}
+
+#[test]
+fn test_single_layer_is_blocked() {
+    assert!(naive_route(wall_grid(1)).is_err());
+}
+
+#[test]
+fn test_route_through_second_layer() {
+    let routed = naive_route(wall_grid(2)).unwrap();
+    let net_a = Net::Character {
+        pad_c: 'A',
+        route_c: 'a',
+    };
+    let traces = routed.traces.get(&net_a).unwrap();
+    let vias = routed.vias.get(&net_a).unwrap();
+    assert_eq!(
+        vias.len(),
+        2,
+        "Net A should dive under the wall and come back up"
+    );
+    assert!(traces.iter().any(|p| p.point.x == 3 && p.layer == 1));
+    // both pads are on the top layer, so the route must start and end there
+    assert!(traces.contains(&LayerPoint::new(0, 2, 0)));
+    assert!(traces.contains(&LayerPoint::new(6, 2, 0)));
+    // the wall itself is never crossed on the top layer
+    assert!(traces.iter().all(|p| !(p.point.x == 3 && p.layer == 0)));
+    // vias can not be placed inside the wall
+    assert!(vias.iter().all(|p| p.x != 3));
+}
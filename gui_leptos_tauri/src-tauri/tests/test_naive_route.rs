@@ -1,6 +1,6 @@
-use std::{collections::HashMap, fs, path::Path};
+use std::fs;
 
-use gui_leptos_tauri_lib::{grid::Grid, grid::Net, grid::Point, naive_route::naive_route};
+use gui_leptos_tauri_lib::{grid::Grid, naive_route::naive_route};
 
 #[test]
 fn test_naive_route() {
@@ -22,13 +22,30 @@ fn test_naive_route() {
         let input = input_and_output[0].trim();
         let expected_output = input_and_output[1].trim();
         let grid = Grid::from_string(input);
+        let expected = Grid::from_string(expected_output);
+        assert_eq!(grid.pads, expected.pads);
         let routed_grid = naive_route(grid).unwrap();
         let output = routed_grid.to_string();
-        let output = output.trim();
         assert_eq!(
-            output, expected_output,
+            routed_grid.traces, expected.traces,
             "Output does not match expected. Output:\n{}",
             output
         );
+        assert_eq!(
+            routed_grid.diagonal_traces, expected.diagonal_traces,
+            "Output does not match expected. Output:\n{}",
+            output
+        );
+        assert_eq!(
+            routed_grid.vias, expected.vias,
+            "Output does not match expected. Output:\n{}",
+            output
+        );
+        assert_eq!(
+            Grid::try_from_string(&output).unwrap(),
+            routed_grid,
+            "Output does not parse back to the routed grid. Output:\n{}",
+            output
+        );
     }
 }
@@ -0,0 +1,109 @@
+use std::collections::BTreeSet;
+
+use gui_leptos_tauri_lib::{
+    design_rules::{NetClass, ViaPolicy},
+    grid::{Grid, Net, Point},
+    naive_route::naive_route,
+};
+
+fn net(c: char) -> Net {
+    Net::Character {
+        pad_c: c,
+        route_c: c.to_ascii_lowercase(),
+    }
+}
+
+fn wall_grid() -> Grid {
+    // net A has to cross a wall of SMD pads of net B on the top layer
+    let mut grid = Grid::new_with_layers(7, 5, 2);
+    grid.insert_smd_pad(net('A'), Point { x: 0, y: 2 }, 0);
+    grid.insert_smd_pad(net('A'), Point { x: 6, y: 2 }, 0);
+    for y in 0..5 {
+        grid.insert_smd_pad(net('B'), Point { x: 3, y }, 0);
+    }
+    grid
+}
+
+#[test]
+fn test_class_rules_and_overrides() {
+    let mut grid = Grid::from_string(
+        "\
+#######
+#A   A#
+#B   B#
+#######
+class A power
+class B signal",
+    );
+    let rules = &grid.design_rules;
+    assert_eq!(rules.net_class(&net('A')).unwrap().name, "power");
+    assert_eq!(rules.trace_width(&net('A')), 3);
+    assert_eq!(rules.trace_width(&net('B')), 1);
+    // the larger clearance of the two classes applies
+    assert_eq!(rules.clearance(&net('A'), &net('B')), 1);
+    grid.design_rules.set_trace_width(net('A'), 5);
+    grid.design_rules.set_clearance(&net('A'), &net('B'), 0);
+    assert_eq!(grid.design_rules.trace_width(&net('A')), 5);
+    assert_eq!(grid.design_rules.clearance(&net('A'), &net('B')), 0);
+    assert!(grid
+        .design_rules
+        .set_net_class(net('A'), "no_such_class")
+        .is_err());
+}
+
+#[test]
+#[should_panic(expected = "Invalid directive")]
+fn test_directive_for_missing_net() {
+    Grid::from_string(
+        "\
+#####
+#A A#
+#####
+class C power",
+    );
+}
+
+#[test]
+fn test_via_policy() {
+    let mut grid = wall_grid();
+    grid.design_rules
+        .set_net_class(net('A'), "high_speed")
+        .unwrap();
+    assert_eq!(
+        grid.design_rules.net_class(&net('A')).unwrap().via_policy,
+        ViaPolicy::Forbidden
+    );
+    assert!(
+        naive_route(grid).is_err(),
+        "A can only cross the wall with vias"
+    );
+    assert!(naive_route(wall_grid()).is_ok());
+}
+
+#[test]
+fn test_allowed_layers_and_max_length() {
+    let mut grid = Grid::new_with_layers(8, 3, 2);
+    grid.insert_pad(net('A'), Point { x: 0, y: 1 });
+    grid.insert_pad(net('A'), Point { x: 7, y: 1 });
+    grid.design_rules.net_classes.insert(
+        "bottom".to_string(),
+        NetClass {
+            name: "bottom".to_string(),
+            trace_width: 1,
+            clearance: 0,
+            allowed_layers: Some(BTreeSet::from([1])),
+            max_length: Some(7.0),
+            via_policy: ViaPolicy::Allowed,
+        },
+    );
+    grid.design_rules.set_net_class(net('A'), "bottom").unwrap();
+    let routed = naive_route(grid.clone()).unwrap();
+    assert!(routed.traces[&net('A')].iter().all(|p| p.layer == 1));
+
+    grid.design_rules
+        .net_classes
+        .get_mut("bottom")
+        .unwrap()
+        .max_length = Some(6.0);
+    assert!(naive_route(grid).is_err(), "The pads are 7 cells apart");
+}
@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+
+use shared::interface_types::Color;
+
+use gui_leptos_tauri_lib::{
+    grid::Point,
+    proba_grid::{NetID, ProbaGrid, ProbaGridProblem, TracePath},
+    router_config::RouterConfig,
+    solve_proba_grid::{
+        finish_sampling, initialize_proba_grid, sample_next_net, start_sampling, update_posterior,
+    },
+};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+fn four_net_problem() -> ProbaGridProblem {
+    let mut problem = ProbaGridProblem::new(12, 10, 11);
+    let nets = [
+        [
+            Point { x: 0, y: 1 },
+            Point { x: 11, y: 2 },
+            Point { x: 6, y: 9 },
+        ],
+        [
+            Point { x: 0, y: 5 },
+            Point { x: 11, y: 5 },
+            Point { x: 5, y: 0 },
+        ],
+        [
+            Point { x: 2, y: 9 },
+            Point { x: 9, y: 0 },
+            Point { x: 0, y: 8 },
+        ],
+        [
+            Point { x: 3, y: 3 },
+            Point { x: 8, y: 7 },
+            Point { x: 11, y: 9 },
+        ],
+    ];
+    for (i, pads) in nets.into_iter().enumerate() {
+        let color = Color {
+            r: 60 * i as u8,
+            g: 0,
+            b: 255 - 60 * i as u8,
+        };
+        for pad in pads {
+            problem.insert_pad(NetID(i), pad, color.clone(), color.clone());
+        }
+    }
+    problem
+}
+
+fn traces(grid: &ProbaGrid) -> BTreeMap<usize, (usize, TracePath, Option<u64>)> {
+    grid.traces
+        .iter()
+        .map(|(trace_id, trace_info)| {
+            (
+                trace_id.0,
+                (
+                    trace_info.pad_pair_id.0,
+                    trace_info.trace_path.clone(),
+                    trace_info.posterior_normalized.map(f64::to_bits),
+                ),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_parallel_pass_matches_stepping() {
+    assert_send_sync::<ProbaGrid>();
+    let config = RouterConfig::default();
+    let mut parallel = initialize_proba_grid(four_net_problem()).unwrap();
+    let mut stepped = initialize_proba_grid(four_net_problem()).unwrap();
+    for _ in 0..3 {
+        let mut cursor = start_sampling(&parallel);
+        finish_sampling(&mut parallel, &mut cursor, &config).unwrap();
+        update_posterior(&mut parallel, &config).unwrap();
+
+        let mut cursor = start_sampling(&stepped);
+        while !sample_next_net(&mut stepped, &mut cursor, &config).unwrap() {}
+        update_posterior(&mut stepped, &config).unwrap();
+    }
+    assert!(parallel.traces.len() > parallel.pad_pairs.len());
+    assert_eq!(traces(&parallel), traces(&stepped));
+    assert_eq!(
+        parallel.trace_collision_adjacency,
+        stepped.trace_collision_adjacency
+    );
+}
@@ -0,0 +1,56 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use gui_leptos_tauri_lib::{
+    dijkstra::{DijkstraModel, PathCosts, SearchMode},
+    grid::{LayerPoint, Point},
+};
+
+fn open_model(costs: PathCosts, search_mode: SearchMode) -> DijkstraModel {
+    DijkstraModel {
+        width: 12,
+        height: 12,
+        num_layers: 1,
+        obstacles: HashSet::from([LayerPoint::new(5, 5, 0), LayerPoint::new(6, 5, 0)]),
+        diagonal_obstacles: HashSet::new(),
+        start: Point { x: 1, y: 2 },
+        start_layers: BTreeSet::from([0]),
+        goals: HashSet::from([LayerPoint::new(10, 8, 0)]),
+        costs,
+        search_mode,
+        cell_costs: HashMap::new(),
+        diagonal_cell_costs: HashMap::new(),
+        allowed_layers: BTreeSet::from([0]),
+        vias_allowed: true,
+    }
+}
+
+#[test]
+fn test_turn_cost_removes_staircases() {
+    let without_turns = open_model(PathCosts::default(), SearchMode::AStar)
+        .run()
+        .unwrap();
+    let with_turns = open_model(PathCosts::scoring(), SearchMode::AStar)
+        .run()
+        .unwrap();
+    let turns_without = PathCosts::count_turns(&without_turns.trace_directions);
+    let turns_with = PathCosts::count_turns(&with_turns.trace_directions);
+    assert!(turns_with <= turns_without);
+    assert!(
+        turns_with <= 2,
+        "Expected at most two bends, got {}",
+        turns_with
+    );
+}
+
+#[test]
+fn test_cost_matches_scorer_objective() {
+    let costs = PathCosts::scoring();
+    for search_mode in [SearchMode::Dijkstra, SearchMode::AStar] {
+        let result = open_model(costs, search_mode).run().unwrap();
+        let objective = costs.path_cost(&result.trace_directions, result.trace_path.vias.len());
+        assert!((result.cost - objective).abs() < 1e-9);
+    }
+    let dijkstra = open_model(costs, SearchMode::Dijkstra).run().unwrap();
+    let a_star = open_model(costs, SearchMode::AStar).run().unwrap();
+    assert!((dijkstra.cost - a_star.cost).abs() < 1e-9);
+}
@@ -0,0 +1,53 @@
+use std::fs;
+
+use gui_leptos_tauri_lib::{grid::Grid, pathfinder_route::pathfinder_route};
+
+fn assert_no_shared_cells(grid: &Grid) {
+    let traces: Vec<_> = grid.traces.iter().collect();
+    for i in 0..traces.len() {
+        for j in (i + 1)..traces.len() {
+            assert!(
+                traces[i].1.is_disjoint(traces[j].1),
+                "Nets {:?} and {:?} share cells",
+                traces[i].0,
+                traces[j].0
+            );
+        }
+    }
+}
+
+#[test]
+fn test_pathfinder_negotiates_crossing() {
+    let input = "\
+########
+#  B   #
+#A   A #
+#  B   #
+########";
+    let routed_grid = pathfinder_route(Grid::from_string(input)).unwrap();
+    assert_eq!(routed_grid.traces.len(), 2);
+    assert_no_shared_cells(&routed_grid);
+}
+
+#[test]
+fn test_pathfinder_routes_test_boards() {
+    for test_file in [
+        "../../test_data/test_naive_route1.txt",
+        "../../test_data/test_naive_route2.txt",
+    ] {
+        let content = fs::read_to_string(test_file).expect("Failed to read file");
+        let content = content.replace("\r\n", "\n");
+        let input = content.split("input:\n").nth(1).unwrap();
+        let input = input.split("output:\n").next().unwrap().trim();
+        let grid = Grid::from_string(input);
+        let routed_grid = pathfinder_route(grid.clone()).unwrap();
+        assert_eq!(routed_grid.traces.len(), grid.pads.len());
+        assert_no_shared_cells(&routed_grid);
+        for (net, pads) in grid.pads.iter() {
+            let traces = routed_grid.traces.get(net).unwrap();
+            assert!(pads
+                .iter()
+                .all(|pad| traces.iter().any(|trace| trace.point == *pad)));
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use shared::interface_types::Color;
+
+use gui_leptos_tauri_lib::{
+    grid::Point,
+    proba_grid::{NetID, ProbaGridProblem, TraceID},
+    router_config::RouterConfig,
+    solve_proba_grid::{initialize_proba_grid, sample_new_traces, update_posterior},
+};
+
+const RED: Color = Color { r: 255, g: 0, b: 0 };
+const BLUE: Color = Color { r: 0, g: 0, b: 255 };
+
+#[test]
+fn test_inspect_heatmaps_and_traces() {
+    let mut problem = ProbaGridProblem::new(7, 5, 0);
+    for point in [Point { x: 0, y: 2 }, Point { x: 6, y: 2 }] {
+        problem.insert_pad(NetID(0), point, RED, RED);
+    }
+    for point in [Point { x: 2, y: 1 }, Point { x: 4, y: 3 }] {
+        problem.insert_pad(NetID(1), point, BLUE, BLUE);
+    }
+    let config = RouterConfig::default();
+    let mut grid = initialize_proba_grid(problem).unwrap();
+    for _ in 0..2 {
+        sample_new_traces(&mut grid, &config).unwrap();
+        update_posterior(&mut grid, &config).unwrap();
+    }
+    let inspection = grid.inspect(&config).unwrap();
+
+    assert_eq!(inspection.heatmaps.len(), 2);
+    let red = &inspection.heatmaps[0];
+    assert_eq!(red.route_color, RED);
+    assert_eq!(red.occupancy.len(), 5);
+    assert_eq!(red.occupancy[2][6], 1.0);
+    assert!(red
+        .occupancy
+        .iter()
+        .flatten()
+        .all(|p| (0.0..=1.0).contains(p)));
+    // the red net has a single pad pair, so a cell is as likely as the traces through it together
+    let through_center: f64 = inspection
+        .traces
+        .iter()
+        .filter(|trace| trace.net_id == 0 && trace.cells.contains(&(3, 2)))
+        .map(|trace| trace.posterior.unwrap())
+        .sum();
+    assert!((red.occupancy[2][3] - through_center.min(1.0)).abs() < 1e-9);
+
+    assert_eq!(inspection.traces.len(), grid.traces.len());
+    for trace in inspection.traces.iter() {
+        let colliding = &grid.trace_collision_adjacency[&TraceID(trace.trace_id)];
+        assert_eq!(trace.colliding_traces.len(), colliding.len());
+        assert!(trace.score > 0.0 && trace.score <= 1.0);
+        assert!(trace.prior > 0.0);
+    }
+    assert!(inspection
+        .traces
+        .iter()
+        .any(|trace| !trace.colliding_traces.is_empty()));
+    assert!(grid.inspect_trace(TraceID(usize::MAX), &config).is_err());
+}
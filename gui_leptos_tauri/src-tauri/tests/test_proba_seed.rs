@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+
+use shared::interface_types::Color;
+
+use gui_leptos_tauri_lib::{
+    grid::Point,
+    proba_grid::{NetID, ProbaGrid, ProbaGridProblem, TracePath},
+    router_config::RouterConfig,
+    solve_proba_grid::{initialize_proba_grid, solve_proba_grid, ConvergenceCriterion},
+};
+
+fn three_net_problem(seed: u64) -> ProbaGridProblem {
+    let mut problem = ProbaGridProblem::new(9, 7, seed);
+    let nets = [
+        (
+            Color { r: 255, g: 0, b: 0 },
+            [Point { x: 0, y: 3 }, Point { x: 8, y: 3 }],
+        ),
+        (
+            Color { r: 0, g: 255, b: 0 },
+            [Point { x: 2, y: 1 }, Point { x: 6, y: 5 }],
+        ),
+        (
+            Color { r: 0, g: 0, b: 255 },
+            [Point { x: 4, y: 0 }, Point { x: 4, y: 6 }],
+        ),
+    ];
+    for (i, (color, pads)) in nets.into_iter().enumerate() {
+        for pad in pads {
+            problem.insert_pad(NetID(i), pad, color.clone(), color.clone());
+        }
+    }
+    problem
+}
+
+fn run(seed: u64) -> ProbaGrid {
+    let mut grid = initialize_proba_grid(three_net_problem(seed)).unwrap();
+    let criterion = ConvergenceCriterion {
+        tolerance: 0.0,
+        max_iterations: 4,
+    };
+    solve_proba_grid(&mut grid, &RouterConfig::default(), &criterion).unwrap();
+    grid
+}
+
+fn traces(grid: &ProbaGrid) -> BTreeMap<usize, (TracePath, u64)> {
+    grid.traces
+        .iter()
+        .map(|(trace_id, trace_info)| {
+            let posterior = trace_info.posterior_normalized.unwrap();
+            (
+                trace_id.0,
+                (trace_info.trace_path.clone(), posterior.to_bits()),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_same_seed_same_run() {
+    let a = run(7);
+    let b = run(7);
+    assert_eq!(traces(&a), traces(&b));
+    assert_eq!(a.to_color_grid().grid, b.to_color_grid().grid);
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use shared::interface_types::Color;
+
+use gui_leptos_tauri_lib::{
+    grid::Point,
+    proba_grid::{NetID, ProbaGrid, ProbaGridProblem},
+    proba_history::ProbaHistory,
+    router_config::RouterConfig,
+    solve_proba_grid::{
+        initialize_proba_grid, sample_new_traces, solve_proba_grid_observed, update_posterior,
+        ConvergenceCriterion,
+    },
+};
+
+fn two_net_problem() -> ProbaGridProblem {
+    let mut problem = ProbaGridProblem::new(7, 5, 5);
+    let red = Color { r: 255, g: 0, b: 0 };
+    let blue = Color { r: 0, g: 0, b: 255 };
+    for point in [Point { x: 0, y: 2 }, Point { x: 6, y: 2 }] {
+        problem.insert_pad(NetID(0), point, red.clone(), red.clone());
+    }
+    for point in [Point { x: 2, y: 1 }, Point { x: 4, y: 3 }] {
+        problem.insert_pad(NetID(1), point, blue.clone(), blue.clone());
+    }
+    problem
+}
+
+fn posteriors(grid: &ProbaGrid) -> BTreeMap<usize, Option<u64>> {
+    grid.traces
+        .iter()
+        .map(|(trace_id, trace_info)| {
+            let posterior = trace_info.posterior_normalized.map(f64::to_bits);
+            (trace_id.0, posterior)
+        })
+        .collect()
+}
+
+#[test]
+fn test_restore_and_branch() {
+    let config = RouterConfig::default();
+    let mut grid = initialize_proba_grid(two_net_problem()).unwrap();
+    let mut history = ProbaHistory::default();
+    history.record(&grid, "init", config.max_snapshots);
+    let criterion = ConvergenceCriterion {
+        tolerance: 0.0,
+        max_iterations: 3,
+    };
+    solve_proba_grid_observed(&mut grid, &config, &criterion, |grid| {
+        history.record(grid, "solve", config.max_snapshots);
+    })
+    .unwrap();
+    let list = history.list();
+    assert_eq!(list.len(), 4);
+    assert_eq!(
+        list.iter().map(|info| info.iteration).collect::<Vec<_>>(),
+        vec![0, 1, 2, 3]
+    );
+    assert!(list[1..].iter().all(|info| info.posteriors_updated));
+
+    // restoring and replaying with the same config reproduces the second iteration
+    let mut restored = history.restore(1).unwrap();
+    assert_eq!(
+        posteriors(&restored),
+        posteriors(&history.snapshots[1].grid)
+    );
+    sample_new_traces(&mut restored, &config).unwrap();
+    update_posterior(&mut restored, &config).unwrap();
+    assert_eq!(
+        posteriors(&restored),
+        posteriors(&history.snapshots[2].grid)
+    );
+
+    // branching with another weight leaves the snapshot untouched
+    let mut branch = history.restore(1).unwrap();
+    let weighted = RouterConfig {
+        score_weight: 2.0,
+        ..config
+    };
+    update_posterior(&mut branch, &weighted).unwrap();
+    assert_ne!(posteriors(&branch), posteriors(&history.snapshots[1].grid));
+    assert_eq!(
+        posteriors(&history.restore(1).unwrap()),
+        posteriors(&history.snapshots[1].grid)
+    );
+    let id = history.record(&branch, "update posterior", config.max_snapshots);
+    assert_eq!(history.list()[id].parent, Some(1));
+
+    assert!(history.restore(100).is_err());
+}
+
+#[test]
+fn test_max_snapshots() {
+    let grid = initialize_proba_grid(two_net_problem()).unwrap();
+    let mut history = ProbaHistory::default();
+    for _ in 0..4 {
+        history.record(&grid, "sample", 3);
+    }
+    // a linear history keeps its newest snapshots
+    let ids = |history: &ProbaHistory| -> Vec<usize> {
+        history.list().iter().map(|info| info.id).collect()
+    };
+    assert_eq!(ids(&history), vec![1, 2, 3]);
+
+    // snapshots off the current branch are dropped before older ancestors
+    history.restore(2).unwrap();
+    history.record(&grid, "update posterior", 3);
+    assert_eq!(ids(&history), vec![1, 2, 4]);
+    assert_eq!(history.get(4).unwrap().parent, Some(2));
+    assert!(history.restore(3).is_err());
+    assert_eq!(history.ancestors(4).len(), 3);
+}
@@ -0,0 +1,62 @@
+use std::num::NonZeroUsize;
+
+use shared::interface_types::Color;
+
+use gui_leptos_tauri_lib::{
+    grid::Point,
+    proba_grid::{NetID, PriorSchedule, ProbaGridProblem},
+    router_config::RouterConfig,
+    solve_proba_grid::{initialize_proba_grid, solve_proba_grid, ConvergenceCriterion},
+};
+
+fn two_net_problem() -> ProbaGridProblem {
+    let mut problem = ProbaGridProblem::new(7, 5, 0);
+    let red = Color { r: 255, g: 0, b: 0 };
+    let blue = Color { r: 0, g: 0, b: 255 };
+    for point in [Point { x: 0, y: 2 }, Point { x: 6, y: 2 }] {
+        problem.insert_pad(NetID(0), point, red.clone(), red.clone());
+    }
+    for point in [Point { x: 2, y: 1 }, Point { x: 4, y: 3 }] {
+        problem.insert_pad(NetID(1), point, blue.clone(), blue.clone());
+    }
+    problem
+}
+
+#[test]
+fn test_geometric_prior_schedule() {
+    let schedule = PriorSchedule::default();
+    let prior = |i: usize| schedule.prior_probability(NonZeroUsize::new(i).unwrap());
+    // the three iterations that used to be hard-coded
+    assert!((prior(1) - 0.7).abs() < 1e-12);
+    assert!((prior(2) - 0.21).abs() < 1e-12);
+    assert!((prior(3) - 0.063).abs() < 1e-12);
+    let total: f64 = (1..=10).map(prior).sum();
+    assert!((schedule.total_prior_probability(10) - total).abs() < 1e-12);
+    // any number of iterations has a prior
+    assert!(prior(100) > 0.0);
+    assert!(schedule.total_prior_probability(100) <= 1.0);
+}
+
+#[test]
+fn test_solve_runs_past_three_iterations() {
+    let config = RouterConfig::default();
+    let mut grid = initialize_proba_grid(two_net_problem()).unwrap();
+    let criterion = ConvergenceCriterion {
+        tolerance: 0.0,
+        max_iterations: 6,
+    };
+    let report = solve_proba_grid(&mut grid, &config, &criterion).unwrap();
+    assert!(!report.converged);
+    assert_eq!(report.iterations(), 6);
+    assert_eq!(grid.next_iteration.get(), 7);
+    assert!(report.summary().contains("without converging"));
+}
+
+#[test]
+fn test_solve_converges() {
+    let config = RouterConfig::default();
+    let mut grid = initialize_proba_grid(two_net_problem()).unwrap();
+    let report = solve_proba_grid(&mut grid, &config, &ConvergenceCriterion::default()).unwrap();
+    assert!(report.converged, "{}", report.summary());
+    assert!(*report.max_posterior_changes.last().unwrap() <= report.criterion.tolerance);
+}
@@ -0,0 +1,59 @@
+use shared::interface_types::Color;
+
+use gui_leptos_tauri_lib::{
+    grid::Point,
+    proba_grid::{NetID, ProbaGridProblem},
+    router_config::RouterConfig,
+    solve_proba_grid::{initialize_proba_grid, sample_next_net, sample_next_pair, start_sampling},
+};
+
+fn two_net_problem() -> ProbaGridProblem {
+    let mut problem = ProbaGridProblem::new(8, 6, 0);
+    let red = Color { r: 255, g: 0, b: 0 };
+    let blue = Color { r: 0, g: 0, b: 255 };
+    for point in [
+        Point { x: 0, y: 1 },
+        Point { x: 7, y: 1 },
+        Point { x: 3, y: 5 },
+    ] {
+        problem.insert_pad(NetID(0), point, red.clone(), red.clone());
+    }
+    for point in [Point { x: 0, y: 4 }, Point { x: 7, y: 4 }] {
+        problem.insert_pad(NetID(1), point, blue.clone(), blue.clone());
+    }
+    problem
+}
+
+#[test]
+fn test_step_through_a_pass() {
+    let config = RouterConfig::default();
+    let mut grid = initialize_proba_grid(two_net_problem()).unwrap();
+    let mut cursor = start_sampling(&grid);
+    let nets: Vec<NetID> = cursor
+        .remaining_pad_pairs
+        .iter()
+        .map(|(net_id, _)| *net_id)
+        .collect();
+    assert_eq!(nets, vec![NetID(0), NetID(0), NetID(0), NetID(1)]);
+
+    assert!(!sample_next_pair(&mut grid, &mut cursor, &config).unwrap());
+    assert_eq!(cursor.remaining_pad_pairs.len(), 3);
+    assert!(!cursor.new_traces.is_empty());
+    // the traces are only added to the grid once the pass is complete
+    assert!(grid.traces.is_empty());
+    assert_ne!(
+        grid.to_color_grid_with_pending(&cursor.new_traces).grid,
+        grid.to_color_grid().grid
+    );
+
+    // the rest of the first net
+    assert!(!sample_next_net(&mut grid, &mut cursor, &config).unwrap());
+    assert_eq!(cursor.remaining_pad_pairs.len(), 1);
+    assert!(sample_next_net(&mut grid, &mut cursor, &config).unwrap());
+    assert!(cursor.new_traces.is_empty());
+    assert_eq!(grid.next_iteration.get(), 2);
+    assert!(grid
+        .pad_pair_to_traces
+        .values()
+        .all(|iterations| !iterations.is_empty()));
+}
@@ -0,0 +1,211 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use gui_leptos_tauri_lib::{
+    grid::{Grid, LayerPoint, Net, Point},
+    kicad::KicadBoard,
+    naive_route::naive_route,
+    proba_grid::{NetID, ProbaGridProblem},
+    problem_file::{ProblemFile, ProblemFileKind, PROBLEM_FILE_VERSION},
+};
+
+fn net(c: char) -> Net {
+    Net::Character {
+        pad_c: c,
+        route_c: c.to_ascii_lowercase(),
+    }
+}
+
+// net A crosses a wall of SMD pads of net B through a via, net C is a high speed net next to a keepout
+fn routed_grid() -> Grid {
+    let mut grid = Grid::new_with_layers(7, 7, 2);
+    grid.insert_smd_pad(net('A'), Point { x: 0, y: 2 }, 0);
+    grid.insert_smd_pad(net('A'), Point { x: 6, y: 2 }, 0);
+    for y in 0..5 {
+        grid.insert_smd_pad(net('B'), Point { x: 3, y }, 0);
+    }
+    grid.insert_pad(net('C'), Point { x: 0, y: 6 });
+    grid.insert_pad(net('C'), Point { x: 6, y: 6 });
+    grid.apply_directive("keepout rect 2 5 4 5 layers 1")
+        .unwrap();
+    grid.apply_directive("class C high_speed").unwrap();
+    let mut grid = naive_route(grid).unwrap();
+    // rules that only need to survive the round trip
+    grid.design_rules.set_clearance(&net('A'), &net('C'), 1);
+    grid.design_rules.set_trace_width(net('B'), 2);
+    grid.diagonal_traces
+        .insert(net('C'), HashSet::from([LayerPoint::new(0, 5, 0)]));
+    grid
+}
+
+fn assert_same_grid(a: &Grid, b: &Grid) {
+    assert_eq!((a.width, a.height, a.layers), (b.width, b.height, b.layers));
+    assert_eq!(a.pads, b.pads);
+    assert_eq!(a.pad_kinds, b.pad_kinds);
+    assert_eq!(a.traces, b.traces);
+    assert_eq!(a.diagonal_traces, b.diagonal_traces);
+    assert_eq!(a.vias, b.vias);
+    assert_eq!(a.design_rules, b.design_rules);
+    assert_eq!(a.keepouts, b.keepouts);
+}
+
+#[test]
+fn test_solution_round_trip() {
+    let grid = routed_grid();
+    assert!(!grid.vias.is_empty());
+    let file = ProblemFile::from_grid(&grid, ProblemFileKind::Solution);
+    assert_eq!(file.version, PROBLEM_FILE_VERSION);
+
+    let json = file.to_json().unwrap();
+    assert_eq!(ProblemFile::from_json(&json).unwrap(), file);
+    assert_same_grid(
+        &ProblemFile::from_json(&json).unwrap().to_grid().unwrap(),
+        &grid,
+    );
+
+    let toml = file.to_toml().unwrap();
+    assert_eq!(ProblemFile::from_toml(&toml).unwrap(), file);
+    assert_same_grid(
+        &ProblemFile::from_toml(&toml).unwrap().to_grid().unwrap(),
+        &grid,
+    );
+
+    // the same grid is always written the same way
+    assert_eq!(
+        ProblemFile::from_grid(&file.to_grid().unwrap(), ProblemFileKind::Solution)
+            .to_json()
+            .unwrap(),
+        json
+    );
+}
+
+#[test]
+fn test_problem_drops_routes() {
+    let grid = routed_grid();
+    let file = ProblemFile::from_grid(&grid, ProblemFileKind::Problem);
+    let problem = ProblemFile::from_json(&file.to_json().unwrap())
+        .unwrap()
+        .to_grid()
+        .unwrap();
+    assert!(problem.traces.is_empty() && problem.diagonal_traces.is_empty());
+    assert!(problem.vias.is_empty());
+    assert_eq!(problem.pads, grid.pads);
+    assert_eq!(problem.design_rules, grid.design_rules);
+
+    // a problem file cannot carry routes
+    let mut solution = ProblemFile::from_grid(&grid, ProblemFileKind::Solution);
+    solution.kind = ProblemFileKind::Problem;
+    assert!(solution.to_grid().is_err());
+}
+
+#[test]
+fn test_hand_written_toml() {
+    // missing rules and routes keep their defaults, the route character defaults to lowercase
+    let file = ProblemFile::from_toml(
+        r#"
+version = 1
+kind = "problem"
+width = 5
+height = 3
+layers = 1
+
+[metadata]
+name = "two pads"
+
+[[nets]]
+id = 1
+pad_char = "A"
+class = "power"
+pads = [{ x = 0, y = 1 }, { x = 4, y = 1 }]
+"#,
+    )
+    .unwrap();
+    let grid = file.to_grid().unwrap();
+    assert_eq!(grid.pads[&net('A')].len(), 2);
+    assert_eq!(grid.design_rules.trace_width(&net('A')), 3);
+    assert_eq!(file.metadata.name.as_deref(), Some("two pads"));
+    naive_route(grid).unwrap();
+}
+
+#[test]
+fn test_invalid_files() {
+    let json = ProblemFile::from_grid(&routed_grid(), ProblemFileKind::Solution)
+        .to_json()
+        .unwrap();
+    let newer = json.replacen(
+        &format!("\"version\": {}", PROBLEM_FILE_VERSION),
+        "\"version\": 99",
+        1,
+    );
+    let error = ProblemFile::from_json(&newer).unwrap_err();
+    assert!(error.contains("version 99"), "{}", error);
+
+    let mut file = ProblemFile::from_grid(&routed_grid(), ProblemFileKind::Problem);
+    file.nets[0].pads[0].x = 7;
+    assert!(file.validate().is_err(), "pad outside of the grid");
+    let mut file = ProblemFile::from_grid(&routed_grid(), ProblemFileKind::Problem);
+    file.nets[1].id = file.nets[0].id;
+    assert!(file.validate().is_err(), "duplicate net id");
+    let mut file = ProblemFile::from_grid(&routed_grid(), ProblemFileKind::Problem);
+    file.nets[0].class = Some("no_such_class".to_string());
+    assert!(file.validate().is_err());
+    let mut file = ProblemFile::from_grid(&routed_grid(), ProblemFileKind::Problem);
+    let pad = file.nets[0].pads[0];
+    file.nets[1].pads.push(pad);
+    assert!(file.validate().is_err(), "two nets on one pad");
+    assert!(ProblemFile::from_toml("version = 1\nkind = \"routed\"").is_err());
+}
+
+fn tutorial_problem() -> ProbaGridProblem {
+    let board = KicadBoard::load(Path::new("../../kicad2grid/tutorial.kicad_pcb")).unwrap();
+    let mut problem = board.to_proba_grid_problem(2.54).unwrap();
+    problem.seed = 7;
+    problem
+        .design_rules
+        .set_net_class(NetID(1), "power")
+        .unwrap();
+    problem.design_rules.set_clearance(&NetID(2), &NetID(3), 1);
+    problem
+}
+
+#[test]
+fn test_proba_problem_round_trip() {
+    let problem = tutorial_problem();
+    let file = ProblemFile::from_proba_grid_problem(&problem);
+    assert_eq!(file.seed, Some(7));
+    let loaded = ProblemFile::from_toml(&file.to_toml().unwrap())
+        .unwrap()
+        .to_proba_grid_problem()
+        .unwrap();
+    assert_eq!(
+        (loaded.width, loaded.height),
+        (problem.width, problem.height)
+    );
+    assert_eq!(loaded.seed, problem.seed);
+    assert_eq!(loaded.net_to_pads, problem.net_to_pads);
+    assert_eq!(loaded.design_rules, problem.design_rules);
+    assert_eq!(loaded.keepouts, problem.keepouts);
+    assert_eq!(loaded.nets.len(), problem.nets.len());
+    for (net_id, info) in &problem.nets {
+        let other = &loaded.nets[net_id];
+        assert_eq!(other.net_id, info.net_id);
+        assert_eq!(other.pad_color, info.pad_color);
+        assert_eq!(other.route_color, info.route_color);
+        assert_eq!(other.pad_character, info.pad_character);
+    }
+}
+
+#[test]
+fn test_save_and_load() {
+    let grid = routed_grid();
+    let file = ProblemFile::from_grid(&grid, ProblemFileKind::Solution);
+    for extension in ["json", "toml"] {
+        let path = std::env::temp_dir().join(format!("test_problem_file.{}", extension));
+        file.save(&path).unwrap();
+        let loaded = ProblemFile::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, file);
+    }
+    let path = std::env::temp_dir().join("test_problem_file.yaml");
+    assert!(file.save(&path).is_err());
+    assert!(ProblemFile::load(&path).is_err());
+}
@@ -0,0 +1,38 @@
+use gui_leptos_tauri_lib::{
+    grid::{Grid, LayerPoint, Net},
+    naive_route::naive_route,
+};
+
+fn net(c: char) -> Net {
+    Net::Character {
+        pad_c: c,
+        route_c: c.to_ascii_lowercase(),
+    }
+}
+
+#[test]
+fn test_rip_up_and_reroute() {
+    // net B is routed first because it is shorter, but its straight route cuts net A off.
+    // B can still be routed around the right pad of A once A is in place.
+    let input = "\
+########
+#  B   #
+#A   A #
+#  B   #
+########";
+    let grid = Grid::from_string(input);
+    let routed_grid = naive_route(grid).unwrap();
+    let traces: Vec<_> = routed_grid.traces.values().collect();
+    assert_eq!(traces.len(), 2, "Both nets should be routed");
+    assert!(
+        traces[0].is_disjoint(traces[1]),
+        "Traces of different nets must not overlap"
+    );
+    // A keeps the straight row, the rerouted B only crosses it right of A's pad
+    let a_traces = &routed_grid.traces[&net('A')];
+    assert!((0..5).all(|x| a_traces.contains(&LayerPoint::new(x, 1, 0))));
+    assert!(routed_grid.traces[&net('B')]
+        .iter()
+        .filter(|layer_point| layer_point.point.y == 1)
+        .all(|layer_point| layer_point.point.x > 4));
+}
@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use shared::interface_types::Color;
+
+use gui_leptos_tauri_lib::{
+    grid::Point,
+    proba_grid::{NetID, ProbaGrid, ProbaGridProblem},
+    router_config::RouterConfig,
+    solve_proba_grid::{initialize_proba_grid, sample_new_traces, update_posterior},
+    steiner_tree::NetDecomposition,
+};
+
+#[test]
+fn test_partial_toml_keeps_defaults() {
+    let config =
+        RouterConfig::from_toml("score_weight = 1.5\nmax_traces_per_iteration = 2\n").unwrap();
+    assert_eq!(config.score_weight, 1.5);
+    assert_eq!(config.max_traces_per_iteration, 2);
+    assert_eq!(
+        config.turn_penalty_rate,
+        RouterConfig::default().turn_penalty_rate
+    );
+    assert_eq!(
+        RouterConfig::from_toml(&config.to_toml().unwrap()).unwrap(),
+        config
+    );
+}
+
+#[test]
+fn test_json_and_validation() {
+    let config = RouterConfig::from_json(r#"{"half_probability_raw_score": 20.0}"#).unwrap();
+    assert_eq!(config.half_probability_raw_score, 20.0);
+    assert!(RouterConfig::from_json(r#"{"half_probability_raw_score": 0.0}"#).is_err());
+    assert!(RouterConfig::from_toml("max_generation_attempts = 0").is_err());
+    assert!(RouterConfig::from_toml("score_weight = -1.0").is_err());
+    assert!(RouterConfig::from_toml("max_snapshots = 0").is_err());
+    assert!(RouterConfig::from_toml("unknown = [").is_err());
+    assert!(RouterConfig::load(Path::new("router.yaml")).is_err());
+    let config = RouterConfig::from_toml("decomposition = \"steiner_tree\"").unwrap();
+    assert_eq!(config.decomposition, Some(NetDecomposition::SteinerTree));
+    assert_eq!(RouterConfig::default().decomposition, None);
+    assert!(RouterConfig::from_toml("decomposition = \"ring\"").is_err());
+}
+
+fn sampled_grid(config: &RouterConfig) -> ProbaGrid {
+    let mut problem = ProbaGridProblem::new(7, 5, 0);
+    let red = Color { r: 255, g: 0, b: 0 };
+    let blue = Color { r: 0, g: 0, b: 255 };
+    for point in [Point { x: 0, y: 2 }, Point { x: 6, y: 2 }] {
+        problem.insert_pad(NetID(0), point, red.clone(), red.clone());
+    }
+    for point in [Point { x: 2, y: 1 }, Point { x: 4, y: 3 }] {
+        problem.insert_pad(NetID(1), point, blue.clone(), blue.clone());
+    }
+    let mut grid = initialize_proba_grid(problem).unwrap();
+    sample_new_traces(&mut grid, config).unwrap();
+    grid
+}
+
+#[test]
+fn test_config_is_passed_explicitly() {
+    let config = RouterConfig {
+        max_traces_per_iteration: 1,
+        ..RouterConfig::default()
+    };
+    let mut grid = sampled_grid(&config);
+    assert!(grid
+        .pad_pair_to_traces
+        .values()
+        .all(|iterations| iterations.values().all(|trace_ids| trace_ids.len() == 1)));
+
+    // the same grid gets different posteriors under different score weights
+    let posteriors = |grid: &ProbaGrid| -> Vec<f64> {
+        let mut trace_ids: Vec<_> = grid.traces.keys().cloned().collect();
+        trace_ids.sort();
+        trace_ids
+            .iter()
+            .map(|trace_id| grid.traces[trace_id].posterior_normalized.unwrap())
+            .collect()
+    };
+    update_posterior(&mut grid, &config).unwrap();
+    let default_posteriors = posteriors(&grid);
+    let weighted = RouterConfig {
+        score_weight: 3.0,
+        ..config
+    };
+    update_posterior(&mut grid, &weighted).unwrap();
+    assert_ne!(posteriors(&grid), default_posteriors);
+}
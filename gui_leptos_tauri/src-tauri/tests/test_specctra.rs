@@ -0,0 +1,236 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs,
+};
+
+use gui_leptos_tauri_lib::{
+    board_file::BoardFile,
+    grid::{LayerPoint, Point},
+    keepout::KeepoutShape,
+    kicad::{grid_net, KicadPadType},
+    naive_route::naive_route,
+    proba_grid::NetID,
+    sexpr::SExpr,
+    specctra::DsnDesign,
+};
+
+// two components of the same image, U1 on the front and U2 on the back rotated by 90 degrees.
+// Pin 1 is through hole, pin 2 is an SMD pad on the front of the image
+const PAIR_DESIGN: &str = r#"(pcb "pair.dsn"
+  (parser
+    (string_quote ")
+    (space_in_quoted_tokens on)
+    (host_cad "KiCad's Pcbnew")
+  )
+  (resolution um 10)
+  (unit um)
+  (structure
+    (layer F.Cu (type signal) (property (index 0)))
+    (layer B.Cu (type signal) (property (index 1)))
+    (boundary (path pcb 0  0 0  10000 0  10000 -8000  0 -8000  0 0))
+    (keepout "" (rect signal 4000 -3000 6000 -5000))
+    (via "Via[0-1]_800:400_um")
+    (rule (width 250) (clearance 200) (clearance 100 (type smd_smd)))
+  )
+  (placement
+    (component Test:Pair
+      (place U1 2000 -2000 front 0)
+      (place U2 8000 -6000 back 90)
+    )
+  )
+  (library
+    (image Test:Pair
+      (pin Round[A]Pad_1000_um 1 0 0)
+      (pin Rect[T]Pad_500x500_um (rotate 90) 2 1000 0)
+    )
+    (padstack Round[A]Pad_1000_um
+      (shape (circle F.Cu 1000))
+      (shape (circle B.Cu 1000))
+      (attach off)
+    )
+    (padstack Rect[T]Pad_500x500_um
+      (shape (rect F.Cu -250 -250 250 250))
+      (attach off)
+    )
+    (padstack "Via[0-1]_800:400_um"
+      (shape (circle F.Cu 800))
+      (shape (circle B.Cu 800))
+      (attach off)
+    )
+  )
+  (network
+    (net A (pins U1-1 U2-1))
+    (net B (pins U1-2 U2-2))
+    (class kicad_default A (rule (width 250) (clearance 200)))
+    (class power B (rule (width 1100) (clearance 400)))
+  )
+  (wiring)
+)
+"#;
+
+fn design() -> DsnDesign {
+    DsnDesign::parse(PAIR_DESIGN).unwrap()
+}
+
+#[test]
+fn test_parse_design() {
+    let design = design();
+    assert_eq!(design.name, "pair.dsn");
+    assert_eq!(design.board.copper_layers, vec!["F.Cu", "B.Cu"]);
+    assert_eq!(
+        design.board.nets.values().collect::<Vec<_>>(),
+        vec!["A", "B"]
+    );
+    let outline = design.board.outline.unwrap();
+    assert_eq!(
+        (outline.min_x, outline.min_y, outline.max_x, outline.max_y),
+        (0.0, 0.0, 10.0, 8.0)
+    );
+    let pad = |footprint: &str, number: &str| {
+        design
+            .board
+            .pads
+            .iter()
+            .find(|pad| pad.footprint == footprint && pad.number == number)
+            .unwrap()
+    };
+    // the y axis is flipped, the back side is mirrored before the rotation
+    let position = |footprint: &str, number: &str| {
+        let pad = pad(footprint, number);
+        ((pad.x * 1e6).round() / 1e6, (pad.y * 1e6).round() / 1e6)
+    };
+    assert_eq!(position("U1", "1"), (2.0, 2.0));
+    assert_eq!(position("U1", "2"), (3.0, 2.0));
+    assert_eq!(position("U2", "2"), (8.0, 7.0));
+    assert_eq!(pad("U1", "1").pad_type, KicadPadType::ThroughHole);
+    assert_eq!(pad("U1", "2").layers, vec!["F.Cu"]);
+    assert_eq!(pad("U2", "2").layers, vec!["B.Cu"]);
+    assert_eq!(pad("U2", "2").net, Some(2));
+    // the smd_smd clearance does not replace the default one
+    assert_eq!(design.rule.width, Some(0.25));
+    assert_eq!(design.rule.clearance, Some(0.2));
+    assert_eq!(design.classes[1].rule.width, Some(1.1));
+    let via = design.via.as_ref().unwrap();
+    assert_eq!(via.name, "Via[0-1]_800:400_um");
+    assert!((via.diameter - 0.8).abs() < 1e-9);
+    assert_eq!(design.keepouts.len(), 1);
+    assert_eq!(design.keepouts[0].layer, None);
+}
+
+#[test]
+fn test_design_to_grid() {
+    let grid = design().to_grid(1.0).unwrap();
+    assert_eq!((grid.width, grid.height, grid.layers), (11, 9, 2));
+    assert_eq!(
+        grid.pads[&grid_net(2)],
+        BTreeSet::from([Point { x: 3, y: 2 }, Point { x: 8, y: 7 }])
+    );
+    assert_eq!(grid.keepouts.len(), 1);
+    assert_eq!(
+        grid.keepouts[0].shape,
+        KeepoutShape::Rect {
+            min: Point { x: 4, y: 3 },
+            max: Point { x: 6, y: 5 },
+        }
+    );
+    // 0.25 mm with 0.2 mm clearance fits in a cell, 1.1 mm with 0.4 mm clearance does not
+    assert_eq!(grid.design_rules.trace_width(&grid_net(1)), 1);
+    assert_eq!(grid.design_rules.trace_width(&grid_net(2)), 2);
+    assert_eq!(grid.design_rules.clearance(&grid_net(1), &grid_net(2)), 1);
+
+    let problem = design().to_proba_grid_problem(1.0).unwrap();
+    assert_eq!(problem.net_to_pads.len(), 2);
+    assert!(problem.net_to_pads[&NetID(1)].contains(&Point { x: 8, y: 6 }));
+    assert_eq!(problem.design_rules.trace_width(&NetID(2)), 2);
+}
+
+#[test]
+fn test_session() {
+    let design = design();
+    let mut grid = design.to_grid(1.0).unwrap();
+    // net A runs along the top edge and drops to U2 through a via
+    grid.traces.insert(
+        grid_net(1),
+        (2..=8)
+            .map(|x| LayerPoint::new(x, 1, 0))
+            .chain([LayerPoint::new(2, 2, 0)])
+            .chain((1..=6).map(|y| LayerPoint::new(8, y, 1)))
+            .collect::<HashSet<_>>(),
+    );
+    grid.vias
+        .insert(grid_net(1), HashSet::from([Point { x: 8, y: 1 }]));
+    let session = design.session(&grid, 1.0).unwrap();
+    assert_eq!(session.atom(0), Some("pair.dsn"));
+    let routes = session.child("routes").unwrap();
+    let padstack = routes
+        .child("library_out")
+        .unwrap()
+        .child("padstack")
+        .unwrap();
+    assert_eq!(padstack.atom(0), Some("Via[0-1]_800:400_um"));
+    let nets: Vec<&SExpr> = routes
+        .child("network_out")
+        .unwrap()
+        .children("net")
+        .collect();
+    assert_eq!(nets.len(), 1);
+    assert_eq!(nets[0].atom(0), Some("A"));
+    let paths: Vec<Vec<String>> = nets[0]
+        .children("wire")
+        .map(|wire| {
+            let path = wire.child("path").unwrap();
+            path.args()
+                .iter()
+                .map(|arg| arg.as_atom().unwrap().to_string())
+                .collect()
+        })
+        .collect();
+    // coordinates are in tenths of a um with the y axis pointing up
+    assert!(paths.contains(&vec![
+        "F.Cu".to_string(),
+        "2500".to_string(),
+        "20000".to_string(),
+        "-10000".to_string(),
+        "80000".to_string(),
+        "-10000".to_string(),
+    ]));
+    assert!(paths
+        .iter()
+        .any(|path| path[0] == "B.Cu" && path[3..] == ["-10000", "80000", "-60000"]));
+    let via = nets[0].child("via").unwrap();
+    assert_eq!(via.number(1), Some(80000.0));
+    assert_eq!(via.number(2), Some(-10000.0));
+
+    // a session can be read back as an S-expression
+    assert_eq!(SExpr::parse(&session.to_string()).unwrap(), session);
+}
+
+#[test]
+fn test_open_board_file() {
+    let path = std::env::temp_dir().join("test_specctra_pair.dsn");
+    // the naive router cannot fit the wide class of net B on this small board
+    fs::write(
+        &path,
+        PAIR_DESIGN.replace(
+            "(width 1100) (clearance 400)",
+            "(width 250) (clearance 200)",
+        ),
+    )
+    .unwrap();
+    let board = BoardFile::open(&path, 1.0).unwrap();
+    fs::remove_file(&path).unwrap();
+    let grid = naive_route(board.to_grid().unwrap()).unwrap();
+    let session_path = std::env::temp_dir().join("test_specctra_pair.ses");
+    board.save_routed(&grid, &session_path).unwrap();
+    let session = SExpr::parse(&fs::read_to_string(&session_path).unwrap()).unwrap();
+    fs::remove_file(&session_path).unwrap();
+    assert_eq!(session.head(), Some("session"));
+    let network_out = session
+        .child("routes")
+        .unwrap()
+        .child("network_out")
+        .unwrap();
+    assert_eq!(network_out.children("net").count(), 2);
+
+    assert!(BoardFile::open(std::path::Path::new("board.txt"), 1.0).is_err());
+}
@@ -0,0 +1,132 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use gui_leptos_tauri_lib::{
+    design_rules::{DesignRules, NetClass, ViaPolicy},
+    grid::{Grid, LayerPoint, Net, Point, PointPair},
+    naive_route::naive_route_with_decomposition,
+    proba_grid::{NetID, NetInfo, ProbaGridProblem},
+    solve_proba_grid::initialize_proba_grid_with_decomposition,
+    steiner_tree::{rectilinear_spanning_tree, rectilinear_steiner_tree, NetDecomposition},
+};
+
+fn plus_terminals() -> BTreeSet<Point> {
+    BTreeSet::from([
+        Point { x: 1, y: 3 },
+        Point { x: 5, y: 3 },
+        Point { x: 3, y: 1 },
+        Point { x: 3, y: 5 },
+    ])
+}
+
+fn rectilinear_length(pairs: &[PointPair]) -> usize {
+    pairs
+        .iter()
+        .map(|pair| {
+            let (start, end) = (pair.start(), pair.end());
+            start.x.abs_diff(end.x) + start.y.abs_diff(end.y)
+        })
+        .sum()
+}
+
+#[test]
+fn test_steiner_point_shortens_tree() {
+    let terminals = plus_terminals();
+    let spanning_tree = rectilinear_spanning_tree(&terminals);
+    let steiner_tree = rectilinear_steiner_tree(&terminals, &HashSet::new());
+    assert_eq!(rectilinear_length(&spanning_tree), 12);
+    assert_eq!(rectilinear_length(&steiner_tree), 8);
+    // every edge of the Steiner tree ends in the center of the plus
+    let center = Point { x: 3, y: 3 };
+    assert_eq!(steiner_tree.len(), 4);
+    assert!(steiner_tree
+        .iter()
+        .all(|pair| pair.start() == center || pair.end() == center));
+}
+
+#[test]
+fn test_blocked_steiner_point() {
+    let terminals = plus_terminals();
+    let blocked = HashSet::from([Point { x: 3, y: 3 }]);
+    let steiner_tree = rectilinear_steiner_tree(&terminals, &blocked);
+    assert!(steiner_tree
+        .iter()
+        .all(|pair| { !blocked.contains(&pair.start()) && !blocked.contains(&pair.end()) }));
+}
+
+#[test]
+fn test_naive_route_with_steiner_tree() {
+    let input = "\
+#########
+#   A   #
+#       #
+# A   A #
+#       #
+#   A   #
+#########";
+    let grid = Grid::from_string(input);
+    let routed_grid = naive_route_with_decomposition(grid, NetDecomposition::SteinerTree).unwrap();
+    let traces = routed_grid.traces.values().next().unwrap();
+    // the Steiner point in the middle of the pads joins all branches
+    assert!(traces.contains(&LayerPoint::new(3, 2, 0)));
+}
+
+#[test]
+fn test_steiner_point_on_allowed_layer() {
+    // the Steiner point is not a pad, so it has to be on a layer the net class allows
+    let mut grid = Grid::new_with_layers(7, 7, 2);
+    let net = Net::Character {
+        pad_c: 'A',
+        route_c: 'a',
+    };
+    for point in plus_terminals() {
+        grid.insert_pad(net.clone(), point);
+    }
+    grid.design_rules.net_classes.insert(
+        "bottom".to_string(),
+        NetClass {
+            name: "bottom".to_string(),
+            trace_width: 1,
+            clearance: 0,
+            allowed_layers: Some(BTreeSet::from([1])),
+            max_length: None,
+            via_policy: ViaPolicy::Allowed,
+        },
+    );
+    grid.design_rules
+        .set_net_class(net.clone(), "bottom")
+        .unwrap();
+    let routed_grid = naive_route_with_decomposition(grid, NetDecomposition::SteinerTree).unwrap();
+    let traces = &routed_grid.traces[&net];
+    assert!(traces.contains(&LayerPoint::new(3, 3, 1)));
+    assert!(traces.iter().all(|point| point.layer == 1));
+}
+
+#[test]
+fn test_proba_grid_steiner_decomposition() {
+    let net_id = NetID(0);
+    let problem = ProbaGridProblem {
+        width: 7,
+        height: 7,
+        nets: HashMap::from([(
+            net_id,
+            NetInfo {
+                net_id: 0,
+                pad_character: Some('A'),
+                route_character: None,
+                pad_color: None,
+                route_color: None,
+            },
+        )]),
+        net_to_pads: HashMap::from([(net_id, plus_terminals().into_iter().collect())]),
+        design_rules: DesignRules::default(),
+        keepouts: Vec::new(),
+        seed: 0,
+    };
+    let all_pairs =
+        initialize_proba_grid_with_decomposition(problem.clone(), NetDecomposition::AllPairs)
+            .unwrap();
+    let steiner =
+        initialize_proba_grid_with_decomposition(problem, NetDecomposition::SteinerTree).unwrap();
+    assert_eq!(all_pairs.pad_pairs.len(), 6);
+    assert_eq!(steiner.pad_pairs.len(), 4);
+}
@@ -0,0 +1,207 @@
+use std::collections::{BTreeSet, HashSet};
+
+use gui_leptos_tauri_lib::{
+    grid::{Grid, LayerPoint, Net, PadKind, Point},
+    naive_route::naive_route,
+};
+
+fn net(c: char) -> Net {
+    Net::Character {
+        pad_c: c,
+        route_c: c.to_ascii_lowercase(),
+    }
+}
+
+fn assert_round_trip(grid: &Grid) {
+    let text = grid.to_string();
+    let parsed = Grid::from_string(&text);
+    assert_eq!(
+        (parsed.width, parsed.height, parsed.layers),
+        (grid.width, grid.height, grid.layers),
+        "{}",
+        text
+    );
+    assert_eq!(parsed.pads, grid.pads, "{}", text);
+    assert_eq!(parsed.pad_kinds, grid.pad_kinds, "{}", text);
+    assert_eq!(parsed.traces, grid.traces, "{}", text);
+    assert_eq!(parsed.diagonal_traces, grid.diagonal_traces, "{}", text);
+    assert_eq!(parsed.vias, grid.vias, "{}", text);
+    assert_eq!(
+        parsed.design_rules.net_to_class, grid.design_rules.net_to_class,
+        "{}",
+        text
+    );
+    assert_eq!(parsed.keepouts, grid.keepouts, "{}", text);
+    // the same grid is always written the same way
+    assert_eq!(parsed.to_string(), text);
+}
+
+#[test]
+fn test_routed_grid_round_trip() {
+    let grid = Grid::from_string(
+        "\
+##########
+#        #
+#  A  B  #
+#        #
+# B  #  A#
+##########",
+    );
+    let grid = naive_route(grid).unwrap();
+    assert_round_trip(&grid);
+}
+
+#[test]
+fn test_parse_solution() {
+    // pads of a routed net are part of its trace, other nets cross in directives
+    let grid = Grid::from_string(
+        "\
+#######
+#AaaaA#
+#  #  #
+#BbbbB#
+#######
+trace B 2 0 0
+",
+    );
+    assert_eq!(grid.pads[&net('A')].len(), 2);
+    assert_eq!(
+        grid.traces[&net('A')],
+        (0..5).map(|x| LayerPoint::new(x, 0, 0)).collect()
+    );
+    assert_eq!(grid.traces[&net('B')].len(), 6);
+    assert!(grid.traces[&net('B')].contains(&LayerPoint::new(2, 0, 0)));
+    assert_eq!(grid.keepouts.len(), 1);
+}
+
+#[test]
+fn test_multi_layer_round_trip() {
+    // net A crosses a wall of SMD pads of net B on the bottom layer
+    let mut grid = Grid::new_with_layers(5, 5, 2);
+    grid.insert_pad(net('A'), Point { x: 0, y: 2 });
+    grid.insert_pad(net('A'), Point { x: 4, y: 2 });
+    for y in 0..5 {
+        grid.insert_smd_pad(net('B'), Point { x: 2, y }, 0);
+    }
+    grid.traces.insert(
+        net('A'),
+        (0..2)
+            .map(|x| LayerPoint::new(x, 2, 0))
+            .chain((1..4).map(|x| LayerPoint::new(x, 2, 1)))
+            .chain((3..5).map(|x| LayerPoint::new(x, 2, 0)))
+            .collect(),
+    );
+    grid.vias.insert(
+        net('A'),
+        HashSet::from([Point { x: 1, y: 2 }, Point { x: 3, y: 2 }]),
+    );
+    grid.traces
+        .insert(net('B'), (0..5).map(|y| LayerPoint::new(2, y, 0)).collect());
+    let text = grid.to_string();
+    assert!(text.contains("layers 2\n"), "{}", text);
+    assert!(text.contains("smd B 2 0 0\n"), "{}", text);
+    assert!(text.contains("via A 1 2\n"), "{}", text);
+    assert_round_trip(&grid);
+    assert_eq!(
+        Grid::from_string(&text).pad_kind(Point { x: 2, y: 1 }),
+        PadKind::Smd { layer: 0 }
+    );
+}
+
+#[test]
+fn test_diagonal_round_trip() {
+    let mut grid = Grid::from_string(
+        "\
+######
+#A   #
+#    #
+#   A#
+######",
+    );
+    grid.traces.insert(
+        net('A'),
+        HashSet::from([LayerPoint::new(0, 0, 0), LayerPoint::new(3, 2, 0)]),
+    );
+    grid.diagonal_traces
+        .insert(net('A'), (0..2).map(|i| LayerPoint::new(i, i, 0)).collect());
+    assert!(grid.to_string().contains("diagonal A 1 1 0\n"));
+    assert_round_trip(&grid);
+}
+
+#[test]
+fn test_bare_pads_round_trip() {
+    // a trace that only touches one of its pads, and one that touches none of them
+    let mut grid = Grid::from_string(
+        "\
+#######
+#A   A#
+#B   B#
+#######",
+    );
+    grid.traces.insert(
+        net('A'),
+        HashSet::from([LayerPoint::new(0, 0, 0), LayerPoint::new(1, 0, 0)]),
+    );
+    grid.traces
+        .insert(net('B'), HashSet::from([LayerPoint::new(0, 1, 0)]));
+    let text = grid.to_string();
+    assert!(text.contains("bare A 4 0 0\n"), "{}", text);
+    assert!(text.contains("trace B 0 1 0\n"), "{}", text);
+    assert_round_trip(&grid);
+}
+
+#[test]
+fn test_directives_round_trip() {
+    let mut grid = Grid::from_string(
+        "\
+########
+#A    A#
+# #  B #
+#B     #
+########",
+    );
+    grid.apply_directive("keepout rect 2 0 3 0").unwrap();
+    grid.apply_directive("keepout polygon 3 1 4 1 4 2 layers 0")
+        .unwrap();
+    grid.apply_directive("class A power").unwrap();
+    assert_round_trip(&grid);
+    let parsed = Grid::from_string(&grid.to_string());
+    assert_eq!(
+        parsed.keepouts[2].layers,
+        Some(BTreeSet::from([0])),
+        "{}",
+        grid.to_string()
+    );
+}
+
+#[test]
+fn test_other_route_character() {
+    // the traces of a net that does not route with the lowercase pad character are written as directives
+    let other = Net::Character {
+        pad_c: 'A',
+        route_c: '*',
+    };
+    let mut grid = Grid::new(4, 1);
+    grid.insert_pad(other.clone(), Point { x: 0, y: 0 });
+    grid.insert_pad(other.clone(), Point { x: 3, y: 0 });
+    grid.traces
+        .insert(other, (0..4).map(|x| LayerPoint::new(x, 0, 0)).collect());
+    let text = grid.to_string();
+    assert!(text.starts_with("######\n#A  A#\n######\n"), "{}", text);
+    assert!(text.contains("trace A 1 0 0\n"), "{}", text);
+    let parsed = Grid::from_string(&text);
+    assert_eq!(
+        parsed.traces[&net('A')],
+        grid.traces.values().next().unwrap().clone()
+    );
+}
+
+#[test]
+fn test_invalid_text() {
+    let error = Grid::try_from_string("#####\n#A A#\n#####\nclass A\n").unwrap_err();
+    assert!(error.contains("class A"), "{}", error);
+    assert!(Grid::try_from_string("#####\n#A A#\n#####\ntrace A 9 0 0\n").is_err());
+    assert!(Grid::try_from_string("#####\n#A A#\n").is_err());
+    assert!(Grid::try_from_string("#####\n#A A #\n#####").is_err());
+    assert!(Grid::try_from_string("#####\n#A A#\n#####\nlayers 2\n").is_ok());
+}
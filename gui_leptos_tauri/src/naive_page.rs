@@ -1,6 +1,6 @@
 use leptos::{prelude::*, tachys::view, task::spawn_local};
 use serde_wasm_bindgen::{from_value, to_value};
-use shared::interface_types::{ClickCellArgs, Color, ColorGrid, MyResult, NewGridArgs};
+use shared::interface_types::{ClickCellArgs, Color, ColorGrid, ExportBoardArgs, MyResult, NewGridArgs, OpenBoardArgs, ProblemFileArgs, SetNetClassArgs};
 use wasm_bindgen::prelude::*;
 
 use crate::app::invoke;
@@ -15,6 +15,11 @@ pub fn NaivePage() -> impl IntoView {
     let (r, set_r) = signal::<u8>(0);
     let (g, set_g) = signal::<u8>(0);
     let (b, set_b) = signal::<u8>(0);
+    let (net_class, set_net_class) = signal::<String>("signal".to_string());
+    let (board_path, set_board_path) = signal::<String>(String::new());
+    let (pitch, set_pitch) = signal::<f64>(2.54);
+    let (export_path, set_export_path) = signal::<String>(String::new());
+    let (problem_path, set_problem_path) = signal::<String>(String::new());
     fn create_new_grid(rows: usize, cols: usize)-> ColorGrid{
         let color_grid = (0..rows)
             .map(|_| {
@@ -53,6 +58,90 @@ pub fn NaivePage() -> impl IntoView {
         });        
     };
 
+    let open_board = move |_| {
+        spawn_local(async move{
+            set_err_msg.set("opening board...".to_string());
+            let args = OpenBoardArgs {
+                path: board_path.get(),
+                pitch: pitch.get(),
+            };
+            let args = to_value(&args).unwrap();
+            let result = invoke("naive_open_board", args).await;
+            let result = from_value::<MyResult<ColorGrid, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(grid) => {
+                    set_err_msg.set(format!("Opened {}", board_path.get()));
+                    set_grid.set(grid);
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
+
+    let export_board = move |_| {
+        spawn_local(async move{
+            set_err_msg.set("exporting board...".to_string());
+            let args = ExportBoardArgs {
+                path: export_path.get(),
+            };
+            let args = to_value(&args).unwrap();
+            let result = invoke("naive_export_board", args).await;
+            let result = from_value::<MyResult<String, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(message) => {
+                    set_err_msg.set(message);
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
+
+    // the save commands answer with a message, e.g. "Wrote two_nets.json"
+    let save_problem_file = move |command: &'static str| {
+        spawn_local(async move{
+            set_err_msg.set("saving...".to_string());
+            let args = ProblemFileArgs {
+                path: problem_path.get(),
+            };
+            let args = to_value(&args).unwrap();
+            let result = invoke(command, args).await;
+            let result = from_value::<MyResult<String, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(message) => {
+                    set_err_msg.set(message);
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
+
+    let load_problem = move |_| {
+        spawn_local(async move{
+            set_err_msg.set("loading problem...".to_string());
+            let args = ProblemFileArgs {
+                path: problem_path.get(),
+            };
+            let args = to_value(&args).unwrap();
+            let result = invoke("naive_load_problem", args).await;
+            let result = from_value::<MyResult<ColorGrid, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(grid) => {
+                    set_err_msg.set(format!("Loaded {}", problem_path.get()));
+                    set_grid.set(grid);
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
+
     let on_cell_click = move |x: usize, y: usize| {
         // You can replace this with a Signal or any effect/handler you prefer
         set_err_msg.set(format!(
@@ -148,6 +237,37 @@ pub fn NaivePage() -> impl IntoView {
                         }
                     }
                 />
+                <select
+                    prop:value=net_class
+                    on:change=move |ev| set_net_class.set(event_target_value(&ev))
+                >
+                    <option value="power">"power"</option>
+                    <option value="signal">"signal"</option>
+                    <option value="high_speed">"high_speed"</option>
+                </select>
+                <button style="width: 6rem;" on:click=move |_| {
+                    // applies the selected class to the net of the current color
+                    spawn_local(async move {
+                        let args = to_value(&SetNetClassArgs {
+                            r: r.get(),
+                            g: g.get(),
+                            b: b.get(),
+                            netClass: net_class.get(),
+                        })
+                        .unwrap();
+                        let result = invoke("naive_set_net_class", args).await;
+                        let result = from_value::<MyResult<ColorGrid, String>>(result).unwrap();
+                        match result {
+                            MyResult::Ok(grid) => {
+                                set_grid.set(grid);
+                                set_err_msg.set(format!("Net class set to {}", net_class.get()));
+                            }
+                            MyResult::Err(err) => {
+                                set_err_msg.set(err);
+                            }
+                        }
+                    });
+                }>"Set Class"</button>
                 <button style="width: 6rem;" on:click=move |_| {
                     set_err_msg.set("Routing...".to_string());
                     spawn_local(async move {                        
@@ -164,6 +284,67 @@ pub fn NaivePage() -> impl IntoView {
                         }
                     });
                 }>"Route"</button>
+                <button style="width: 6rem;" on:click=move |_| {
+                    set_err_msg.set("Routing with PathFinder...".to_string());
+                    spawn_local(async move {
+                        let result = invoke("naive_do_pathfinder_route", JsValue::NULL).await;
+                        let result = from_value::<MyResult<ColorGrid, String>>(result).unwrap();
+                        match result {
+                            MyResult::Ok(grid) => {
+                                set_grid.set(grid);
+                                set_err_msg.set("PathFinder routing completed".to_string());
+                            }
+                            MyResult::Err(err) => {
+                                set_err_msg.set(err);
+                            }
+                        }
+                    });
+                }>"PathFinder"</button>
+            </div>
+            <div>
+                <label>"Board: "</label>
+                <input
+                style="width: 20rem;"
+                    type="text"
+                    placeholder="path to a .kicad_pcb or .dsn file"
+                    prop:value=board_path
+                    on:input=move |ev| set_board_path.set(event_target_value(&ev))
+                />
+                <label style="margin-left: 1rem;">"Pitch (mm): "</label>
+                <input
+                style="width: 4rem;"
+                    type="number"
+                    min="0"
+                    step="0.01"
+                    prop:value=pitch
+                    on:input=move |ev| {
+                        if let Ok(val) = event_target_value(&ev).parse::<f64>() {
+                            set_pitch.set(val);
+                        }
+                    }
+                />
+                <button style="width: 6rem;" on:click=open_board>"Open Board"</button>
+                <input
+                style="width: 20rem; margin-left: 1rem;"
+                    type="text"
+                    placeholder="path of the routed copy (.kicad_pcb) or session (.ses)"
+                    prop:value=export_path
+                    on:input=move |ev| set_export_path.set(event_target_value(&ev))
+                />
+                <button style="width: 6rem;" on:click=export_board>"Export Board"</button>
+            </div>
+            <div>
+                <label>"Problem: "</label>
+                <input
+                style="width: 20rem;"
+                    type="text"
+                    placeholder="path to a .json or .toml problem file"
+                    prop:value=problem_path
+                    on:input=move |ev| set_problem_path.set(event_target_value(&ev))
+                />
+                <button style="width: 6rem;" on:click=load_problem>"Load"</button>
+                <button style="width: 6rem;" on:click=move |_| save_problem_file("naive_save_problem")>"Save Problem"</button>
+                <button style="width: 6rem;" on:click=move |_| save_problem_file("naive_save_solution")>"Save Solution"</button>
             </div>
 
             <div style="
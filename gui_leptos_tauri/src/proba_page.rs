@@ -1,6 +1,6 @@
 use leptos::{prelude::*, tachys::view, task::spawn_local};
 use serde_wasm_bindgen::{from_value, to_value};
-use shared::interface_types::{ClickCellArgs, Color, ColorGrid, MyResult, NewGridArgs, UpdatePosteriorArgs};
+use shared::interface_types::{ClickCellArgs, Color, ColorGrid, ExportBoardArgs, MyResult, NewGridArgs, OpenBoardArgs, ProblemFileArgs, ProbaInitArgs, ProbaInspection, ProbaSnapshotInfo, ProbaSolveResult, RestoreSnapshotArgs, UpdatePosteriorArgs};
 use wasm_bindgen::prelude::*;
 
 use crate::app::invoke;
@@ -19,6 +19,11 @@ pub fn ProbaPage() -> impl IntoView {
 
     let (score_weight, set_score_weight) = signal::<f64>(0.5);
     let (opportunity_cost_weight, set_opportunity_cost_weight) = signal::<f64>(0.5);
+    let (seed, set_seed) = signal::<u64>(0);
+    let (board_path, set_board_path) = signal::<String>(String::new());
+    let (pitch, set_pitch) = signal::<f64>(2.54);
+    let (export_path, set_export_path) = signal::<String>(String::new());
+    let (problem_path, set_problem_path) = signal::<String>(String::new());
     fn create_new_grid(rows: usize, cols: usize)-> ColorGrid{
         let color_grid = (0..rows)
             .map(|_| {
@@ -34,6 +39,10 @@ pub fn ProbaPage() -> impl IntoView {
         ColorGrid{grid: color_grid}
     }
     let (grid, set_grid) = signal::<ColorGrid>(create_new_grid(rows.get(), cols.get()));
+    // the traces covering each cell, shown when hovering it after Heatmap
+    let (tooltips, set_tooltips) = signal::<Vec<Vec<String>>>(Vec::new());
+    let (snapshots, set_snapshots) = signal::<Vec<ProbaSnapshotInfo>>(Vec::new());
+    let (snapshot_id, set_snapshot_id) = signal::<usize>(0);
     
     let proba_clear = move |e| {
         spawn_local(async move{
@@ -49,6 +58,7 @@ pub fn ProbaPage() -> impl IntoView {
             match result {
                 MyResult::Ok(grid) => {                    
                     set_grid.set(grid);
+                    set_tooltips.set(Vec::new());
                 }
                 MyResult::Err(err) => {
                     set_err_msg.set(err);
@@ -57,6 +67,91 @@ pub fn ProbaPage() -> impl IntoView {
         });        
     };
 
+    let open_board = move |_| {
+        spawn_local(async move{
+            set_err_msg.set("opening board...".to_string());
+            let args = OpenBoardArgs {
+                path: board_path.get(),
+                pitch: pitch.get(),
+            };
+            let args = to_value(&args).unwrap();
+            let result = invoke("proba_open_board", args).await;
+            let result = from_value::<MyResult<ColorGrid, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(grid) => {
+                    set_err_msg.set(format!("Opened {}", board_path.get()));
+                    set_grid.set(grid);
+                    set_tooltips.set(Vec::new());
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
+
+    let export_board = move |_| {
+        spawn_local(async move{
+            set_err_msg.set("exporting board...".to_string());
+            let args = ExportBoardArgs {
+                path: export_path.get(),
+            };
+            let args = to_value(&args).unwrap();
+            let result = invoke("proba_export_board", args).await;
+            let result = from_value::<MyResult<String, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(message) => {
+                    set_err_msg.set(message);
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
+
+    // the save commands answer with a message, e.g. "Wrote two_nets.json"
+    let save_problem_file = move |command: &'static str| {
+        spawn_local(async move{
+            set_err_msg.set("saving...".to_string());
+            let args = ProblemFileArgs {
+                path: problem_path.get(),
+            };
+            let args = to_value(&args).unwrap();
+            let result = invoke(command, args).await;
+            let result = from_value::<MyResult<String, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(message) => {
+                    set_err_msg.set(message);
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
+
+    let load_problem = move |_| {
+        spawn_local(async move{
+            set_err_msg.set("loading problem...".to_string());
+            let args = ProblemFileArgs {
+                path: problem_path.get(),
+            };
+            let args = to_value(&args).unwrap();
+            let result = invoke("proba_load_problem", args).await;
+            let result = from_value::<MyResult<ColorGrid, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(grid) => {
+                    set_err_msg.set(format!("Loaded {}", problem_path.get()));
+                    set_grid.set(grid);
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
+
     let on_cell_click = move |x: usize, y: usize| {
         // You can replace this with a Signal or any effect/handler you prefer
         set_err_msg.set(format!(
@@ -94,7 +189,8 @@ pub fn ProbaPage() -> impl IntoView {
     let on_init_click = move |_| {
         spawn_local(async move{
             set_err_msg.set("initializing iteration 1".to_string());
-            let result = invoke("proba_init", JsValue::NULL).await;
+            let args = to_value(&ProbaInitArgs { seed: Some(seed.get()) }).unwrap();
+            let result = invoke("proba_init", args).await;
             set_err_msg.set("iteration 1 initialized".to_string());
             let result = from_value::<MyResult<ColorGrid, String>>(result).unwrap();
             match result {
@@ -181,6 +277,126 @@ pub fn ProbaPage() -> impl IntoView {
             }
         });
     };
+    let on_solve_click = move |_| {
+        spawn_local(async move{
+            set_err_msg.set("solving".to_string());
+            let args = UpdatePosteriorArgs {
+                scoreWeight: score_weight.get(),
+                opportunityCostWeight: opportunity_cost_weight.get(),
+            };
+            let args = to_value(&args).unwrap();
+            let result = invoke("proba_solve", args).await;
+            let result = from_value::<MyResult<ProbaSolveResult, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(ProbaSolveResult { grid, report }) => {
+                    set_grid.set(grid);
+                    set_err_msg.set(report);
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
+    let on_extract_click = move |_| {
+        spawn_local(async move{
+            set_err_msg.set("extracting routing".to_string());
+            let result = invoke("proba_extract", JsValue::NULL).await;
+            let result = from_value::<MyResult<ColorGrid, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(grid) => {
+                    set_grid.set(grid);
+                    set_err_msg.set("Routing extracted".to_string());
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
+    let on_heatmap_click = move |_| {
+        spawn_local(async move{
+            set_err_msg.set("inspecting".to_string());
+            let result = invoke("proba_inspect", JsValue::NULL).await;
+            let result = from_value::<MyResult<ProbaInspection, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(inspection) => {
+                    // the heatmap of the net with the selected color
+                    let color = Color{r: r.get(), g: g.get(), b: b.get()};
+                    let Some(heatmap) = inspection.heatmaps.iter().find(|heatmap| heatmap.route_color == color) else {
+                        set_err_msg.set("No net has the selected color".to_string());
+                        return;
+                    };
+                    let heatmap_grid = heatmap.occupancy.iter().map(|row| {
+                        row.iter().map(|p| Color {
+                            r: (color.r as f64 * p + 255.0 * (1.0 - p)) as u8,
+                            g: (color.g as f64 * p + 255.0 * (1.0 - p)) as u8,
+                            b: (color.b as f64 * p + 255.0 * (1.0 - p)) as u8,
+                        }).collect()
+                    }).collect();
+                    let mut cell_tooltips = vec![vec![String::new(); heatmap.occupancy.first().map_or(0, |row| row.len())]; heatmap.occupancy.len()];
+                    for trace in inspection.traces.iter() {
+                        for (x, y) in trace.cells.iter() {
+                            cell_tooltips[*y][*x].push_str(&format!(
+                                "trace {} (net {}, iteration {}): score {:.3}, prior {:.3}, posterior {}, collides with {:?}\n",
+                                trace.trace_id,
+                                trace.net_id,
+                                trace.iteration,
+                                trace.score,
+                                trace.prior,
+                                trace.posterior.map_or("-".to_string(), |p| format!("{:.3}", p)),
+                                trace.colliding_traces,
+                            ));
+                        }
+                    }
+                    set_grid.set(ColorGrid{grid: heatmap_grid});
+                    set_tooltips.set(cell_tooltips);
+                    set_err_msg.set(format!("Heatmap of net {}", heatmap.net_id));
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
+    let on_snapshots_click = move |_| {
+        spawn_local(async move{
+            let result = invoke("proba_list_snapshots", JsValue::NULL).await;
+            let result = from_value::<MyResult<Vec<ProbaSnapshotInfo>, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(list) => {
+                    set_err_msg.set(format!("{} snapshots", list.len()));
+                    if let Some(last) = list.last() {
+                        set_snapshot_id.set(last.id);
+                    }
+                    set_snapshots.set(list);
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
+    let on_restore_click = move |_| {
+        spawn_local(async move{
+            let args = RestoreSnapshotArgs {
+                snapshotId: snapshot_id.get(),
+            };
+            let args = to_value(&args).unwrap();
+            let result = invoke("proba_restore_snapshot", args).await;
+            let result = from_value::<MyResult<ColorGrid, String>>(result).unwrap();
+            match result {
+                MyResult::Ok(grid) => {
+                    set_grid.set(grid);
+                    set_tooltips.set(Vec::new());
+                    set_err_msg.set(format!("Restored snapshot {}", snapshot_id.get()));
+                }
+                MyResult::Err(err) => {
+                    set_err_msg.set(err);
+                }
+            }
+        });
+    };
     view! {
         <div style="padding: 1rem;">
             <div style="margin-bottom: 1rem;">
@@ -211,6 +427,51 @@ pub fn ProbaPage() -> impl IntoView {
                 />
                 <button style="width: 6rem;" on:click=proba_clear>"Clear"</button>
             </div>
+            <div>
+                <label>"Board: "</label>
+                <input
+                style="width: 20rem;"
+                    type="text"
+                    placeholder="path to a .kicad_pcb or .dsn file"
+                    prop:value=board_path
+                    on:input=move |ev| set_board_path.set(event_target_value(&ev))
+                />
+                <label style="margin-left: 1rem;">"Pitch (mm): "</label>
+                <input
+                style="width: 4rem;"
+                    type="number"
+                    min="0"
+                    step="0.01"
+                    prop:value=pitch
+                    on:input=move |ev| {
+                        if let Ok(val) = event_target_value(&ev).parse::<f64>() {
+                            set_pitch.set(val);
+                        }
+                    }
+                />
+                <button style="width: 6rem;" on:click=open_board>"Open Board"</button>
+                <input
+                style="width: 20rem; margin-left: 1rem;"
+                    type="text"
+                    placeholder="path of the routed copy (.kicad_pcb) or session (.ses)"
+                    prop:value=export_path
+                    on:input=move |ev| set_export_path.set(event_target_value(&ev))
+                />
+                <button style="width: 6rem;" on:click=export_board>"Export Board"</button>
+            </div>
+            <div>
+                <label>"Problem: "</label>
+                <input
+                style="width: 20rem;"
+                    type="text"
+                    placeholder="path to a .json or .toml problem file"
+                    prop:value=problem_path
+                    on:input=move |ev| set_problem_path.set(event_target_value(&ev))
+                />
+                <button style="width: 6rem;" on:click=load_problem>"Load"</button>
+                <button style="width: 6rem;" on:click=move |_| save_problem_file("proba_save_problem")>"Save Problem"</button>
+                <button style="width: 6rem;" on:click=move |_| save_problem_file("proba_save_solution")>"Save Solution"</button>
+            </div>
             <div>
                 <button style="width: 6rem;" on:click=move|_| on_color_click(Color{r: 255, g: 0, b: 0})>"Red"</button>
                 <button style="width: 6rem;" on:click=move|_| on_color_click(Color{r: 0, g: 255, b: 0})>"Green"</button>
@@ -221,13 +482,52 @@ pub fn ProbaPage() -> impl IntoView {
                 <span style:background-color=move||{format!("#{:06x}", ((r.get() as u32) << 16) + ((g.get() as u32) << 8) + b.get() as u32)}>"Color"</span>
             </div>
             <div>
+                <label>"Seed: "</label>
+                <input
+                style="width: 5rem;"
+                    type="number"
+                    min="0"
+                    prop:value=seed
+                    on:input=move |ev| {
+                        if let Ok(val) = event_target_value(&ev).parse::<u64>() {
+                            set_seed.set(val);
+                        }
+                    }
+                />
                 <button style="width: 6rem;" on:click=on_init_click>"Init"</button>
                 <button style="width: 8rem;" on:click=on_update_posterior_click>"Update Posterior"</button>
                 <button style="width: 8rem;" on:click=on_sample_click>"Sample New Traces"</button>
+                <button style="width: 6rem;" on:click=on_solve_click>"Solve"</button>
             </div>
             <div>
                 <button style="width: 6rem;" on:click=on_next_net_click>"Next Net"</button>
                 <button style="width: 6rem;" on:click=on_next_pair_click>"Next Pair"</button>
+                <button style="width: 6rem;" on:click=on_extract_click>"Extract"</button>
+                <button style="width: 6rem;" on:click=on_heatmap_click>"Heatmap"</button>
+            </div>
+            <div>
+                <button style="width: 6rem;" on:click=on_snapshots_click>"Snapshots"</button>
+                <select
+                    on:change=move |ev| {
+                        if let Ok(val) = event_target_value(&ev).parse::<usize>() {
+                            set_snapshot_id.set(val);
+                        }
+                    }
+                    prop:value=move || snapshot_id.get().to_string()
+                >
+                    {move || snapshots.get().into_iter().map(|snapshot| {
+                        let text = format!(
+                            "#{} {} (iteration {}, {} traces{})",
+                            snapshot.id,
+                            snapshot.label,
+                            snapshot.iteration,
+                            snapshot.num_traces,
+                            snapshot.parent.map_or(String::new(), |parent| format!(", from #{}", parent)),
+                        );
+                        view! { <option value=snapshot.id.to_string()>{text}</option> }
+                    }).collect::<Vec<_>>()}
+                </select>
+                <button style="width: 6rem;" on:click=on_restore_click>"Restore"</button>
                 
             </div>
             <div>                
@@ -294,6 +594,7 @@ pub fn ProbaPage() -> impl IntoView {
                                                 "width: 16px; height: 16px; background-color: {}; border: 2px solid black; display: inline",
                                                 color_str,
                                             )
+                                            title=move || tooltips.with(|tooltips| tooltips.get(y).and_then(|row| row.get(x)).cloned().unwrap_or_default())
                                             on:click=move |_| on_cell_click(x, y)
                                             ></div>
                                         }